@@ -8,12 +8,14 @@
 //! - GET  /info      - Get instance info (port, PID, project name)
 //! - POST /eval      - Execute JavaScript in the webview context
 //! - POST /exec      - Execute a named API method with JSON params
+//! - POST /project/validate - Check a project file: `{"path": "..."}` or `{"project": {...}}`
 
 use std::net::TcpListener;
 use std::sync::{Arc, Mutex};
 use std::collections::HashMap;
 use std::time::{Duration, Instant};
 use tauri::WebviewWindow;
+use crate::project_schema;
 
 /// Result from JS eval, stored by callback
 struct EvalResult {
@@ -310,9 +312,29 @@ fn handle_request(
             respond_json(request, 200, &resp_body);
         }
 
+        ("POST", "/project/validate") => {
+            let body = read_body(&mut request);
+            let parsed: serde_json::Value = serde_json::from_str(&body).unwrap_or_default();
+            // Files are read by the backend, so this works without the webview
+            let report = if let Some(path) = parsed["path"].as_str() {
+                match crate::commands::read_project(std::path::Path::new(path)) {
+                    Ok(json) => project_schema::validate(&json),
+                    Err(e) => project_schema::ValidationReport::unreadable(format!("Failed to load file: {}", e)),
+                }
+            } else if parsed["project"].is_object() {
+                project_schema::validate(&parsed["project"].to_string())
+            } else {
+                respond_json(request, 400,
+                    r#"{"success":false,"error":"Send {\"path\":\"...\"} or {\"project\":{...}}"}"#);
+                return;
+            };
+            let status = if report.valid { 200 } else { 422 };
+            respond_json(request, status, &serde_json::to_string(&report).unwrap_or_default());
+        }
+
         _ => {
             respond_json(request, 404,
-                r#"{"error":"Not found","endpoints":["/health","/info","/eval"]}"#);
+                r#"{"error":"Not found","endpoints":["/health","/info","/eval","/project/validate"]}"#);
         }
     }
 }
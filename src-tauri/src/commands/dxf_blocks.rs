@@ -0,0 +1,240 @@
+//! BLOCK definitions, INSERT placement and ATTRIB values.
+
+use super::dxf_hatch::BlockHatch;
+use super::dxf_import::{self, DxfImportOptions, Importer};
+use super::dxf_transform::{self, Transform};
+use super::dxf_xdata::DxfEntityData;
+use super::shape::{PointData, ShapeData};
+use super::{dxf_ocs, dxf_text};
+use dxf::entities::{Attribute, Insert};
+use dxf::{Block, Drawing};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// Block definition as exchanged with the frontend. Shapes are in block
+/// coordinates; nested INSERTs are kept as "block" shapes.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BlockData {
+    pub name: String,
+    pub base_point: PointData,
+    pub shapes: Vec<ShapeData>,
+}
+
+/// Group created for an exploded INSERT, carrying its ATTRIB values
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GroupData {
+    pub id: String,
+    pub block_name: String,
+    /// ATTRIB tag -> value (empty when attributes were imported as text)
+    pub attributes: BTreeMap<String, String>,
+}
+
+/// Convert all user block definitions, keeping the application data of their
+/// entities. Layout blocks and anonymous dimension blocks are skipped; their
+/// content is imported through other paths. Hatches come from `block_hatches`
+/// and go first so they draw behind the other shapes.
+pub fn read_blocks(
+    drawing: &Drawing,
+    entity_data: &HashMap<String, DxfEntityData>,
+    block_hatches: &HashMap<String, Vec<BlockHatch>>,
+) -> Vec<BlockData> {
+    let options = DxfImportOptions {
+        explode_blocks: false,
+        ..Default::default()
+    };
+    drawing
+        .blocks()
+        .filter(|block| !is_layout_block(&block.name) && !is_dimension_block(&block.name))
+        .map(|block| {
+            let mut shapes: Vec<ShapeData> = block_hatches
+                .get(&block.name.to_ascii_uppercase())
+                .into_iter()
+                .flatten()
+                .flat_map(|hatch| hatch.shapes.iter().cloned())
+                .collect();
+            let mut importer = Importer::new(drawing, &options).with_entity_data(entity_data);
+            importer.push_entities(block.entities.iter());
+            shapes.extend(importer.finish().shapes);
+            BlockData {
+                name: block.name.clone(),
+                base_point: dxf_import::to_point_data(&block.base_point),
+                shapes,
+            }
+        })
+        .collect()
+}
+
+pub fn find_block<'a>(drawing: &'a Drawing, name: &str) -> Option<&'a Block> {
+    drawing
+        .blocks()
+        .find(|block| block.name.eq_ignore_ascii_case(name))
+}
+
+/// `*Model_Space` and `*Paper_Space*` hold layout content, not reusable blocks
+pub fn is_layout_block(name: &str) -> bool {
+    let upper = name.to_ascii_uppercase();
+    upper == "*MODEL_SPACE" || upper.starts_with("*PAPER_SPACE")
+}
+
+/// Anonymous `*D` blocks hold the graphics of DIMENSION entities
+pub fn is_dimension_block(name: &str) -> bool {
+    name.len() > 2 && name.starts_with("*D") && name[2..].chars().all(|c| c.is_ascii_digit())
+}
+
+/// Row and column index of every MINSERT array cell. Counts below 1, as plain
+/// INSERTs may have, count as 1.
+pub fn array_cells(insert: &Insert) -> impl Iterator<Item = (i16, i16)> {
+    let rows = insert.row_count.max(1);
+    let columns = insert.column_count.max(1);
+    (0..rows).flat_map(move |row| (0..columns).map(move |column| (row, column)))
+}
+
+/// Block coordinates to the INSERT's parent coordinates for one array cell.
+/// Array spacing is measured along the rotated axes but is not scaled.
+pub fn insert_transform(insert: &Insert, block: &Block, row: i16, column: i16) -> Transform {
+    dxf_ocs::insert_ocs(insert)
+        .unwrap_or_else(Transform::identity)
+        .then_after(&Transform::translate(insert.location.x, insert.location.y))
+        .then_after(&Transform::rotate(insert.rotation.to_radians()))
+        .then_after(&Transform::translate(
+            f64::from(column) * insert.column_spacing,
+            f64::from(row) * insert.row_spacing,
+        ))
+        .then_after(&Transform::scale(
+            insert.x_scale_factor,
+            insert.y_scale_factor,
+        ))
+        .then_after(&Transform::translate(
+            -block.base_point.x,
+            -block.base_point.y,
+        ))
+}
+
+/// INSERT kept as a reference to its block definition, for one array cell
+pub fn block_reference(insert: &Insert, row: i16, column: i16) -> ShapeData {
+    let rotation = insert.rotation.to_radians();
+    let offset = Transform::rotate(rotation).apply_vector(PointData {
+        x: f64::from(column) * insert.column_spacing,
+        y: f64::from(row) * insert.row_spacing,
+    });
+    let mut shape = ShapeData {
+        shape_type: "block".to_string(),
+        block_name: Some(insert.name.clone()),
+        position: Some(PointData {
+            x: insert.location.x + offset.x,
+            y: insert.location.y + offset.y,
+        }),
+        rotation: Some(rotation),
+        scale_x: Some(insert.x_scale_factor),
+        scale_y: Some(insert.y_scale_factor),
+        ..Default::default()
+    };
+    // Mirrored inserts become a negative Y scale with the rotation adjusted
+    if let Some(ocs) = dxf_ocs::insert_ocs(insert) {
+        dxf_transform::transform_shape(&mut shape, &ocs);
+    }
+    shape
+}
+
+/// ATTRIB tag -> value pairs of an INSERT
+pub fn attribute_map(insert: &Insert) -> BTreeMap<String, String> {
+    insert
+        .attributes()
+        .map(|a| (a.attribute_tag.clone(), dxf_text::parse_text(&a.value).text))
+        .collect()
+}
+
+/// ATTRIB value as a text shape (already in the INSERT's parent coordinates)
+pub fn attribute_to_shape(attribute: &Attribute) -> Option<ShapeData> {
+    let text = dxf_text::parse_text(&attribute.value).text;
+    if text.trim().is_empty() {
+        return None;
+    }
+    Some(ShapeData {
+        shape_type: "text".to_string(),
+        position: Some(dxf_import::to_point_data(&attribute.location)),
+        text: Some(text),
+        font_size: Some(attribute.text_height),
+        rotation: Some(attribute.rotation.to_radians()),
+        alignment: Some("left".to_string()),
+        vertical_alignment: Some("bottom".to_string()),
+        text_style_name: Some(attribute.text_style_name.clone()),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dxf::entities::{Circle, Entity, EntityType};
+    use dxf::enums::AcadVersion;
+    use dxf::Point;
+    use std::fs;
+
+    #[test]
+    fn blocks_and_inserts_round_trip() {
+        let mut drawing = Drawing::new();
+        drawing.header.version = AcadVersion::R2018;
+        let circle = Circle::new(Point::new(5.0, 5.0, 0.0), 2.0);
+        drawing.add_block(Block {
+            name: "Door".to_string(),
+            base_point: Point::new(5.0, 0.0, 0.0),
+            entities: vec![Entity::new(EntityType::Circle(circle))],
+            ..Default::default()
+        });
+        let insert = Insert {
+            name: "Door".to_string(),
+            location: Point::new(100.0, 50.0, 0.0),
+            rotation: 30.0,
+            x_scale_factor: 2.0,
+            y_scale_factor: 3.0,
+            ..Default::default()
+        };
+        let mut insert = Entity::new(EntityType::Insert(insert));
+        if let EntityType::Insert(insert) = &mut insert.specific {
+            let attribute = Attribute {
+                attribute_tag: "NUMBER".to_string(),
+                value: "D-12".to_string(),
+                ..Default::default()
+            };
+            insert.add_attribute(&mut drawing, attribute);
+        }
+        drawing.add_entity(insert);
+        let path = std::env::temp_dir().join(format!("blocks_{}.dxf", std::process::id()));
+        drawing.save_file(path.to_str().unwrap()).unwrap();
+        let drawing = Drawing::load_file(path.to_str().unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let blocks = read_blocks(&drawing, &HashMap::new(), &HashMap::new());
+        let block = blocks.iter().find(|b| b.name == "Door").unwrap();
+        assert_eq!((block.base_point.x, block.base_point.y), (5.0, 0.0));
+        assert_eq!(block.shapes.len(), 1);
+        assert_eq!(block.shapes[0].shape_type, "circle");
+        assert_eq!(block.shapes[0].radius, Some(2.0));
+
+        let insert = drawing
+            .entities()
+            .find_map(|e| match &e.specific {
+                EntityType::Insert(insert) => Some(insert),
+                _ => None,
+            })
+            .unwrap();
+        let reference = block_reference(insert, 0, 0);
+        let position = reference.position.unwrap();
+        assert_eq!((position.x, position.y), (100.0, 50.0));
+        assert!((reference.rotation.unwrap() - 30f64.to_radians()).abs() < 1e-12);
+        assert_eq!(
+            (reference.scale_x, reference.scale_y),
+            (Some(2.0), Some(3.0))
+        );
+        let attributes = attribute_map(insert);
+        assert_eq!(attributes.get("NUMBER").map(String::as_str), Some("D-12"));
+
+        // Exploded, the circle centre lands where the INSERT puts the block base point
+        let block = find_block(&drawing, "door").unwrap();
+        let centre = insert_transform(insert, block, 0, 0).apply(PointData { x: 5.0, y: 5.0 });
+        let (sin, cos) = 30f64.to_radians().sin_cos();
+        assert!((centre.x - (100.0 - 15.0 * sin)).abs() < 1e-9);
+        assert!((centre.y - (50.0 + 15.0 * cos)).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,110 @@
+//! AutoCAD Color Index (ACI) and true colour conversion to `#rrggbb` strings.
+
+use dxf::Color;
+
+/// Brightness levels of the ACI hue rows, indexed by `(index % 10) / 2`
+const ACI_LEVELS: [f64; 5] = [1.0, 0.8, 0.6, 0.5, 0.3];
+
+/// Fixed colours 1-9 and the grey ramp 250-255
+const ACI_FIXED: [u32; 9] = [
+    0xFF0000, 0xFFFF00, 0x00FF00, 0x00FFFF, 0x0000FF, 0xFF00FF, 0xFFFFFF, 0x808080, 0xC0C0C0,
+];
+const ACI_GREYS: [u32; 6] = [0x545454, 0x767676, 0x989898, 0xBABABA, 0xDCDCDC, 0xFFFFFF];
+
+/// RGB value of an ACI index (1-255). Indices 10-249 are 24 hues in 15° steps, each
+/// with five brightness levels in a saturated and a half-saturated variant.
+pub fn aci_to_rgb(index: u8) -> u32 {
+    match index {
+        1..=9 => ACI_FIXED[index as usize - 1],
+        10..=249 => {
+            let hue = f64::from((index - 10) / 10) * 15.0;
+            let level = ACI_LEVELS[((index % 10) / 2) as usize];
+            let pastel = index % 2 == 1;
+            let channel = |offset: f64| {
+                // Saturated HSV channel intensity for this hue, in 0..=1
+                let h = (hue + offset).rem_euclid(360.0);
+                let c = match h {
+                    h if h < 60.0 => 1.0,
+                    h if h < 120.0 => (120.0 - h) / 60.0,
+                    h if h < 240.0 => 0.0,
+                    h if h < 300.0 => (h - 240.0) / 60.0,
+                    _ => 1.0,
+                };
+                let c = if pastel { 0.5 + 0.5 * c } else { c };
+                (255.0 * level * c + 1e-6).floor() as u32
+            };
+            (channel(0.0) << 16) | (channel(-120.0) << 8) | channel(-240.0)
+        }
+        250..=255 => ACI_GREYS[index as usize - 250],
+        _ => 0xFFFFFF,
+    }
+}
+
+/// Closest ACI index (1-255) for an RGB value
+pub fn nearest_aci(rgb: u32) -> u8 {
+    let distance = |other: u32| {
+        let d = |shift: u32| {
+            let a = ((rgb >> shift) & 0xFF) as i32;
+            let b = ((other >> shift) & 0xFF) as i32;
+            (a - b) * (a - b)
+        };
+        d(16) + d(8) + d(0)
+    };
+    (1..=255u8)
+        .min_by_key(|index| distance(aci_to_rgb(*index)))
+        .unwrap_or(7)
+}
+
+/// Format an RGB value as `#rrggbb`
+pub fn to_hex(rgb: u32) -> String {
+    format!("#{:06x}", rgb & 0xFF_FFFF)
+}
+
+/// Parse `#rrggbb` or `#rgb` into an RGB value
+pub fn from_hex(hex: &str) -> Option<u32> {
+    let hex = hex.trim().trim_start_matches('#');
+    match hex.len() {
+        6 => u32::from_str_radix(hex, 16).ok(),
+        3 => {
+            let short = u32::from_str_radix(hex, 16).ok()?;
+            let (r, g, b) = ((short >> 8) & 0xF, (short >> 4) & 0xF, short & 0xF);
+            Some(((r * 0x11) << 16) | ((g * 0x11) << 8) | (b * 0x11))
+        }
+        _ => None,
+    }
+}
+
+/// Resolve an entity colour to `#rrggbb`. ByLayer / ByBlock resolve to `None`.
+pub fn entity_color(color: &Color, true_color: i32) -> Option<String> {
+    if true_color > 0 {
+        return Some(to_hex(true_color as u32));
+    }
+    color
+        .index()
+        .filter(|i| (1..=255).contains(i))
+        .map(|i| to_hex(aci_to_rgb(i)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_round_trip() {
+        for index in 1..=255u8 {
+            let rgb = aci_to_rgb(index);
+            assert_eq!(aci_to_rgb(nearest_aci(rgb)), rgb, "ACI {}", index);
+            assert_eq!(from_hex(&to_hex(rgb)), Some(rgb));
+        }
+        assert_eq!(from_hex("#1a2"), Some(0x11AA22));
+        assert_eq!(
+            entity_color(&Color::from_index(1), 0).as_deref(),
+            Some("#ff0000")
+        );
+        assert_eq!(
+            entity_color(&Color::by_layer(), 0x123456).as_deref(),
+            Some("#123456")
+        );
+        assert_eq!(entity_color(&Color::by_block(), 0), None);
+    }
+}
@@ -0,0 +1,706 @@
+//! DIMENSION entities and the DIMSTYLE table.
+//!
+//! Dimensions are imported from their definition points and exported as real
+//! DIMENSION entities whose graphics live in an anonymous `*D` block, so other
+//! CAD packages keep them associative.
+
+use super::dxf_export::to_point;
+use super::dxf_import::to_point_data;
+use super::dxf_text::{self, sanitize_table_name};
+use super::shape::{PointData, ShapeData};
+use dxf::entities::{
+    AngularThreePointDimension, Arc, Circle, DiameterDimension, DimensionBase, Entity, EntityType,
+    Line, RadialDimension, RotatedDimension, Solid,
+};
+use dxf::enums::DimensionType;
+use dxf::tables::DimStyle;
+use dxf::{Block, Drawing};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::{FRAC_PI_2, PI, TAU};
+
+/// Dimension style as exchanged with the frontend (`DimensionStyle` in
+/// `src/types/dimension.ts`). Sizes are in drawing units.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DimensionStyleData {
+    /// "filled" | "open" | "dot" | "tick" | "none"
+    pub arrow_type: String,
+    pub arrow_size: f64,
+    pub extension_line_gap: f64,
+    pub extension_line_overshoot: f64,
+    pub text_height: f64,
+    pub precision: u8,
+}
+
+impl Default for DimensionStyleData {
+    /// Mirrors `DEFAULT_DIMENSION_STYLE` in `src/constants/cadDefaults.ts`
+    fn default() -> Self {
+        Self {
+            arrow_type: "tick".to_string(),
+            arrow_size: 2.5,
+            extension_line_gap: 1.5,
+            extension_line_overshoot: 2.5,
+            text_height: 2.5,
+            precision: 0,
+        }
+    }
+}
+
+/// Convert a DIMENSION entity into a "dimension" shape. The dxf crate reads
+/// aligned dimensions as rotated ones of the aligned type; rotated dimensions at
+/// an angle other than horizontal or vertical are imported as aligned too.
+pub fn dimension_to_shape(specific: &EntityType, drawing: &Drawing) -> Option<ShapeData> {
+    let (base, dimension_type, linear_direction, points, offset) = match specific {
+        EntityType::RotatedDimension(dim) => {
+            let p1 = to_point_data(&dim.definition_point_2);
+            let p2 = to_point_data(&dim.definition_point_3);
+            let on_line = to_point_data(&dim.dimension_base.definition_point_1);
+            let angle = dim.rotation_angle.rem_euclid(180.0);
+            let aligned = dim.dimension_base.dimension_type == DimensionType::Aligned;
+            let (dimension_type, direction, offset) = if aligned {
+                ("aligned", None, offset_from_line(p1, p2, on_line))
+            } else if angle < 1e-6 || 180.0 - angle < 1e-6 {
+                ("linear", Some("horizontal"), on_line.y - p1.y)
+            } else if (angle - 90.0).abs() < 1e-6 {
+                ("linear", Some("vertical"), on_line.x - p1.x)
+            } else {
+                ("aligned", None, offset_from_line(p1, p2, on_line))
+            };
+            (
+                &dim.dimension_base,
+                dimension_type,
+                direction,
+                vec![p1, p2],
+                offset,
+            )
+        }
+        EntityType::RadialDimension(dim) => {
+            let center = to_point_data(&dim.dimension_base.definition_point_1);
+            let on_curve = to_point_data(&dim.definition_point_2);
+            (
+                &dim.dimension_base,
+                "radius",
+                None,
+                vec![center, on_curve],
+                0.0,
+            )
+        }
+        EntityType::DiameterDimension(dim) => {
+            // Group 15 is on the curve, group 10 diametrically opposite
+            let on_curve = to_point_data(&dim.definition_point_2);
+            let opposite = to_point_data(&dim.dimension_base.definition_point_1);
+            let center = midpoint(on_curve, opposite);
+            (
+                &dim.dimension_base,
+                "diameter",
+                None,
+                vec![center, on_curve],
+                0.0,
+            )
+        }
+        EntityType::AngularThreePointDimension(dim) => {
+            let vertex = to_point_data(&dim.definition_point_4);
+            let on_arc = to_point_data(&dim.dimension_base.definition_point_1);
+            (
+                &dim.dimension_base,
+                "angular",
+                None,
+                vec![
+                    vertex,
+                    to_point_data(&dim.definition_point_2),
+                    to_point_data(&dim.definition_point_3),
+                ],
+                distance(vertex, on_arc),
+            )
+        }
+        _ => return None,
+    };
+
+    let style = drawing
+        .dim_styles()
+        .find(|s| s.name.eq_ignore_ascii_case(&base.dimension_style_name))
+        .map(read_dim_style)
+        .unwrap_or_default();
+
+    let mut shape = ShapeData {
+        shape_type: "dimension".to_string(),
+        dimension_type: Some(dimension_type.to_string()),
+        linear_direction: linear_direction.map(str::to_string),
+        points: Some(points),
+        dimension_line_offset: Some(offset),
+        dimension_style_name: Some(base.dimension_style_name.clone()),
+        ..Default::default()
+    };
+
+    let geometry = DimensionGeometry::new(&shape, &style)?;
+    let measured = format_value(geometry.measurement, dimension_type, style.precision);
+    // An empty override or "<>" shows the measurement; "<>" inside text is replaced by it
+    let raw = base.text.trim();
+    let overridden = !raw.is_empty() && raw != "<>";
+    shape.value = Some(if overridden {
+        dxf_text::parse_mtext(&raw.replace("<>", &measured), style.text_height).text
+    } else {
+        measured
+    });
+    shape.value_overridden = Some(overridden);
+    if base.is_at_user_defined_location {
+        let text = to_point_data(&base.text_mid_point);
+        shape.text_offset = Some(PointData {
+            x: text.x - geometry.text_position.x,
+            y: text.y - geometry.text_position.y,
+        });
+    }
+    shape.dimension_style = Some(style);
+    Some(shape)
+}
+
+/// DIMSTYLE sizes multiplied by `DIMSCALE`. A tick size (`DIMTSZ`) replaces arrowheads.
+fn read_dim_style(style: &DimStyle) -> DimensionStyleData {
+    let scale = if style.dimensioning_scale_factor > 0.0 {
+        style.dimensioning_scale_factor
+    } else {
+        1.0
+    };
+    let tick = style.dimensioning_tick_size > 0.0;
+    DimensionStyleData {
+        arrow_type: if tick { "tick" } else { "filled" }.to_string(),
+        arrow_size: scale
+            * if tick {
+                style.dimensioning_tick_size
+            } else {
+                style.dimensioning_arrow_size
+            },
+        extension_line_gap: scale * style.dimension_extension_line_offset,
+        extension_line_overshoot: scale * style.dimension_extension_line_extension,
+        text_height: scale * style.dimensioning_text_height,
+        precision: style.dimension_unit_tolerance_decimal_places.clamp(0, 8) as u8,
+    }
+}
+
+/// Write the DIMSTYLE entry and the anonymous `*D` block for a dimension shape
+/// and return the DIMENSION entity referencing them
+pub fn shape_to_dimension(shape: &ShapeData, drawing: &mut Drawing) -> Option<EntityType> {
+    let style = shape.dimension_style.clone().unwrap_or_default();
+    let geometry = DimensionGeometry::new(shape, &style)?;
+    let dimension_type = shape.dimension_type.as_deref()?;
+    let points = shape.points.as_deref()?;
+
+    let measured = format_value(geometry.measurement, dimension_type, style.precision);
+    let value = shape.value.clone().unwrap_or_else(|| measured.clone());
+    let overridden = shape.value_overridden.unwrap_or(false) && value != measured;
+    let text_position = match shape.text_offset {
+        Some(offset) => PointData {
+            x: geometry.text_position.x + offset.x,
+            y: geometry.text_position.y + offset.y,
+        },
+        None => geometry.text_position,
+    };
+
+    let block_name = next_dimension_block_name(drawing);
+    let mut entities = geometry.entities(&style);
+    let text = ShapeData {
+        shape_type: "text".to_string(),
+        position: Some(text_position),
+        text: Some(value.clone()),
+        font_size: Some(style.text_height),
+        rotation: Some(geometry.text_angle),
+        alignment: Some("center".to_string()),
+        vertical_alignment: Some("middle".to_string()),
+        ..Default::default()
+    };
+    if let Some(mtext) = dxf_text::shape_to_mtext(&text, drawing) {
+        entities.push(Entity::new(EntityType::MText(mtext)));
+    }
+    // Block contents inherit the DIMENSION's layer and colour
+    for entity in &mut entities {
+        entity.common.layer = "0".to_string();
+    }
+    let mut block = Block {
+        name: block_name.clone(),
+        layer: "0".to_string(),
+        entities,
+        ..Default::default()
+    };
+    block.set_is_anonymous(true);
+    drawing.add_block(block);
+
+    let mut base = DimensionBase {
+        block_name,
+        text_mid_point: to_point(text_position),
+        actual_measurement: geometry.measurement,
+        text: if overridden { value } else { String::new() },
+        dimension_style_name: ensure_dim_style(
+            drawing,
+            shape.dimension_style_name.as_deref(),
+            &style,
+        ),
+        is_at_user_defined_location: shape.text_offset.is_some(),
+        ..Default::default()
+    };
+
+    let (p1, p2) = (*points.first()?, *points.get(1)?);
+    let specific = match dimension_type {
+        "linear" | "aligned" => {
+            base.definition_point_1 = to_point(geometry.dimension_line.1);
+            match shape.linear_direction.as_deref() {
+                Some(direction) if dimension_type == "linear" => {
+                    base.dimension_type = DimensionType::RotatedHorizontalOrVertical;
+                    EntityType::RotatedDimension(RotatedDimension {
+                        dimension_base: base,
+                        definition_point_2: to_point(p1),
+                        definition_point_3: to_point(p2),
+                        rotation_angle: if direction == "vertical" { 90.0 } else { 0.0 },
+                        ..Default::default()
+                    })
+                }
+                // The dxf crate writes aligned dimensions as rotated ones of the
+                // aligned type, rotated along the measured line
+                _ => {
+                    base.dimension_type = DimensionType::Aligned;
+                    EntityType::RotatedDimension(RotatedDimension {
+                        dimension_base: base,
+                        definition_point_2: to_point(p1),
+                        definition_point_3: to_point(p2),
+                        rotation_angle: (p2.y - p1.y).atan2(p2.x - p1.x).to_degrees(),
+                        ..Default::default()
+                    })
+                }
+            }
+        }
+        "radius" => {
+            base.dimension_type = DimensionType::Radius;
+            base.definition_point_1 = to_point(p1);
+            EntityType::RadialDimension(RadialDimension {
+                dimension_base: base,
+                definition_point_2: to_point(p2),
+                ..Default::default()
+            })
+        }
+        "diameter" => {
+            base.dimension_type = DimensionType::Diameter;
+            base.definition_point_1 = to_point(geometry.dimension_line.0);
+            EntityType::DiameterDimension(DiameterDimension {
+                dimension_base: base,
+                definition_point_2: to_point(p2),
+                ..Default::default()
+            })
+        }
+        "angular" => {
+            let arc = geometry.arc?;
+            let mid = (arc.start_angle + arc.end_angle) / 2.0;
+            base.dimension_type = DimensionType::AngularThreePoint;
+            base.definition_point_1 = to_point(point_at_angle(arc.center, mid, arc.radius));
+            EntityType::AngularThreePointDimension(AngularThreePointDimension {
+                dimension_base: base,
+                definition_point_2: to_point(p2),
+                definition_point_3: to_point(points.get(2).copied()?),
+                definition_point_4: to_point(p1),
+                ..Default::default()
+            })
+        }
+        _ => return None,
+    };
+    Some(specific)
+}
+
+/// Reuse a DIMSTYLE with the same name and values, otherwise add one with a
+/// numbered suffix
+fn ensure_dim_style(
+    drawing: &mut Drawing,
+    name: Option<&str>,
+    style: &DimensionStyleData,
+) -> String {
+    let base_name = sanitize_table_name(name.filter(|n| !n.is_empty()).unwrap_or("Standard"));
+    let mut candidate = base_name.clone();
+    for suffix in 2.. {
+        match drawing
+            .dim_styles()
+            .find(|s| s.name.eq_ignore_ascii_case(&candidate))
+        {
+            Some(existing) if read_dim_style(existing) == as_written(style) => {
+                return existing.name.clone()
+            }
+            Some(_) => candidate = format!("{}-{}", base_name, suffix),
+            None => break,
+        }
+    }
+
+    let tick = style.arrow_type == "tick";
+    drawing.add_dim_style(DimStyle {
+        name: candidate.clone(),
+        dimensioning_scale_factor: 1.0,
+        dimensioning_arrow_size: style.arrow_size,
+        dimensioning_tick_size: if tick { style.arrow_size } else { 0.0 },
+        dimension_extension_line_offset: style.extension_line_gap,
+        dimension_extension_line_extension: style.extension_line_overshoot,
+        dimensioning_text_height: style.text_height,
+        dimension_unit_tolerance_decimal_places: i16::from(style.precision),
+        ..Default::default()
+    });
+    candidate
+}
+
+/// DIMSTYLE only distinguishes ticks from arrowheads; other terminators are
+/// drawn in the `*D` block
+fn as_written(style: &DimensionStyleData) -> DimensionStyleData {
+    DimensionStyleData {
+        arrow_type: if style.arrow_type == "tick" {
+            "tick"
+        } else {
+            "filled"
+        }
+        .to_string(),
+        ..style.clone()
+    }
+}
+
+/// Anonymous dimension blocks are numbered `*D1`, `*D2`, ...
+fn next_dimension_block_name(drawing: &Drawing) -> String {
+    let last = drawing
+        .blocks()
+        .filter_map(|b| b.name.strip_prefix("*D")?.parse::<u32>().ok())
+        .max()
+        .unwrap_or(0);
+    format!("*D{}", last + 1)
+}
+
+struct DimensionArc {
+    center: PointData,
+    radius: f64,
+    start_angle: f64,
+    end_angle: f64,
+}
+
+/// Dimension graphics, computed the same way as `src/engine/geometry/DimensionUtils.ts`
+struct DimensionGeometry {
+    measurement: f64,
+    /// Dimension line start and end (unused for angular dimensions)
+    dimension_line: (PointData, PointData),
+    arc: Option<DimensionArc>,
+    extension_lines: Vec<(PointData, PointData)>,
+    /// Arrow tips with the direction they point in
+    arrows: Vec<(PointData, f64)>,
+    text_position: PointData,
+    text_angle: f64,
+}
+
+impl DimensionGeometry {
+    fn new(shape: &ShapeData, style: &DimensionStyleData) -> Option<Self> {
+        let points = shape.points.as_deref()?;
+        let (p1, p2) = (*points.first()?, *points.get(1)?);
+        let offset = shape.dimension_line_offset.unwrap_or(0.0);
+
+        let geometry = match shape.dimension_type.as_deref()? {
+            "linear" | "aligned" => {
+                let direction = if shape.dimension_type.as_deref() == Some("linear") {
+                    shape.linear_direction.as_deref()
+                } else {
+                    None
+                };
+                let (start, end, angle, measurement) = match direction {
+                    Some("horizontal") => (
+                        PointData {
+                            x: p1.x,
+                            y: p1.y + offset,
+                        },
+                        PointData {
+                            x: p2.x,
+                            y: p1.y + offset,
+                        },
+                        0.0,
+                        (p2.x - p1.x).abs(),
+                    ),
+                    Some("vertical") => (
+                        PointData {
+                            x: p1.x + offset,
+                            y: p1.y,
+                        },
+                        PointData {
+                            x: p1.x + offset,
+                            y: p2.y,
+                        },
+                        FRAC_PI_2,
+                        (p2.y - p1.y).abs(),
+                    ),
+                    _ => {
+                        let angle = angle_between(p1, p2);
+                        (
+                            point_at_angle(p1, angle + FRAC_PI_2, offset),
+                            point_at_angle(p2, angle + FRAC_PI_2, offset),
+                            angle,
+                            distance(p1, p2),
+                        )
+                    }
+                };
+                let extension = |from: PointData, to: PointData| {
+                    let angle = angle_between(from, to);
+                    (
+                        point_at_angle(from, angle, style.extension_line_gap),
+                        point_at_angle(to, angle, style.extension_line_overshoot),
+                    )
+                };
+                let line_angle = angle_between(start, end);
+                Self {
+                    measurement,
+                    dimension_line: (start, end),
+                    arc: None,
+                    extension_lines: vec![extension(p1, start), extension(p2, end)],
+                    arrows: vec![(start, line_angle + PI), (end, line_angle)],
+                    text_position: midpoint(start, end),
+                    text_angle: readable(angle),
+                }
+            }
+            "radius" => Self {
+                measurement: distance(p1, p2),
+                dimension_line: (p1, p2),
+                arc: None,
+                extension_lines: Vec::new(),
+                arrows: vec![(p2, angle_between(p1, p2))],
+                text_position: midpoint(p1, p2),
+                text_angle: readable(angle_between(p1, p2)),
+            },
+            "diameter" => {
+                let angle = angle_between(p1, p2);
+                let radius = distance(p1, p2);
+                let start = point_at_angle(p1, angle + PI, radius);
+                Self {
+                    measurement: 2.0 * radius,
+                    dimension_line: (start, p2),
+                    arc: None,
+                    extension_lines: Vec::new(),
+                    arrows: vec![(start, angle + PI), (p2, angle)],
+                    text_position: p1,
+                    text_angle: readable(angle),
+                }
+            }
+            "angular" => {
+                let p3 = *points.get(2)?;
+                let (mut start_angle, mut end_angle) =
+                    (angle_between(p1, p2), angle_between(p1, p3));
+                let mut sweep = (end_angle - start_angle).rem_euclid(TAU);
+                if sweep > PI {
+                    std::mem::swap(&mut start_angle, &mut end_angle);
+                    sweep = TAU - sweep;
+                }
+                let radius = offset.abs();
+                let mid = start_angle + sweep / 2.0;
+                let extension = |angle: f64| {
+                    (
+                        point_at_angle(p1, angle, style.extension_line_gap),
+                        point_at_angle(p1, angle, radius + style.extension_line_overshoot),
+                    )
+                };
+                Self {
+                    measurement: sweep.to_degrees(),
+                    dimension_line: (p1, p1),
+                    arc: Some(DimensionArc {
+                        center: p1,
+                        radius,
+                        start_angle,
+                        end_angle: start_angle + sweep,
+                    }),
+                    extension_lines: vec![extension(start_angle), extension(start_angle + sweep)],
+                    arrows: vec![
+                        (
+                            point_at_angle(p1, start_angle, radius),
+                            start_angle - FRAC_PI_2,
+                        ),
+                        (
+                            point_at_angle(p1, start_angle + sweep, radius),
+                            start_angle + sweep + FRAC_PI_2,
+                        ),
+                    ],
+                    text_position: point_at_angle(p1, mid, radius),
+                    text_angle: mid - FRAC_PI_2,
+                }
+            }
+            _ => return None,
+        };
+        Some(geometry)
+    }
+
+    /// Lines, arc and arrowheads for the `*D` block
+    fn entities(&self, style: &DimensionStyleData) -> Vec<Entity> {
+        let mut lines = self.extension_lines.clone();
+        if self.arc.is_none() {
+            lines.insert(0, self.dimension_line);
+        }
+        let mut entities: Vec<Entity> = lines.into_iter().map(|(a, b)| line(a, b)).collect();
+        if let Some(arc) = &self.arc {
+            entities.push(Entity::new(EntityType::Arc(Arc::new(
+                to_point(arc.center),
+                arc.radius,
+                arc.start_angle.to_degrees(),
+                arc.end_angle.to_degrees(),
+            ))));
+        }
+        for &(tip, angle) in &self.arrows {
+            entities.extend(arrowhead(tip, angle, style));
+        }
+        entities
+    }
+}
+
+/// Terminator at `tip` for a dimension line arriving in direction `angle`
+fn arrowhead(tip: PointData, angle: f64, style: &DimensionStyleData) -> Vec<Entity> {
+    let size = style.arrow_size;
+    // Wings at 30 degrees either side, like `calculateArrowPoints`
+    let left = point_at_angle(tip, angle + PI - PI / 6.0, size);
+    let right = point_at_angle(tip, angle + PI + PI / 6.0, size);
+    match style.arrow_type.as_str() {
+        "none" => Vec::new(),
+        "open" => vec![line(tip, left), line(tip, right)],
+        "dot" => vec![Entity::new(EntityType::Circle(Circle::new(
+            to_point(tip),
+            size / 4.0,
+        )))],
+        "tick" => {
+            let half = size / 2.0;
+            vec![line(
+                point_at_angle(tip, angle + PI / 4.0, half),
+                point_at_angle(tip, angle + PI + PI / 4.0, half),
+            )]
+        }
+        _ => vec![Entity::new(EntityType::Solid(Solid {
+            first_corner: to_point(tip),
+            second_corner: to_point(left),
+            third_corner: to_point(right),
+            fourth_corner: to_point(right),
+            ..Default::default()
+        }))],
+    }
+}
+
+fn line(a: PointData, b: PointData) -> Entity {
+    Entity::new(EntityType::Line(Line::new(to_point(a), to_point(b))))
+}
+
+fn format_value(value: f64, dimension_type: &str, precision: u8) -> String {
+    let precision = usize::from(precision);
+    if dimension_type == "angular" {
+        format!("{:.*}\u{B0}", precision, value)
+    } else {
+        format!("{:.*}", precision, value)
+    }
+}
+
+/// Signed distance of `p` from the line p1->p2, positive to the left
+fn offset_from_line(p1: PointData, p2: PointData, p: PointData) -> f64 {
+    let length = distance(p1, p2);
+    if length == 0.0 {
+        return 0.0;
+    }
+    ((p2.x - p1.x) * (p.y - p1.y) - (p2.y - p1.y) * (p.x - p1.x)) / length
+}
+
+/// Keep text from reading upside down; vertical text reads from the right
+fn readable(angle: f64) -> f64 {
+    let mut angle = (angle + PI).rem_euclid(TAU) - PI;
+    if angle >= FRAC_PI_2 {
+        angle -= PI;
+    }
+    if angle < -FRAC_PI_2 {
+        angle += PI;
+    }
+    angle
+}
+
+fn angle_between(a: PointData, b: PointData) -> f64 {
+    (b.y - a.y).atan2(b.x - a.x)
+}
+
+fn point_at_angle(origin: PointData, angle: f64, length: f64) -> PointData {
+    PointData {
+        x: origin.x + length * angle.cos(),
+        y: origin.y + length * angle.sin(),
+    }
+}
+
+fn distance(a: PointData, b: PointData) -> f64 {
+    (b.x - a.x).hypot(b.y - a.y)
+}
+
+fn midpoint(a: PointData, b: PointData) -> PointData {
+    PointData {
+        x: (a.x + b.x) / 2.0,
+        y: (a.y + b.y) / 2.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dxf::enums::AcadVersion;
+    use std::fs;
+
+    fn dimension(dimension_type: &str, points: &[(f64, f64)], offset: f64) -> ShapeData {
+        ShapeData {
+            shape_type: "dimension".to_string(),
+            dimension_type: Some(dimension_type.to_string()),
+            points: Some(points.iter().map(|&(x, y)| PointData { x, y }).collect()),
+            dimension_line_offset: Some(offset),
+            dimension_style: Some(DimensionStyleData {
+                precision: 2,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn dimensions_round_trip() {
+        let mut shapes = vec![
+            dimension("linear", &[(0.0, 0.0), (40.0, 10.0)], 15.0),
+            dimension("linear", &[(0.0, 0.0), (40.0, 10.0)], -5.0),
+            dimension("aligned", &[(0.0, 0.0), (30.0, 40.0)], 8.0),
+            dimension("radius", &[(5.0, 5.0), (15.0, 5.0)], 0.0),
+            dimension("diameter", &[(5.0, 5.0), (5.0, 17.0)], 0.0),
+            dimension("angular", &[(0.0, 0.0), (10.0, 0.0), (0.0, 10.0)], 20.0),
+        ];
+        shapes[0].linear_direction = Some("horizontal".to_string());
+        shapes[1].linear_direction = Some("vertical".to_string());
+        shapes[2].value = Some("Gap".to_string());
+        shapes[2].value_overridden = Some(true);
+        shapes[3].text_offset = Some(PointData { x: 2.0, y: -1.0 });
+
+        let mut drawing = Drawing::new();
+        drawing.header.version = AcadVersion::R2018;
+        for shape in &shapes {
+            let specific = shape_to_dimension(shape, &mut drawing).unwrap();
+            drawing.add_entity(Entity::new(specific));
+        }
+        let path = std::env::temp_dir().join(format!("dimensions_{}.dxf", std::process::id()));
+        drawing.save_file(path.to_str().unwrap()).unwrap();
+        let read = Drawing::load_file(path.to_str().unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let read: Vec<ShapeData> = read
+            .entities()
+            .filter_map(|e| dimension_to_shape(&e.specific, &read))
+            .collect();
+        assert_eq!(read.len(), shapes.len());
+        for (written, read) in shapes.iter().zip(&read) {
+            assert_eq!(read.dimension_type, written.dimension_type);
+            assert_eq!(read.linear_direction, written.linear_direction);
+            let offset = read.dimension_line_offset.unwrap();
+            assert!((offset - written.dimension_line_offset.unwrap()).abs() < 1e-9);
+            for (a, b) in read
+                .points
+                .as_ref()
+                .unwrap()
+                .iter()
+                .zip(written.points.as_ref().unwrap())
+            {
+                assert!((a.x - b.x).abs() < 1e-9 && (a.y - b.y).abs() < 1e-9);
+            }
+            assert_eq!(read.dimension_style, written.dimension_style);
+        }
+        assert_eq!(read[0].value.as_deref(), Some("40.00"));
+        assert_eq!(read[1].value.as_deref(), Some("10.00"));
+        assert_eq!(read[2].value.as_deref(), Some("Gap"));
+        assert_eq!(read[2].value_overridden, Some(true));
+        let text_offset = read[3].text_offset.unwrap();
+        assert!((text_offset.x - 2.0).abs() < 1e-9 && (text_offset.y + 1.0).abs() < 1e-9);
+        assert_eq!(read[4].value.as_deref(), Some("24.00"));
+    }
+}
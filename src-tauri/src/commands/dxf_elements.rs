@@ -0,0 +1,589 @@
+//! BIM elements (walls, slabs, beams, piles, gridlines, levels and spaces) for
+//! partners without BIM support. Each element is exported as the shapes it is
+//! drawn with: outlines, hatches, symbols and labels. All entities written for
+//! an element carry its type, id and properties as XDATA.
+
+use super::dxf_report::ConversionReport;
+use super::dxf_xdata::DxfEntityData;
+use super::shape::{PointData, ShapeData};
+use dxf::enums::AcadVersion;
+use std::f64::consts::{FRAC_1_SQRT_2, FRAC_PI_2, PI, TAU};
+
+/// Application name of the element XDATA
+pub const APPLICATION: &str = "OPEN_ND_STUDIO";
+
+/// XDATA strings are limited to 255 bytes
+const MAX_XDATA_STRING: usize = 255;
+
+/// Label height of elements without a font size
+const DEFAULT_FONT_SIZE: f64 = 250.0;
+
+/// Layer per element type. Hatches, labels and centerlines go on sub layers
+/// with the `-PATT`, `-IDEN` and `-CNTR` suffixes.
+const ELEMENT_LAYERS: [(&str, &str); 7] = [
+    ("wall", "A-WALL"),
+    ("slab", "S-SLAB"),
+    ("beam", "S-BEAM"),
+    ("pile", "S-PILE"),
+    ("gridline", "S-GRID"),
+    ("level", "A-LEVL"),
+    ("space", "A-AREA"),
+];
+
+/// Replace the element shapes by the shapes they are drawn with. Each element is
+/// reported once under its type; the shapes written for it are not reported
+/// again. With `element_layers` the shapes go on the layer of their element type
+/// instead of the element's layer.
+pub fn explode(
+    shapes: Vec<ShapeData>,
+    element_layers: bool,
+    version: AcadVersion,
+    report: &mut ConversionReport,
+) -> Vec<ShapeData> {
+    let mut exploded = Vec::with_capacity(shapes.len());
+    for shape in shapes {
+        let Some((_, layer)) = ELEMENT_LAYERS
+            .iter()
+            .find(|(shape_type, _)| *shape_type == shape.shape_type)
+        else {
+            exploded.push(shape);
+            continue;
+        };
+        let mut element = Element {
+            shape: &shape,
+            layer: element_layers.then_some(*layer),
+            dxf_data: element_data(&shape),
+            parts: Vec::new(),
+        };
+        let drawn = match shape.shape_type.as_str() {
+            "wall" | "beam" => element.linear(),
+            "slab" => element.slab(),
+            "pile" => element.pile(),
+            "gridline" => element.gridline(),
+            "level" => element.level(),
+            _ => element.space(),
+        };
+
+        let shape_type = shape.shape_type.as_str();
+        let has_hatch = element.parts.iter().any(|p| p.shape_type == "hatch");
+        match drawn {
+            None => report.skipped(
+                shape_type,
+                shape.id.clone(),
+                "missing or degenerate geometry",
+            ),
+            Some(()) if has_hatch && version < AcadVersion::R13 => report.approximated(
+                shape_type,
+                shape.id.clone(),
+                "hatch fills need R13 or later; only the outline was written",
+            ),
+            Some(()) => report.converted(shape_type),
+        }
+        if drawn.is_some() {
+            exploded.extend(element.parts);
+        }
+    }
+    exploded
+}
+
+/// XDATA of the source entity plus the element type, id and properties as
+/// `key=value` strings. The data of a previous export is replaced.
+fn element_data(shape: &ShapeData) -> DxfEntityData {
+    let mut data = shape.dxf_data.clone().unwrap_or_default();
+    let mut ours = false;
+    data.xdata.retain(|(code, value)| {
+        if *code == 1001 {
+            ours = value.trim().eq_ignore_ascii_case(APPLICATION);
+        }
+        !ours
+    });
+
+    data.xdata.push((1001, APPLICATION.to_string()));
+    let mut push = |key: &str, value: &str| {
+        let mut text = format!("{}={}", key, value);
+        if text.len() > MAX_XDATA_STRING {
+            let mut end = MAX_XDATA_STRING;
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            text.truncate(end);
+        }
+        data.xdata.push((1000, text));
+    };
+    push("element", &shape.shape_type);
+    if let Some(id) = &shape.id {
+        push("id", id);
+    }
+    for (key, value) in shape.attributes.iter().flatten() {
+        push(key, value);
+    }
+    data
+}
+
+/// Element being exploded, with the shapes drawn for it so far
+struct Element<'a> {
+    shape: &'a ShapeData,
+    /// Layer of the element type; `None` keeps the element's layer
+    layer: Option<&'static str>,
+    dxf_data: DxfEntityData,
+    parts: Vec<ShapeData>,
+}
+
+impl<'a> Element<'a> {
+    /// New shape with the element's appearance, on the layer for `suffix`
+    fn part(&mut self, shape_type: &str, suffix: &str) -> &mut ShapeData {
+        let source = self.shape;
+        let layer = match self.layer {
+            Some(layer) => Some(format!("{}{}", layer, suffix)),
+            None => source.layer.clone(),
+        };
+        self.parts.push(ShapeData {
+            id: source.id.clone(),
+            shape_type: shape_type.to_string(),
+            layer,
+            color: source.color.clone(),
+            line_type: source.line_type.clone(),
+            line_type_scale: source.line_type_scale,
+            element_type: Some(source.shape_type.clone()),
+            dxf_data: Some(self.dxf_data.clone()),
+            ..Default::default()
+        });
+        let index = self.parts.len() - 1;
+        &mut self.parts[index]
+    }
+
+    fn line(&mut self, start: PointData, end: PointData, suffix: &str) -> &mut ShapeData {
+        let line = self.part("line", suffix);
+        line.start = Some(start);
+        line.end = Some(end);
+        line
+    }
+
+    fn polyline(
+        &mut self,
+        points: Vec<PointData>,
+        bulges: Vec<f64>,
+        closed: bool,
+        suffix: &str,
+    ) -> &mut ShapeData {
+        let polyline = self.part("polyline", suffix);
+        polyline.points = Some(points);
+        polyline.bulge = Some(bulges);
+        polyline.closed = Some(closed);
+        polyline
+    }
+
+    fn circle(&mut self, center: PointData, radius: f64, suffix: &str) {
+        let circle = self.part("circle", suffix);
+        circle.center = Some(center);
+        circle.radius = Some(radius);
+    }
+
+    /// Hatch with the element's pattern, behind the outline
+    fn hatch(&mut self, points: Vec<PointData>, bulges: Vec<f64>) {
+        let source = self.shape;
+        let Some(pattern_type) = source.pattern_type.as_deref().filter(|p| *p != "none") else {
+            return;
+        };
+        let hatch = self.part("hatch", "-PATT");
+        hatch.points = Some(points);
+        hatch.bulge = Some(bulges);
+        hatch.closed = Some(true);
+        hatch.boundary_visible = Some(false);
+        hatch.pattern_type = Some(pattern_type.to_string());
+        hatch.pattern_angle = source.pattern_angle;
+        hatch.pattern_scale = source.pattern_scale;
+        hatch.custom_pattern_id = source.custom_pattern_id.clone();
+        if source.pattern_color.is_some() {
+            hatch.color = source.pattern_color.clone();
+        }
+    }
+
+    fn text(&mut self, text: &str, position: PointData, rotation: f64) -> &mut ShapeData {
+        let font_size = self.shape.font_size.unwrap_or(DEFAULT_FONT_SIZE);
+        let label = self.part("text", "-IDEN");
+        label.text = Some(text.to_string());
+        label.position = Some(position);
+        label.rotation = Some(rotation);
+        label.font_size = Some(font_size);
+        label.alignment = Some("center".to_string());
+        label.vertical_alignment = Some("middle".to_string());
+        label
+    }
+
+    fn label(&self) -> Option<&'a str> {
+        self.shape.text.as_deref().filter(|t| !t.trim().is_empty())
+    }
+
+    /// Wall or beam: outline around the (possibly curved) centerline, offset by
+    /// the justification
+    fn linear(&mut self) -> Option<()> {
+        let shape = self.shape;
+        let (start, end) = (shape.start?, shape.end?);
+        let thickness = shape.thickness.filter(|t| *t > 0.0)?;
+        let bulge = shape
+            .bulge
+            .as_ref()
+            .and_then(|b| b.first().copied())
+            .unwrap_or(0.0);
+        let centerline = Centerline::new(start, end, bulge)?;
+        let (left, right) = match shape.justification.as_deref() {
+            Some("left" | "top") => (thickness, 0.0),
+            Some("right" | "bottom") => (0.0, thickness),
+            _ => (thickness / 2.0, thickness / 2.0),
+        };
+        let outline = vec![
+            centerline.offset(start, left),
+            centerline.offset(end, left),
+            centerline.offset(end, -right),
+            centerline.offset(start, -right),
+        ];
+        // Concentric arcs over the same sweep share the bulge
+        let bulges = vec![bulge, 0.0, -bulge, 0.0];
+        self.hatch(outline.clone(), bulges.clone());
+        self.polyline(outline, bulges, true, "");
+        if shape.show_centerline == Some(true) {
+            let line = self.polyline(vec![start, end], vec![bulge], false, "-CNTR");
+            line.line_type = Some("dashdot".to_string());
+        }
+        if let Some(label) = self.label() {
+            let (position, angle) = centerline.midpoint();
+            self.text(label, position, readable(angle));
+        }
+        Some(())
+    }
+
+    fn slab(&mut self) -> Option<()> {
+        let points = self.shape.points.clone().filter(|p| p.len() >= 3)?;
+        let bulges = self.shape.bulge.clone().unwrap_or_default();
+        self.hatch(points.clone(), bulges.clone());
+        let center = centroid(&points);
+        self.polyline(points, bulges, true, "");
+        if let Some(label) = self.label() {
+            self.text(label, center, 0.0);
+        }
+        Some(())
+    }
+
+    /// Circle with an optional cross and the pile number below it
+    fn pile(&mut self) -> Option<()> {
+        let center = self.shape.position?;
+        let radius = self.shape.radius.filter(|r| *r > 0.0)?;
+        self.circle(center, radius, "");
+        if self.shape.show_cross == Some(true) {
+            let r = radius * FRAC_1_SQRT_2;
+            for (dx, dy) in [(r, r), (r, -r)] {
+                self.line(
+                    PointData {
+                        x: center.x - dx,
+                        y: center.y - dy,
+                    },
+                    PointData {
+                        x: center.x + dx,
+                        y: center.y + dy,
+                    },
+                    "",
+                );
+            }
+        }
+        if let Some(label) = self.label() {
+            let font_size = self.shape.font_size.unwrap_or(DEFAULT_FONT_SIZE);
+            let position = PointData {
+                x: center.x,
+                y: center.y - radius - font_size * 0.3,
+            };
+            self.text(label, position, 0.0).vertical_alignment = Some("top".to_string());
+        }
+        Some(())
+    }
+
+    /// Dash-dot line past both ends, with the label in a bubble at either or
+    /// both ends
+    fn gridline(&mut self) -> Option<()> {
+        let shape = self.shape;
+        let (start, end) = (shape.start?, shape.end?);
+        let direction = unit(start, end)?;
+        let extension = shape.extension.unwrap_or(0.0);
+        let beyond = |point: PointData, distance: f64| PointData {
+            x: point.x + direction.x * distance,
+            y: point.y + direction.y * distance,
+        };
+        let line = self.line(beyond(start, -extension), beyond(end, extension), "");
+        line.line_type.get_or_insert_with(|| "dashdot".to_string());
+
+        let radius = shape.bubble_radius.filter(|r| *r > 0.0);
+        if let (Some(radius), Some(label)) = (radius, self.label()) {
+            let (at_start, at_end) = match shape.bubble_position.as_deref() {
+                Some("start") => (true, false),
+                Some("both") => (true, true),
+                _ => (false, true),
+            };
+            let centers = [
+                at_start.then(|| beyond(start, -(extension + radius))),
+                at_end.then(|| beyond(end, extension + radius)),
+            ];
+            for center in centers.into_iter().flatten() {
+                self.circle(center, radius, "-IDEN");
+                self.text(label, center, 0.0);
+            }
+        }
+        Some(())
+    }
+
+    /// Dashed line with a filled triangle at the end and the level label
+    /// (and the `description` property) beside it
+    fn level(&mut self) -> Option<()> {
+        let shape = self.shape;
+        let (start, end) = (shape.start?, shape.end?);
+        let d = unit(start, end)?;
+        let line = self.line(start, end, "");
+        line.line_type.get_or_insert_with(|| "dashed".to_string());
+
+        let radius = shape.bubble_radius.unwrap_or(0.0);
+        let size = radius * 0.7;
+        if size > 0.0 {
+            let corner = |side: f64| PointData {
+                x: end.x + d.x * size - d.y * size * 0.4 * side,
+                y: end.y + d.y * size + d.x * size * 0.4 * side,
+            };
+            let marker = vec![end, corner(1.0), corner(-1.0)];
+            let fill = self.part("hatch", "-IDEN");
+            fill.points = Some(marker.clone());
+            fill.closed = Some(true);
+            fill.boundary_visible = Some(false);
+            fill.pattern_type = Some("solid".to_string());
+            self.polyline(marker, Vec::new(), true, "-IDEN");
+        }
+
+        let font_size = shape.font_size.unwrap_or(DEFAULT_FONT_SIZE);
+        let distance = size * 1.5 + radius * 0.3;
+        let position = PointData {
+            x: end.x + d.x * distance,
+            y: end.y + d.y * distance,
+        };
+        let angle = d.y.atan2(d.x);
+        let description = shape
+            .attributes
+            .as_ref()
+            .and_then(|attributes| attributes.get("description"))
+            .filter(|description| !description.trim().is_empty());
+        if let Some(label) = self.label() {
+            let text = self.text(label, position, angle);
+            text.alignment = Some("left".to_string());
+            text.vertical_alignment = Some("bottom".to_string());
+        }
+        if let Some(description) = description {
+            let text = self.text(description, position, angle);
+            text.alignment = Some("left".to_string());
+            text.vertical_alignment = Some("top".to_string());
+            text.font_size = Some(font_size * 0.8);
+        }
+        Some(())
+    }
+
+    /// Dashed boundary with the label; the translucent fill is not exported, as
+    /// an opaque hatch would cover the plan
+    fn space(&mut self) -> Option<()> {
+        let points = self.shape.points.clone().filter(|p| p.len() >= 3)?;
+        let center = self.shape.position.unwrap_or_else(|| centroid(&points));
+        let boundary = self.polyline(points, Vec::new(), true, "");
+        boundary
+            .line_type
+            .get_or_insert_with(|| "dashed".to_string());
+        if let Some(label) = self.label() {
+            self.text(label, center, 0.0).bold = Some(true);
+        }
+        Some(())
+    }
+}
+
+/// Straight or arc centerline of a wall or beam
+struct Centerline {
+    start: PointData,
+    end: PointData,
+    bulge: f64,
+    /// Arc centre and radius
+    arc: Option<(PointData, f64)>,
+}
+
+impl Centerline {
+    fn new(start: PointData, end: PointData, bulge: f64) -> Option<Self> {
+        let chord = unit(start, end)?;
+        let length = (end.x - start.x).hypot(end.y - start.y);
+        let arc = (bulge.abs() > 1e-9).then(|| {
+            // Distance from the chord midpoint to the centre, to the left for
+            // counter-clockwise arcs
+            let h = length * (1.0 - bulge * bulge) / (4.0 * bulge);
+            let center = PointData {
+                x: (start.x + end.x) / 2.0 - chord.y * h,
+                y: (start.y + end.y) / 2.0 + chord.x * h,
+            };
+            (center, (start.x - center.x).hypot(start.y - center.y))
+        });
+        Some(Self {
+            start,
+            end,
+            bulge,
+            arc,
+        })
+    }
+
+    /// `point` on the centerline moved `distance` to its left
+    fn offset(&self, point: PointData, distance: f64) -> PointData {
+        let normal = match self.arc {
+            // Counter-clockwise arcs have their centre on the left
+            Some((center, radius)) => {
+                let sign = self.bulge.signum() / radius;
+                PointData {
+                    x: (center.x - point.x) * sign,
+                    y: (center.y - point.y) * sign,
+                }
+            }
+            None => {
+                let d = unit(self.start, self.end).unwrap_or_default();
+                PointData { x: -d.y, y: d.x }
+            }
+        };
+        PointData {
+            x: point.x + normal.x * distance,
+            y: point.y + normal.y * distance,
+        }
+    }
+
+    /// Middle of the centerline and the chord direction
+    fn midpoint(&self) -> (PointData, f64) {
+        let d = unit(self.start, self.end).unwrap_or_default();
+        let length = (self.end.x - self.start.x).hypot(self.end.y - self.start.y);
+        // The arc bulges to the right of the chord by the sagitta
+        let sagitta = self.bulge * length / 2.0;
+        let position = PointData {
+            x: (self.start.x + self.end.x) / 2.0 + d.y * sagitta,
+            y: (self.start.y + self.end.y) / 2.0 - d.x * sagitta,
+        };
+        (position, d.y.atan2(d.x))
+    }
+}
+
+fn unit(start: PointData, end: PointData) -> Option<PointData> {
+    let length = (end.x - start.x).hypot(end.y - start.y);
+    (length > 1e-9).then(|| PointData {
+        x: (end.x - start.x) / length,
+        y: (end.y - start.y) / length,
+    })
+}
+
+/// Text angle turned so labels never read upside down
+fn readable(angle: f64) -> f64 {
+    let angle = angle.rem_euclid(TAU);
+    if angle > FRAC_PI_2 && angle <= 3.0 * FRAC_PI_2 {
+        angle - PI
+    } else if angle > 3.0 * FRAC_PI_2 {
+        angle - TAU
+    } else {
+        angle
+    }
+}
+
+/// Area centroid of a polygon, the vertex average when it has no area
+fn centroid(points: &[PointData]) -> PointData {
+    let mut area = 0.0;
+    let (mut x, mut y) = (0.0, 0.0);
+    for (i, a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        let cross = a.x * b.y - b.x * a.y;
+        area += cross;
+        x += (a.x + b.x) * cross;
+        y += (a.y + b.y) * cross;
+    }
+    if area.abs() < 1e-9 {
+        let n = points.len() as f64;
+        return PointData {
+            x: points.iter().map(|p| p.x).sum::<f64>() / n,
+            y: points.iter().map(|p| p.y).sum::<f64>() / n,
+        };
+    }
+    PointData {
+        x: x / (3.0 * area),
+        y: y / (3.0 * area),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::dxf_export::DxfExportOptions;
+    use super::super::dxf_file::{self, Content, ExportTables};
+    use super::super::dxf_import::{self, DxfImportOptions};
+    use super::super::test_support::temp_dir;
+    use super::*;
+    use dxf::Drawing;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn elements_round_trip_as_tagged_shapes() {
+        let wall = ShapeData {
+            id: Some("w1".to_string()),
+            shape_type: "wall".to_string(),
+            layer: Some("Walls".to_string()),
+            start: Some(PointData { x: 0.0, y: 0.0 }),
+            end: Some(PointData { x: 4000.0, y: 0.0 }),
+            thickness: Some(200.0),
+            pattern_type: Some("solid".to_string()),
+            text: Some("W1".to_string()),
+            attributes: Some(BTreeMap::from([("fire".to_string(), "EI60".to_string())])),
+            ..Default::default()
+        };
+        let pile = ShapeData {
+            id: Some("p1".to_string()),
+            shape_type: "pile".to_string(),
+            position: Some(PointData { x: 0.0, y: 2000.0 }),
+            radius: Some(300.0),
+            ..Default::default()
+        };
+        let dir = temp_dir("dxf_elements_round_trip");
+        let path = dir.join("elements.dxf");
+        let path = path.to_str().unwrap();
+        let content = Content {
+            model: vec![wall, pile],
+            ..Default::default()
+        };
+        let options = DxfExportOptions {
+            element_layers: true,
+            ..Default::default()
+        };
+        dxf_file::write_file(path, content, &[], &ExportTables::default(), &options).unwrap();
+        let drawing = Drawing::load_file(path).unwrap();
+        let options = DxfImportOptions {
+            detect_origin: false,
+            ..Default::default()
+        };
+        let document = dxf_import::import_drawing(path, &drawing, &options, &()).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let tagged = |shape: &ShapeData| -> Vec<String> {
+            let xdata = &shape.dxf_data.as_ref().unwrap().xdata;
+            assert_eq!(xdata[0], (1001, APPLICATION.to_string()));
+            xdata[1..].iter().map(|(_, value)| value.clone()).collect()
+        };
+        let placed: Vec<_> = document
+            .shapes
+            .iter()
+            .map(|s| (s.shape_type.as_str(), s.layer.as_deref().unwrap_or("")))
+            .collect();
+        assert_eq!(
+            placed,
+            [
+                ("hatch", "A-WALL-PATT"),
+                ("polyline", "A-WALL"),
+                ("text", "A-WALL-IDEN"),
+                ("circle", "S-PILE"),
+            ]
+        );
+        for shape in &document.shapes[..3] {
+            assert_eq!(tagged(shape), ["element=wall", "id=w1", "fire=EI60"]);
+        }
+        assert_eq!(tagged(&document.shapes[3]), ["element=pile", "id=p1"]);
+        let outline = document.shapes[1].points.as_ref().unwrap();
+        let ys: Vec<f64> = outline.iter().map(|p| p.y).collect();
+        assert_eq!(ys, [100.0, 100.0, -100.0, -100.0]);
+    }
+}
@@ -0,0 +1,417 @@
+//! Conversion of exchange shapes into DXF entities.
+
+use super::dxf_report::ConversionReport;
+use super::shape::{PointData, ShapeData};
+use super::{dxf_color, dxf_dimensions, dxf_image, dxf_linetypes, dxf_text, dxf_xdata};
+use dxf::entities::{
+    Arc, Circle, Ellipse, Entity, EntityType, Line, LwPolyline, ModelPoint, Spline,
+};
+use dxf::enums::{AcadVersion, DrawingUnits, Units};
+use dxf::{Color, Drawing, LwPolylineVertex, Point, Vector};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::f64::consts::{FRAC_PI_2, PI};
+
+/// Export settings chosen in the export dialog
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DxfExportOptions {
+    /// "R12" | "R13" | "R14" | "R2000" | "R2004" | "R2007" | "R2010" | "R2013" | "R2018"
+    pub version: String,
+    /// Length unit the file is written in: "mm" | "cm" | "m" | "in" | "ft" | "ft-in".
+    /// Project coordinates are millimetres and are scaled to this unit.
+    pub units: String,
+    pub binary: bool,
+    /// Decimal places for coordinates and `$LUPREC`; `None` keeps full precision
+    pub precision: Option<u8>,
+    /// Project base point (as returned by `import_dxf`) added back to model space
+    /// coordinates, in project millimetres
+    pub origin: Option<PointData>,
+    /// Put the shapes of BIM elements on one layer per element type (A-WALL,
+    /// S-BEAM, ...) instead of the element's layer
+    pub element_layers: bool,
+}
+
+impl Default for DxfExportOptions {
+    fn default() -> Self {
+        Self {
+            version: "R2018".to_string(),
+            units: "mm".to_string(),
+            binary: false,
+            precision: None,
+            origin: None,
+            element_layers: true,
+        }
+    }
+}
+
+impl DxfExportOptions {
+    pub fn acad_version(&self) -> Result<AcadVersion, String> {
+        Ok(match self.version.to_ascii_uppercase().as_str() {
+            "R12" => AcadVersion::R12,
+            "R13" => AcadVersion::R13,
+            "R14" => AcadVersion::R14,
+            "R2000" => AcadVersion::R2000,
+            "R2004" => AcadVersion::R2004,
+            "R2007" => AcadVersion::R2007,
+            "R2010" => AcadVersion::R2010,
+            "R2013" => AcadVersion::R2013,
+            "R2018" => AcadVersion::R2018,
+            other => return Err(format!("Unsupported DXF version '{}'", other)),
+        })
+    }
+
+    /// Factor from project millimetres to the output unit, with its `$INSUNITS` value.
+    /// Feet-and-inches drawings are written in inches, as architectural DXF usually is.
+    pub fn unit_scale(&self) -> Result<(f64, Units), String> {
+        Ok(match self.units.as_str() {
+            "mm" => (1.0, Units::Millimeters),
+            "cm" => (0.1, Units::Centimeters),
+            "m" => (0.001, Units::Meters),
+            "in" | "ft-in" => (1.0 / 25.4, Units::Inches),
+            "ft" => (1.0 / 304.8, Units::Feet),
+            other => return Err(format!("Unsupported drawing unit '{}'", other)),
+        })
+    }
+}
+
+/// Set the version, units, precision and extents header variables
+pub fn write_header(
+    drawing: &mut Drawing,
+    version: AcadVersion,
+    units: Units,
+    precision: Option<u8>,
+    shapes: &[ShapeData],
+) {
+    drawing.header.version = version;
+    drawing.header.default_drawing_units = units;
+    drawing.header.drawing_units = match units {
+        Units::Inches | Units::Feet => DrawingUnits::English,
+        _ => DrawingUnits::Metric,
+    };
+    if let Some(precision) = precision {
+        drawing.header.unit_precision = i16::from(precision);
+    }
+    if let Some((min, max)) = extents(shapes) {
+        drawing.header.minimum_drawing_extents = to_point(min);
+        drawing.header.maximum_drawing_extents = to_point(max);
+    }
+}
+
+/// Bounding box of all shape geometry
+pub fn extents(shapes: &[ShapeData]) -> Option<(PointData, PointData)> {
+    let mut corners = Vec::new();
+    for shape in shapes {
+        corners.extend(shape.start);
+        corners.extend(shape.end);
+        corners.extend(shape.position);
+        corners.extend(shape.points.iter().flatten().copied());
+        if let Some(center) = shape.center {
+            let radius = shape
+                .radius
+                .or_else(|| {
+                    let radius_x = shape.radius_x?;
+                    Some(radius_x.max(shape.radius_y.unwrap_or(radius_x)))
+                })
+                .unwrap_or(0.0);
+            corners.push(PointData {
+                x: center.x - radius,
+                y: center.y - radius,
+            });
+            corners.push(PointData {
+                x: center.x + radius,
+                y: center.y + radius,
+            });
+        }
+    }
+    let first = *corners.first()?;
+    Some(corners.iter().fold((first, first), |(min, max), p| {
+        (
+            PointData {
+                x: min.x.min(p.x),
+                y: min.y.min(p.y),
+            },
+            PointData {
+                x: max.x.max(p.x),
+                y: max.y.max(p.y),
+            },
+        )
+    }))
+}
+
+/// Add every shape that has a DXF representation to the drawing.
+/// `layer_names` maps project layer names to their DXF table names. Returns the
+/// handle of the first entity of each shape with application data, for
+/// `dxf_xdata::write_entity_data`; hatch fills are handled by `dxf_hatch`.
+pub fn add_shapes<'s>(
+    drawing: &mut Drawing,
+    shapes: &'s [ShapeData],
+    layer_names: &HashMap<String, String>,
+    report: &mut ConversionReport,
+) -> Vec<(String, &'s ShapeData)> {
+    let mut handles = Vec::new();
+    for shape in shapes {
+        let keep_data = shape.dxf_data.is_some() && shape.shape_type != "hatch";
+        for (index, entity) in shape_entities(drawing, shape, layer_names, report)
+            .into_iter()
+            .enumerate()
+        {
+            let handle = drawing.add_entity(entity).common.handle;
+            if keep_data && index == 0 {
+                handles.push((format!("{:X}", handle.0), shape));
+            }
+        }
+    }
+    handles
+}
+
+/// Entities for one shape, not yet added to the drawing. Dimension blocks and
+/// text styles are created as needed. Hatches yield only their outlines.
+pub fn shape_entities(
+    drawing: &mut Drawing,
+    shape: &ShapeData,
+    layer_names: &HashMap<String, String>,
+    report: &mut ConversionReport,
+) -> Vec<Entity> {
+    let shape_type = shape.shape_type.as_str();
+    let image_supported = dxf_image::is_supported(drawing.header.version);
+    let specifics: Vec<EntityType> = match shape_type {
+        "text" => dxf_text::shape_to_mtext(shape, drawing)
+            .map(EntityType::MText)
+            .into_iter()
+            .collect(),
+        "dimension" => dxf_dimensions::shape_to_dimension(shape, drawing)
+            .into_iter()
+            .collect(),
+        // The fill itself is written and reported by `dxf_hatch` after saving
+        "hatch" => hatch_outlines(shape),
+        // Written and reported by `dxf_image` after saving, or as their frame
+        "image" if image_supported => Vec::new(),
+        "image" => dxf_image::corners(shape)
+            .and_then(|corners| lw_polyline(&corners, &[], true))
+            .map(EntityType::LwPolyline)
+            .into_iter()
+            .collect(),
+        _ => shape_to_entity(shape).into_iter().collect(),
+    };
+    match shape_type {
+        // Reported as the element by `dxf_elements`
+        _ if shape.element_type.is_some() => {}
+        "hatch" => {}
+        "image" if image_supported => {}
+        _ if specifics.is_empty() => {
+            report.skipped(shape_type, shape.id.clone(), skip_reason(shape))
+        }
+        "image" => report.approximated(
+            shape_type,
+            shape.id.clone(),
+            "images need R14 or later; only the frame was written",
+        ),
+        _ => match dxf_xdata::approximation(shape, drawing.header.version) {
+            Some(reason) => report.approximated(shape_type, shape.id.clone(), reason),
+            None => report.converted(shape_type),
+        },
+    }
+    specifics
+        .into_iter()
+        .map(|specific| {
+            let mut entity = Entity::new(specific);
+            apply_common(&mut entity, shape, layer_names);
+            entity
+        })
+        .collect()
+}
+
+/// Why `shape_entities` produced no entity for a shape
+fn skip_reason(shape: &ShapeData) -> String {
+    match shape.shape_type.as_str() {
+        "line" | "circle" | "arc" | "ellipse" | "polyline" | "spline" | "point" | "text"
+        | "image" => "missing or degenerate geometry".to_string(),
+        "dimension" => match shape.dimension_type.as_deref() {
+            Some(dimension_type @ ("linear" | "aligned" | "radius" | "diameter" | "angular")) => {
+                format!(
+                    "{} dimension has missing or coincident points",
+                    dimension_type
+                )
+            }
+            Some(dimension_type) => format!("{} dimensions are not supported", dimension_type),
+            None => "dimension type missing".to_string(),
+        },
+        shape_type => format!("shape type '{}' is not supported", shape_type),
+    }
+}
+
+/// Layer, colour and linetype shared by all entity types
+fn apply_common(entity: &mut Entity, shape: &ShapeData, layer_names: &HashMap<String, String>) {
+    if let Some(layer) = &shape.layer {
+        entity.common.layer = layer_names
+            .get(layer)
+            .cloned()
+            .unwrap_or_else(|| layer.clone());
+    }
+    if let Some(line_type) = &shape.line_type {
+        entity.common.line_type_name = dxf_linetypes::dxf_line_type_name(line_type);
+    }
+    if let Some(scale) = shape.line_type_scale {
+        entity.common.line_type_scale = scale;
+    }
+    if let Some(rgb) = shape.color.as_deref().and_then(dxf_color::from_hex) {
+        entity.common.color = Color::from_index(dxf_color::nearest_aci(rgb));
+        entity.common.color_24_bit = rgb as i32;
+    }
+}
+
+/// Convert a single shape into its native DXF entity
+fn shape_to_entity(shape: &ShapeData) -> Option<EntityType> {
+    match shape.shape_type.as_str() {
+        "line" => {
+            let (start, end) = (shape.start?, shape.end?);
+            Some(EntityType::Line(Line::new(to_point(start), to_point(end))))
+        }
+        "circle" => Some(EntityType::Circle(Circle::new(
+            to_point(shape.center?),
+            shape.radius?,
+        ))),
+        "arc" => {
+            let start = shape.start_angle.unwrap_or(0.0);
+            let end = shape.end_angle.unwrap_or(2.0 * PI);
+            Some(EntityType::Arc(Arc::new(
+                to_point(shape.center?),
+                shape.radius?,
+                start.to_degrees(),
+                end.to_degrees(),
+            )))
+        }
+        "ellipse" => ellipse_to_entity(shape).map(EntityType::Ellipse),
+        "polyline" => polyline_to_entity(shape).map(EntityType::LwPolyline),
+        "spline" => spline_to_entity(shape).map(EntityType::Spline),
+        "point" => {
+            let position = shape.position.or(shape.center)?;
+            Some(EntityType::ModelPoint(ModelPoint::new(to_point(position))))
+        }
+        _ => None,
+    }
+}
+
+fn ellipse_to_entity(shape: &ShapeData) -> Option<Ellipse> {
+    let center = shape.center?;
+    let radius_x = shape.radius_x?;
+    let radius_y = shape.radius_y.unwrap_or(radius_x);
+    if radius_x <= 0.0 || radius_y <= 0.0 {
+        return None;
+    }
+    let rotation = shape.rotation.unwrap_or(0.0);
+    let mut start = shape.start_angle.unwrap_or(0.0);
+    let mut end = shape.end_angle.unwrap_or(2.0 * PI);
+
+    // DXF requires the major axis to be the longer one (ratio <= 1). When the
+    // Y radius is longer, the axes swap and the parameters shift by a quarter turn.
+    let (major, ratio, axis_angle) = if radius_x >= radius_y {
+        (radius_x, radius_y / radius_x, rotation)
+    } else {
+        start -= FRAC_PI_2;
+        end -= FRAC_PI_2;
+        (radius_y, radius_x / radius_y, rotation + FRAC_PI_2)
+    };
+
+    Some(Ellipse {
+        center: to_point(center),
+        major_axis: Vector::new(major * axis_angle.cos(), major * axis_angle.sin(), 0.0),
+        minor_axis_ratio: ratio,
+        start_parameter: start,
+        end_parameter: end,
+        ..Default::default()
+    })
+}
+
+pub fn polyline_to_entity(shape: &ShapeData) -> Option<LwPolyline> {
+    lw_polyline(
+        shape.points.as_deref()?,
+        shape.bulge.as_deref().unwrap_or(&[]),
+        shape.closed.unwrap_or(false),
+    )
+}
+
+/// Visible hatch boundaries as closed polylines, including the holes
+fn hatch_outlines(shape: &ShapeData) -> Vec<EntityType> {
+    if shape.boundary_visible == Some(false) {
+        return Vec::new();
+    }
+    let outer = shape
+        .points
+        .as_deref()
+        .map(|points| (points, shape.bulge.as_deref().unwrap_or(&[])));
+    let inner = shape
+        .inner_loops
+        .iter()
+        .flatten()
+        .map(|points| (points.as_slice(), &[][..]));
+    outer
+        .into_iter()
+        .chain(inner)
+        .filter_map(|(points, bulges)| lw_polyline(points, bulges, true))
+        .map(EntityType::LwPolyline)
+        .collect()
+}
+
+fn lw_polyline(points: &[PointData], bulges: &[f64], closed: bool) -> Option<LwPolyline> {
+    if points.len() < 2 {
+        return None;
+    }
+    let mut polyline = LwPolyline {
+        vertices: points
+            .iter()
+            .enumerate()
+            .map(|(i, p)| LwPolylineVertex {
+                x: p.x,
+                y: p.y,
+                bulge: bulges.get(i).copied().unwrap_or(0.0),
+                ..Default::default()
+            })
+            .collect(),
+        ..Default::default()
+    };
+    polyline.set_is_closed(closed);
+    Some(polyline)
+}
+
+fn spline_to_entity(shape: &ShapeData) -> Option<Spline> {
+    let points = shape.points.as_ref()?;
+    let degree = shape.degree.unwrap_or(3).max(1);
+    if points.len() < 2 {
+        return None;
+    }
+    // A spline needs at least degree + 1 control points
+    let degree = degree.min(points.len() as i32 - 1);
+
+    let mut spline = Spline {
+        degree_of_curve: degree,
+        control_points: points.iter().map(|p| to_point(*p)).collect(),
+        ..Default::default()
+    };
+    spline.knot_values = match &shape.knots {
+        Some(knots) if knots.len() == points.len() + degree as usize + 1 => knots.clone(),
+        _ => clamped_knots(points.len(), degree as usize),
+    };
+    if let Some(weights) = shape.weights.as_ref().filter(|w| w.len() == points.len()) {
+        spline.weight_values = weights.clone();
+        spline.set_is_rational(true);
+    }
+    spline.set_is_closed(shape.closed.unwrap_or(false));
+    spline.set_is_planar(true);
+    Some(spline)
+}
+
+/// Clamped uniform knot vector for `count` control points
+fn clamped_knots(count: usize, degree: usize) -> Vec<f64> {
+    let spans = count - degree;
+    let mut knots = Vec::with_capacity(count + degree + 1);
+    knots.extend(std::iter::repeat(0.0).take(degree));
+    knots.extend((0..=spans).map(|i| i as f64 / spans as f64));
+    knots.extend(std::iter::repeat(1.0).take(degree));
+    knots
+}
+
+pub fn to_point(p: PointData) -> Point {
+    Point::new(p.x, p.y, 0.0)
+}
@@ -0,0 +1,304 @@
+//! Writing a complete DXF file: the header, tables, model space, blocks and
+//! layouts through the dxf crate, then the group code passes on the saved file.
+
+use super::dxf_export::{self, DxfExportOptions};
+use super::dxf_hatch::{self, HatchPatternData};
+use super::dxf_layers::{self, LayerData};
+use super::dxf_layouts::{self, SheetData};
+use super::dxf_linetypes::{self, LineTypeData};
+use super::dxf_report::ConversionReport;
+use super::dxf_transform::{self, Transform};
+use super::shape::{PointData, ShapeData};
+use super::{dxf_elements, dxf_image, dxf_raw, dxf_xdata};
+use dxf::entities::{Entity, EntityType, Insert};
+use dxf::enums::AcadVersion;
+use dxf::objects::{Dictionary, Object, ObjectType};
+use dxf::{Block, Drawing};
+use std::collections::HashMap;
+use std::io;
+use std::ops::Range;
+
+/// Project tables shared by every drawing written
+#[derive(Debug, Clone, Default)]
+pub struct ExportTables {
+    pub layers: Vec<LayerData>,
+    pub line_types: Vec<LineTypeData>,
+    pub patterns: Vec<HatchPatternData>,
+}
+
+/// Shapes to write, in project millimetres
+#[derive(Debug, Clone, Default)]
+pub struct Content {
+    /// Drawn directly in model space
+    pub model: Vec<ShapeData>,
+    /// Block definitions, each inserted once into model space
+    pub blocks: Vec<BlockContent>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BlockContent {
+    /// Valid, unique block name
+    pub name: String,
+    pub shapes: Vec<ShapeData>,
+    /// Where the block is inserted, in project millimetres
+    pub offset: PointData,
+}
+
+/// Write `content` and `sheets` to `path`. Errors are ready to be shown.
+pub fn write_file(
+    path: &str,
+    content: Content,
+    sheets: &[SheetData],
+    tables: &ExportTables,
+    options: &DxfExportOptions,
+) -> Result<ConversionReport, String> {
+    let version = options.acad_version()?;
+    let (unit_scale, units) = options.unit_scale()?;
+
+    // BIM elements are exported as the shapes they are drawn with. The shapes of
+    // the blocks follow the model space shapes, so the tables see them all.
+    let mut report = ConversionReport::default();
+    let explode = |shapes, report: &mut ConversionReport| {
+        dxf_elements::explode(shapes, options.element_layers, version, report)
+    };
+    let mut shapes = explode(content.model, &mut report);
+    let model_range = 0..shapes.len();
+    let mut blocks = Vec::new();
+    for block in content.blocks {
+        let start = shapes.len();
+        shapes.extend(explode(block.shapes, &mut report));
+        blocks.push((block.name, start..shapes.len(), block.offset));
+    }
+
+    // Project coordinates are millimetres relative to the project base point,
+    // which is in millimetres too and is added once scaled to the output unit
+    let mut model = Transform::scale(unit_scale, unit_scale);
+    if let Some(origin) = options.origin {
+        model =
+            Transform::translate(origin.x * unit_scale, origin.y * unit_scale).then_after(&model);
+    }
+    if !model.is_identity() {
+        for shape in &mut shapes {
+            dxf_transform::transform_shape(shape, &model);
+        }
+    }
+
+    // Create DXF drawing
+    let mut drawing = Drawing::new();
+    // The first object is the root dictionary; images add theirs to it
+    drawing.add_object(Object::new(ObjectType::Dictionary(Dictionary::default())));
+    dxf_export::write_header(&mut drawing, version, units, options.precision, &shapes);
+    if !blocks.is_empty() {
+        write_extents(&mut drawing, &shapes, model_range.clone(), &blocks, &model);
+    }
+    let layer_line_types: Vec<&str> = tables.layers.iter().map(|l| l.line_type.as_str()).collect();
+    dxf_linetypes::write_line_types(&mut drawing, &tables.line_types, &layer_line_types, &shapes);
+    // Dash patterns are defined in millimetres
+    drawing.header.line_type_scale = unit_scale;
+    let layer_names = dxf_layers::write_layers(&mut drawing, &tables.layers, &shapes);
+    dxf_xdata::register_applications(&mut drawing, &shapes);
+    let mut entity_handles = dxf_export::add_shapes(
+        &mut drawing,
+        &shapes[model_range.clone()],
+        &layer_names,
+        &mut report,
+    );
+    for (name, range, offset) in &blocks {
+        entity_handles.extend(add_block(
+            &mut drawing,
+            name,
+            &shapes[range.clone()],
+            model.apply_vector(*offset),
+            &layer_names,
+            &mut report,
+        ));
+    }
+    // Sheets are in paper millimetres and are not scaled to the output units
+    let viewports =
+        dxf_layouts::write_layouts(&mut drawing, sheets, &model, &layer_names, &mut report);
+
+    // R12 has no HATCH entity; hatches are then exported as their outlines only
+    let hatches_supported = version >= AcadVersion::R13;
+    if !hatches_supported {
+        // Hatches of BIM elements are reported with their element
+        for shape in shapes
+            .iter()
+            .filter(|s| s.shape_type == "hatch" && s.element_type.is_none())
+        {
+            report.approximated(
+                "hatch",
+                shape.id.clone(),
+                "hatch fills need R13 or later; only the outline was written",
+            );
+        }
+    }
+
+    // Save as ASCII first: hatches and rounding are applied to the group codes,
+    // then the file is converted to binary if requested
+    let targets: Vec<(Option<&str>, Range<usize>)> = std::iter::once((None, model_range))
+        .chain(
+            blocks
+                .iter()
+                .map(|(name, range, _)| (Some(name.as_str()), range.clone())),
+        )
+        .collect();
+    let mut post_process = || -> io::Result<()> {
+        for (block, range) in &targets {
+            let shapes = &shapes[range.clone()];
+            if hatches_supported {
+                entity_handles.extend(dxf_hatch::write_hatches(
+                    path,
+                    *block,
+                    shapes,
+                    &layer_names,
+                    &tables.patterns,
+                    version,
+                    &mut report,
+                )?);
+            }
+            // Written after the hatches so they end up behind them
+            if dxf_image::is_supported(version) {
+                entity_handles.extend(dxf_image::write_images(
+                    path,
+                    *block,
+                    shapes,
+                    &layer_names,
+                    version,
+                    &mut report,
+                )?);
+            }
+        }
+        dxf_xdata::write_entity_data(path, &entity_handles, version)?;
+        dxf_layers::write_layer_groups(path, &tables.layers, &layer_names, version)?;
+        dxf_layouts::write_viewports(path, &viewports, version)?;
+        if let Some(precision) = options.precision {
+            dxf_raw::round_coordinates(path, precision)?;
+        }
+        if options.binary {
+            dxf_raw::convert_to_binary(path, version <= AcadVersion::R12)?;
+        }
+        Ok(())
+    };
+    drawing
+        .save_file(path)
+        .map_err(|e| e.to_string())
+        .and_then(|_| post_process().map_err(|e| e.to_string()))
+        .map_err(|e| format!("Failed to export DXF: {}", e))?;
+    Ok(report)
+}
+
+/// Header extents covering model space and every block where it is inserted
+fn write_extents(
+    drawing: &mut Drawing,
+    shapes: &[ShapeData],
+    model_range: Range<usize>,
+    blocks: &[(String, Range<usize>, PointData)],
+    model: &Transform,
+) {
+    let shift = |p: PointData, by: PointData| PointData {
+        x: p.x + by.x,
+        y: p.y + by.y,
+    };
+    let block_extents = blocks.iter().filter_map(|(_, range, offset)| {
+        let (min, max) = dxf_export::extents(&shapes[range.clone()])?;
+        let offset = model.apply_vector(*offset);
+        Some((shift(min, offset), shift(max, offset)))
+    });
+    let all = dxf_export::extents(&shapes[model_range])
+        .into_iter()
+        .chain(block_extents)
+        .reduce(|(min, max), (other_min, other_max)| {
+            (
+                PointData {
+                    x: min.x.min(other_min.x),
+                    y: min.y.min(other_min.y),
+                },
+                PointData {
+                    x: max.x.max(other_max.x),
+                    y: max.y.max(other_max.y),
+                },
+            )
+        });
+    if let Some((min, max)) = all {
+        drawing.header.minimum_drawing_extents = dxf_export::to_point(min);
+        drawing.header.maximum_drawing_extents = dxf_export::to_point(max);
+    }
+}
+
+/// Define a block from `shapes` and insert it into model space at `location`.
+/// Returns the handles of the entities written for shapes with application data,
+/// like `dxf_export::add_shapes`.
+fn add_block<'s>(
+    drawing: &mut Drawing,
+    name: &str,
+    shapes: &'s [ShapeData],
+    location: PointData,
+    layer_names: &HashMap<String, String>,
+    report: &mut ConversionReport,
+) -> Vec<(String, &'s ShapeData)> {
+    let mut entities = Vec::new();
+    let mut data_shapes = Vec::new();
+    for shape in shapes {
+        let keep_data = shape.dxf_data.is_some() && shape.shape_type != "hatch";
+        let converted = dxf_export::shape_entities(drawing, shape, layer_names, report);
+        if keep_data && !converted.is_empty() {
+            data_shapes.push((entities.len(), shape));
+        }
+        entities.extend(converted);
+    }
+    let block = drawing.add_block(Block {
+        name: name.to_string(),
+        layer: "0".to_string(),
+        entities,
+        ..Default::default()
+    });
+    // Block entities get their handles when the block is added
+    let handles = data_shapes
+        .into_iter()
+        .filter_map(|(index, shape)| {
+            let handle = block.entities.get(index)?.common.handle;
+            (handle.0 != 0).then(|| (format!("{:X}", handle.0), shape))
+        })
+        .collect();
+
+    drawing.add_entity(Entity::new(EntityType::Insert(Insert {
+        name: name.to_string(),
+        location: dxf_export::to_point(location),
+        ..Default::default()
+    })));
+    handles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::temp_dir;
+    use super::*;
+
+    #[test]
+    fn images_are_written_without_sheets() {
+        let dir = temp_dir("dxf_file_image");
+        let path = dir.join("plan.dxf");
+        let path = path.to_str().unwrap();
+        let image = ShapeData {
+            shape_type: "image".to_string(),
+            position: Some(PointData { x: 0.0, y: 10.0 }),
+            width: Some(10.0),
+            height: Some(10.0),
+            image_data: Some("data:image/png;base64,AAAA".to_string()),
+            ..Default::default()
+        };
+        let content = Content {
+            model: vec![image],
+            ..Default::default()
+        };
+        let options = DxfExportOptions::default();
+        let written = write_file(path, content, &[], &ExportTables::default(), &options);
+        let drawing = Drawing::load_file(path);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(written.is_ok(), "{:?}", written.err());
+        let drawing = drawing.unwrap();
+        let root = drawing.objects().next().map(|object| &object.specific);
+        assert!(matches!(root, Some(ObjectType::Dictionary(_))));
+    }
+}
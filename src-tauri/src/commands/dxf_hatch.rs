@@ -0,0 +1,913 @@
+//! HATCH entities. The `dxf` crate does not support HATCH, so hatches are read
+//! from and written to the ASCII group codes directly.
+
+use super::dxf_raw::{self, RawEntityWriter, RawRecords};
+use super::dxf_report::ConversionReport;
+use super::dxf_text::sanitize_table_name;
+use super::dxf_xdata::DxfEntityData;
+use super::shape::{PointData, ShapeData};
+use super::{dxf_blocks, dxf_color, dxf_ocs, dxf_transform};
+use dxf::enums::AcadVersion;
+use dxf::Vector;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::f64::consts::TAU;
+use std::io;
+
+/// Segments used to flatten a full turn of a curved boundary edge
+const SEGMENTS_PER_TURN: f64 = 64.0;
+
+/// Boundary path flags (group 92)
+const PATH_EXTERNAL: i64 = 1;
+const PATH_POLYLINE: i64 = 2;
+const PATH_OUTERMOST: i64 = 16;
+
+/// Line family as (angle, delta-x, delta-y, dashes)
+type BasicFamily = (f64, f64, f64, &'static [f64]);
+
+/// Basic project pattern types as line families, mirroring `BUILTIN_PATTERNS`
+/// in `src/types/hatch.ts`
+const BASIC_PATTERNS: [(&str, &[BasicFamily]); 5] = [
+    ("diagonal", &[(45.0, 0.0, 10.0, &[])]),
+    (
+        "crosshatch",
+        &[(45.0, 0.0, 10.0, &[]), (-45.0, 0.0, 10.0, &[])],
+    ),
+    ("horizontal", &[(0.0, 0.0, 10.0, &[])]),
+    ("vertical", &[(90.0, 0.0, 10.0, &[])]),
+    ("dots", &[(0.0, 10.0, 10.0, &[0.0])]),
+];
+
+/// Line family of a hatch pattern (`LineFamily` in `src/types/hatch.ts`).
+/// Angles are in degrees and lengths in pattern units, as in PAT files.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LineFamilyData {
+    pub angle: f64,
+    pub origin_x: f64,
+    pub origin_y: f64,
+    pub delta_x: f64,
+    pub delta_y: f64,
+    pub dash_pattern: Vec<f64>,
+}
+
+/// Hatch pattern definition (`CustomHatchPattern` in `src/types/hatch.ts`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HatchPatternData {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub line_families: Vec<LineFamilyData>,
+}
+
+/// Read the model space HATCH entities as hatch shapes, together with the
+/// definitions of the patterns they use. Application data is kept on the
+/// first shape of each hatch.
+pub fn read_hatches(
+    raw: &RawRecords,
+    entity_data: &HashMap<String, DxfEntityData>,
+    report: &mut ConversionReport,
+) -> (Vec<ShapeData>, Vec<HatchPatternData>) {
+    let mut shapes = Vec::new();
+    let mut patterns = Vec::new();
+    for pairs in raw.entities("HATCH") {
+        if let Some(hatch) = read_hatch(pairs, entity_data, &mut patterns, report) {
+            shapes.extend(hatch.shapes);
+        }
+    }
+    (shapes, patterns)
+}
+
+/// HATCH entity inside a block definition, in block coordinates
+#[derive(Debug, Clone)]
+pub struct BlockHatch {
+    pub shapes: Vec<ShapeData>,
+    /// Colour ByBlock, taken from the INSERT that places the block
+    pub color_by_block: bool,
+}
+
+/// Read the HATCH entities of the block definitions, keyed by upper-case block
+/// name, adding the patterns they use to `patterns`. Hatches of layout blocks
+/// are reported as skipped; those of anonymous dimension blocks belong to the
+/// dimension graphics, which are not imported.
+pub fn read_block_hatches(
+    raw: &RawRecords,
+    entity_data: &HashMap<String, DxfEntityData>,
+    patterns: &mut Vec<HatchPatternData>,
+    report: &mut ConversionReport,
+) -> HashMap<String, Vec<BlockHatch>> {
+    let mut blocks: HashMap<String, Vec<BlockHatch>> = HashMap::new();
+    for (block, pairs) in raw.block_entities("HATCH") {
+        if dxf_blocks::is_dimension_block(block) {
+            continue;
+        }
+        if dxf_blocks::is_layout_block(block) {
+            report.skipped(
+                "HATCH",
+                entity_handle(pairs),
+                "hatches on layouts are not imported",
+            );
+            continue;
+        }
+        if let Some(hatch) = read_hatch(pairs, entity_data, patterns, report) {
+            blocks
+                .entry(block.to_ascii_uppercase())
+                .or_default()
+                .push(BlockHatch {
+                    shapes: hatch.shapes,
+                    color_by_block: hatch.color_by_block,
+                });
+        }
+    }
+    blocks
+}
+
+/// Convert and report one HATCH entity, adding a custom pattern to `patterns`
+fn read_hatch(
+    pairs: &[(i32, String)],
+    entity_data: &HashMap<String, DxfEntityData>,
+    patterns: &mut Vec<HatchPatternData>,
+    report: &mut ConversionReport,
+) -> Option<ParsedHatch> {
+    let handle = entity_handle(pairs);
+    if common_pairs(pairs)
+        .iter()
+        .any(|(code, value)| *code == 67 && value.trim() == "1")
+    {
+        report.skipped("HATCH", handle, "hatches on layouts are not imported");
+        return None;
+    }
+    let Some(mut hatch) = parse_hatch(pairs) else {
+        report.skipped("HATCH", handle, "unreadable boundary or pattern data");
+        return None;
+    };
+    if hatch.shapes.is_empty() {
+        report.skipped("HATCH", handle, "no closed boundary");
+        return None;
+    }
+    if let (Some(shape), Some(handle)) = (hatch.shapes.first_mut(), &handle) {
+        shape.dxf_data = entity_data.get(handle).cloned();
+    }
+    match hatch.approximation {
+        Some(reason) => report.approximated("HATCH", handle, reason),
+        None => report.converted("HATCH"),
+    }
+    if let Some(pattern) = hatch.pattern.take() {
+        if !patterns.iter().any(|p| p.id == pattern.id) {
+            patterns.push(pattern);
+        }
+    }
+    Some(hatch)
+}
+
+fn entity_handle(pairs: &[(i32, String)]) -> Option<String> {
+    common_pairs(pairs)
+        .iter()
+        .find(|(code, _)| *code == 5)
+        .map(|(_, value)| value.trim().to_uppercase())
+}
+
+/// Boundary loop as vertices with the bulge of the segment starting at each
+struct BoundaryLoop {
+    vertices: Vec<PointData>,
+    bulges: Vec<f64>,
+    external: bool,
+    /// Elliptic or spline edges were replaced by straight segments
+    flattened: bool,
+}
+
+struct ParsedHatch {
+    shapes: Vec<ShapeData>,
+    pattern: Option<HatchPatternData>,
+    approximation: Option<&'static str>,
+    color_by_block: bool,
+}
+
+/// Group codes before the AcDbHatch subclass marker (handle, layer, colour)
+fn common_pairs(pairs: &[(i32, String)]) -> &[(i32, String)] {
+    let marker = pairs
+        .iter()
+        .position(|(code, value)| *code == 100 && value.trim() == "AcDbHatch")
+        .unwrap_or(0);
+    &pairs[..marker]
+}
+
+/// Sequential reader over the group codes of one entity
+struct Cursor<'a> {
+    pairs: &'a [(i32, String)],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    /// Value of the next pair with `code`, skipping anything in between
+    fn take(&mut self, code: i32) -> Option<&'a str> {
+        let offset = self.pairs[self.pos..]
+            .iter()
+            .position(|(c, _)| *c == code)?;
+        self.pos += offset + 1;
+        Some(self.pairs[self.pos - 1].1.trim())
+    }
+
+    fn float(&mut self, code: i32) -> Option<f64> {
+        self.take(code)?.parse().ok()
+    }
+
+    fn int(&mut self, code: i32) -> Option<i64> {
+        self.take(code)?.parse().ok()
+    }
+
+    fn point(&mut self, x_code: i32) -> Option<PointData> {
+        Some(PointData {
+            x: self.float(x_code)?,
+            y: self.float(x_code + 10)?,
+        })
+    }
+
+    fn peek(&self, ahead: usize) -> Option<i32> {
+        self.pairs.get(self.pos + ahead).map(|(code, _)| *code)
+    }
+}
+
+fn parse_hatch(pairs: &[(i32, String)]) -> Option<ParsedHatch> {
+    let common = common_pairs(pairs);
+
+    let mut shape = ShapeData {
+        shape_type: "hatch".to_string(),
+        closed: Some(true),
+        ..Default::default()
+    };
+    let mut aci = None;
+    let mut true_color = None;
+    for (code, value) in common {
+        let value = value.trim();
+        match code {
+            8 => shape.layer = Some(value.to_string()),
+            62 => aci = value.parse::<i16>().ok(),
+            420 => true_color = value.parse::<i64>().ok().map(|c| (c & 0xFF_FFFF) as u32),
+            _ => {}
+        }
+    }
+    shape.color = match (true_color, aci) {
+        (Some(rgb), _) => Some(dxf_color::to_hex(rgb)),
+        (None, Some(index @ 1..=255)) => {
+            Some(dxf_color::to_hex(dxf_color::aci_to_rgb(index as u8)))
+        }
+        _ => None,
+    };
+
+    // The elevation point and extrusion direction precede the pattern name
+    let header = &pairs[common.len()..];
+    let header = &header[..header.iter().position(|(code, _)| *code == 2)?];
+    let number = |code: i32| {
+        header
+            .iter()
+            .find(|(c, _)| *c == code)
+            .and_then(|(_, value)| value.trim().parse::<f64>().ok())
+    };
+    let normal = Vector::new(
+        number(210).unwrap_or(0.0),
+        number(220).unwrap_or(0.0),
+        number(230).unwrap_or(1.0),
+    );
+    let ocs = dxf_ocs::to_world(&normal, number(30).unwrap_or(0.0));
+
+    let mut cursor = Cursor {
+        pairs,
+        pos: common.len(),
+    };
+    let name = cursor.take(2)?.to_string();
+    let solid = cursor.int(70)? & 1 == 1;
+    let path_count = cursor.int(91)?;
+    let loops: Vec<BoundaryLoop> = (0..path_count)
+        .map(|_| read_boundary_path(&mut cursor))
+        .collect::<Option<_>>()?;
+
+    let mut pattern = None;
+    if solid {
+        shape.pattern_type = Some("solid".to_string());
+    } else {
+        cursor.take(76)?;
+        let angle = cursor.float(52).unwrap_or(0.0);
+        let scale = cursor.float(41).filter(|s| *s > 0.0).unwrap_or(1.0);
+        let line_count = cursor.int(78).unwrap_or(0);
+        let line_families: Vec<LineFamilyData> = (0..line_count)
+            .map(|_| read_pattern_line(&mut cursor, angle.to_radians(), scale))
+            .collect::<Option<_>>()?;
+        shape.pattern_angle = Some(angle);
+        shape.pattern_scale = Some(scale);
+
+        let id = name.to_lowercase();
+        if BASIC_PATTERNS.iter().any(|(basic, _)| *basic == id) {
+            shape.pattern_type = Some(id);
+        } else {
+            shape.pattern_type = Some("custom".to_string());
+            shape.custom_pattern_id = Some(id.clone());
+            pattern = Some(HatchPatternData {
+                id,
+                name,
+                line_families,
+            });
+        }
+    }
+
+    let approximation = if loops.iter().any(|l| l.flattened) {
+        Some("elliptic or spline boundary edges flattened to straight segments")
+    } else if loops
+        .iter()
+        .filter(|l| !l.external)
+        .any(|l| l.bulges.iter().any(|b| *b != 0.0))
+    {
+        Some("arcs in island boundaries flattened to straight segments")
+    } else {
+        None
+    };
+    let mut shapes = split_regions(loops, &shape);
+    if let Some(ocs) = ocs {
+        for shape in &mut shapes {
+            dxf_transform::transform_shape(shape, &ocs);
+        }
+    }
+    Some(ParsedHatch {
+        shapes,
+        pattern,
+        approximation,
+        color_by_block: aci == Some(0),
+    })
+}
+
+fn read_boundary_path(cursor: &mut Cursor) -> Option<BoundaryLoop> {
+    let flags = cursor.int(92)?;
+    let mut boundary = BoundaryLoop {
+        vertices: Vec::new(),
+        bulges: Vec::new(),
+        external: flags & (PATH_EXTERNAL | PATH_OUTERMOST) != 0,
+        flattened: false,
+    };
+
+    if flags & PATH_POLYLINE != 0 {
+        let has_bulge = cursor.int(72)? != 0;
+        cursor.take(73)?;
+        for _ in 0..cursor.int(93)? {
+            boundary.vertices.push(cursor.point(10)?);
+            boundary
+                .bulges
+                .push(if has_bulge { cursor.float(42)? } else { 0.0 });
+        }
+    } else {
+        for _ in 0..cursor.int(93)? {
+            read_edge(cursor, &mut boundary)?;
+        }
+    }
+
+    // Associated source objects are not kept
+    for _ in 0..cursor.int(97).unwrap_or(0) {
+        cursor.take(330)?;
+    }
+    Some(boundary)
+}
+
+/// Append one boundary edge. Lines and arcs keep their exact shape through bulges;
+/// elliptic arcs are flattened and splines follow their fit or control points.
+fn read_edge(cursor: &mut Cursor, boundary: &mut BoundaryLoop) -> Option<()> {
+    match cursor.int(72)? {
+        1 => {
+            boundary.vertices.push(cursor.point(10)?);
+            boundary.bulges.push(0.0);
+            cursor.point(11)?;
+        }
+        2 => {
+            let center = cursor.point(10)?;
+            let radius = cursor.float(40)?;
+            let (start, sweep) = edge_angles(cursor)?;
+            boundary.vertices.push(PointData {
+                x: center.x + radius * start.cos(),
+                y: center.y + radius * start.sin(),
+            });
+            boundary.bulges.push((sweep / 4.0).tan());
+        }
+        3 => {
+            let center = cursor.point(10)?;
+            let major = cursor.point(11)?;
+            let ratio = cursor.float(40)?;
+            let (start, sweep) = edge_angles(cursor)?;
+            let minor = PointData {
+                x: -major.y * ratio,
+                y: major.x * ratio,
+            };
+            let steps = segment_count(sweep);
+            for i in 0..steps {
+                let t = start + sweep * i as f64 / steps as f64;
+                boundary.vertices.push(PointData {
+                    x: center.x + major.x * t.cos() + minor.x * t.sin(),
+                    y: center.y + major.y * t.cos() + minor.y * t.sin(),
+                });
+                boundary.bulges.push(0.0);
+            }
+            boundary.flattened = true;
+        }
+        4 => {
+            cursor.int(94)?;
+            let rational = cursor.int(73)? != 0;
+            cursor.int(74)?;
+            let knot_count = cursor.int(95)?;
+            let control_count = cursor.int(96)?;
+            for _ in 0..knot_count {
+                cursor.float(40)?;
+            }
+            let mut control_points = Vec::new();
+            for _ in 0..control_count {
+                control_points.push(cursor.point(10)?);
+                if rational {
+                    cursor.float(42)?;
+                }
+            }
+            // Fit data (R2010+) also starts with group 97, like the source object
+            // count that ends the path; fit data is followed by 11/12 or the next edge
+            let mut fit_points = Vec::new();
+            if cursor.peek(0) == Some(97) && matches!(cursor.peek(1), Some(11 | 12 | 72 | 97)) {
+                for _ in 0..cursor.int(97)? {
+                    fit_points.push(cursor.point(11)?);
+                }
+                if cursor.peek(0) == Some(12) {
+                    cursor.point(12)?;
+                    cursor.point(13)?;
+                }
+            }
+            let points = if fit_points.len() >= 2 {
+                fit_points
+            } else {
+                control_points
+            };
+            // The next edge starts where this one ends
+            let count = points.len().saturating_sub(1);
+            boundary.vertices.extend(points.into_iter().take(count));
+            boundary.bulges.resize(boundary.vertices.len(), 0.0);
+            boundary.flattened = true;
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+/// Start angle and signed sweep in radians of an arc or elliptic arc edge.
+/// Clockwise edges store their angles mirrored.
+fn edge_angles(cursor: &mut Cursor) -> Option<(f64, f64)> {
+    let start = cursor.float(50)?.to_radians();
+    let end = cursor.float(51)?.to_radians();
+    let counter_clockwise = cursor.int(73)? != 0;
+    let mut sweep = (end - start).rem_euclid(TAU);
+    if sweep == 0.0 && end != start {
+        sweep = TAU;
+    }
+    Some(if counter_clockwise {
+        (start, sweep)
+    } else {
+        (-start, -sweep)
+    })
+}
+
+/// Reverse the rotation and scaling HATCH applies to its pattern lines
+fn read_pattern_line(cursor: &mut Cursor, angle: f64, scale: f64) -> Option<LineFamilyData> {
+    let line_angle = cursor.float(53)?.to_radians();
+    let base = rotate(
+        PointData {
+            x: cursor.float(43)?,
+            y: cursor.float(44)?,
+        },
+        -angle,
+    );
+    let offset = PointData {
+        x: cursor.float(45)?,
+        y: cursor.float(46)?,
+    };
+    let (sin, cos) = line_angle.sin_cos();
+    let dash_count = cursor.int(79)?;
+    let dash_pattern = (0..dash_count)
+        .map(|_| cursor.float(49).map(|d| d / scale))
+        .collect::<Option<_>>()?;
+    Some(LineFamilyData {
+        angle: (line_angle - angle).to_degrees(),
+        origin_x: base.x / scale,
+        origin_y: base.y / scale,
+        delta_x: (offset.x * cos + offset.y * sin) / scale,
+        delta_y: (-offset.x * sin + offset.y * cos) / scale,
+        dash_pattern,
+    })
+}
+
+/// One hatch shape per external loop, holding the loops that lie inside it
+fn split_regions(loops: Vec<BoundaryLoop>, template: &ShapeData) -> Vec<ShapeData> {
+    let flattened: Vec<Vec<PointData>> = loops.iter().map(flatten).collect();
+    let mut outer: Vec<usize> = (0..loops.len()).filter(|&i| loops[i].external).collect();
+    if outer.is_empty() {
+        // Without flags the largest loop is the outline
+        outer.extend(
+            (0..loops.len()).max_by(|&a, &b| area(&flattened[a]).total_cmp(&area(&flattened[b]))),
+        );
+    }
+
+    let mut shapes: Vec<ShapeData> = outer
+        .iter()
+        .map(|&i| {
+            let boundary = &loops[i];
+            ShapeData {
+                points: Some(boundary.vertices.clone()),
+                bulge: boundary
+                    .bulges
+                    .iter()
+                    .any(|b| *b != 0.0)
+                    .then(|| boundary.bulges.clone()),
+                ..template.clone()
+            }
+        })
+        .collect();
+    for (i, points) in flattened.iter().enumerate() {
+        if outer.contains(&i) || points.len() < 3 {
+            continue;
+        }
+        let owner = outer
+            .iter()
+            .position(|&o| contains(&flattened[o], points[0]))
+            .unwrap_or(0);
+        if let Some(shape) = shapes.get_mut(owner) {
+            shape
+                .inner_loops
+                .get_or_insert_with(Vec::new)
+                .push(points.clone());
+        }
+    }
+    shapes.retain(|s| s.points.as_ref().is_some_and(|p| p.len() >= 3));
+    shapes
+}
+
+/// Loop vertices with bulged segments replaced by short chords
+fn flatten(boundary: &BoundaryLoop) -> Vec<PointData> {
+    let count = boundary.vertices.len();
+    let mut points = Vec::new();
+    for (i, &p) in boundary.vertices.iter().enumerate() {
+        points.push(p);
+        let bulge = boundary.bulges.get(i).copied().unwrap_or(0.0);
+        if bulge.abs() < 1e-9 {
+            continue;
+        }
+        let q = boundary.vertices[(i + 1) % count];
+        let k = (1.0 - bulge * bulge) / (4.0 * bulge);
+        let center = PointData {
+            x: (p.x + q.x) / 2.0 - (q.y - p.y) * k,
+            y: (p.y + q.y) / 2.0 + (q.x - p.x) * k,
+        };
+        let radius = (p.x - center.x).hypot(p.y - center.y);
+        let start = (p.y - center.y).atan2(p.x - center.x);
+        let sweep = 4.0 * bulge.atan();
+        let steps = segment_count(sweep);
+        for step in 1..steps {
+            let t = start + sweep * step as f64 / steps as f64;
+            points.push(PointData {
+                x: center.x + radius * t.cos(),
+                y: center.y + radius * t.sin(),
+            });
+        }
+    }
+    points
+}
+
+fn segment_count(sweep: f64) -> usize {
+    ((sweep.abs() / TAU * SEGMENTS_PER_TURN).ceil() as usize).max(2)
+}
+
+fn area(points: &[PointData]) -> f64 {
+    let n = points.len();
+    (0..n)
+        .map(|i| {
+            let (p, q) = (points[i], points[(i + 1) % n]);
+            p.x * q.y - q.x * p.y
+        })
+        .sum::<f64>()
+        .abs()
+        / 2.0
+}
+
+/// Even-odd point in polygon test
+fn contains(polygon: &[PointData], point: PointData) -> bool {
+    let n = polygon.len();
+    let mut inside = false;
+    for i in 0..n {
+        let (a, b) = (polygon[i], polygon[(i + n - 1) % n]);
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < (b.x - a.x) * (point.y - a.y) / (b.y - a.y) + a.x
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+fn rotate(p: PointData, angle: f64) -> PointData {
+    let (sin, cos) = angle.sin_cos();
+    PointData {
+        x: p.x * cos - p.y * sin,
+        y: p.x * sin + p.y * cos,
+    }
+}
+
+/// One HATCH entity of a hatch shape: foreground, background pattern or background colour
+struct Fill<'a> {
+    pattern_type: &'a str,
+    custom_pattern_id: Option<&'a str>,
+    angle: f64,
+    scale: f64,
+    color: Option<&'a str>,
+}
+
+/// Write the hatch shapes as HATCH entities into an already saved ASCII DXF file,
+/// in model space or in the definition of `block`. Background colours and
+/// background patterns become separate hatches behind the foreground pattern.
+/// Returns the handles of the hatches written for shapes with application data,
+/// for `dxf_xdata::write_entity_data`.
+pub fn write_hatches<'s>(
+    path: &str,
+    block: Option<&str>,
+    shapes: &'s [ShapeData],
+    layer_names: &HashMap<String, String>,
+    patterns: &[HatchPatternData],
+    version: AcadVersion,
+    report: &mut ConversionReport,
+) -> io::Result<Vec<(String, &'s ShapeData)>> {
+    let mut handles = Vec::new();
+    let mut hatches = Vec::new();
+    for shape in shapes.iter().filter(|s| s.shape_type == "hatch") {
+        // Hatches of BIM elements are reported as the element by `dxf_elements`
+        if shape.element_type.is_some() {
+            hatches.push(shape);
+            continue;
+        }
+        if !shape.points.as_ref().is_some_and(|p| p.len() >= 3) {
+            report.skipped(
+                "hatch",
+                shape.id.clone(),
+                "fewer than three boundary points",
+            );
+            continue;
+        }
+        let missing = [
+            (
+                shape.pattern_type.as_deref(),
+                shape.custom_pattern_id.as_deref(),
+            ),
+            (
+                shape.bg_pattern_type.as_deref(),
+                shape.bg_custom_pattern_id.as_deref(),
+            ),
+        ]
+        .into_iter()
+        .find_map(|(pattern_type, custom_id)| match pattern_type? {
+            "solid" => None,
+            "custom" => {
+                let id = custom_id.unwrap_or_default();
+                (!patterns.iter().any(|p| p.id == id))
+                    .then(|| format!("pattern '{}' not found, written as diagonal", id))
+            }
+            basic => (!BASIC_PATTERNS.iter().any(|(name, _)| *name == basic))
+                .then(|| format!("pattern '{}' not supported, written as solid", basic)),
+        });
+        match missing {
+            Some(reason) => report.approximated("hatch", shape.id.clone(), reason),
+            None => report.converted("hatch"),
+        }
+        hatches.push(shape);
+    }
+    if hatches.is_empty() {
+        return Ok(handles);
+    }
+    dxf_raw::insert_entities(path, block, version, |writer| {
+        for shape in hatches {
+            let mut fills = Vec::new();
+            if let Some(color) = shape.background_color.as_deref() {
+                fills.push(Fill {
+                    pattern_type: "solid",
+                    custom_pattern_id: None,
+                    angle: 0.0,
+                    scale: 1.0,
+                    color: Some(color),
+                });
+            }
+            if let Some(pattern_type) = shape.bg_pattern_type.as_deref() {
+                fills.push(Fill {
+                    pattern_type,
+                    custom_pattern_id: shape.bg_custom_pattern_id.as_deref(),
+                    angle: shape.bg_pattern_angle.unwrap_or(0.0),
+                    scale: shape.bg_pattern_scale.unwrap_or(1.0),
+                    color: shape.bg_fill_color.as_deref().or(shape.color.as_deref()),
+                });
+            }
+            fills.push(Fill {
+                pattern_type: shape.pattern_type.as_deref().unwrap_or("solid"),
+                custom_pattern_id: shape.custom_pattern_id.as_deref(),
+                angle: shape.pattern_angle.unwrap_or(0.0),
+                scale: shape.pattern_scale.unwrap_or(1.0),
+                color: shape.color.as_deref(),
+            });
+            // The foreground fill, written last, carries the application data
+            let mut handle = None;
+            for fill in fills {
+                handle = write_hatch(writer, shape, &fill, layer_names, patterns);
+            }
+            if let (Some(handle), Some(_)) = (handle, &shape.dxf_data) {
+                handles.push((handle, shape));
+            }
+        }
+    })?;
+    Ok(handles)
+}
+
+fn write_hatch(
+    w: &mut RawEntityWriter,
+    shape: &ShapeData,
+    fill: &Fill,
+    layer_names: &HashMap<String, String>,
+    patterns: &[HatchPatternData],
+) -> Option<String> {
+    let points = shape.points.as_deref().filter(|p| p.len() >= 3)?;
+    let (name, families) = pattern_families(fill, patterns);
+    let solid = families.is_empty();
+    let scale = if fill.scale > 0.0 { fill.scale } else { 1.0 };
+
+    let handle = w.start_entity("HATCH");
+    w.pair(100, "AcDbEntity");
+    let layer = shape.layer.as_deref().unwrap_or("0");
+    w.pair(8, layer_names.get(layer).map_or(layer, String::as_str));
+    if let Some(rgb) = fill.color.and_then(dxf_color::from_hex) {
+        w.pair(62, dxf_color::nearest_aci(rgb));
+        w.pair(420, rgb);
+    }
+    w.pair(100, "AcDbHatch");
+    for (code, value) in [
+        (10, 0.0),
+        (20, 0.0),
+        (30, 0.0),
+        (210, 0.0),
+        (220, 0.0),
+        (230, 1.0),
+    ] {
+        w.pair(code, value);
+    }
+    w.pair(2, if solid { "SOLID".to_string() } else { name });
+    w.pair(70, i32::from(solid));
+    w.pair(71, 0);
+
+    let inner_loops = shape.inner_loops.as_deref().unwrap_or(&[]);
+    w.pair(91, 1 + inner_loops.len());
+    write_polyline_path(
+        w,
+        PATH_EXTERNAL | PATH_POLYLINE,
+        points,
+        shape.bulge.as_deref().unwrap_or(&[]),
+    );
+    for inner in inner_loops {
+        write_polyline_path(w, PATH_POLYLINE, inner, &[]);
+    }
+
+    // Normal (odd parity) style, so inner loops become holes
+    w.pair(75, 0);
+    w.pair(76, if solid { 1 } else { 2 });
+    if !solid {
+        w.pair(52, fill.angle);
+        w.pair(41, scale);
+        w.pair(77, 0);
+        w.pair(78, families.len());
+        for family in &families {
+            // Pattern lines are stored already rotated and scaled
+            let angle = fill.angle.to_radians();
+            let line_angle = family.angle.to_radians() + angle;
+            let base = rotate(
+                PointData {
+                    x: family.origin_x * scale,
+                    y: family.origin_y * scale,
+                },
+                angle,
+            );
+            let offset = rotate(
+                PointData {
+                    x: family.delta_x * scale,
+                    y: family.delta_y * scale,
+                },
+                line_angle,
+            );
+            w.pair(53, line_angle.to_degrees());
+            w.pair(43, base.x);
+            w.pair(44, base.y);
+            w.pair(45, offset.x);
+            w.pair(46, offset.y);
+            w.pair(79, family.dash_pattern.len());
+            for dash in &family.dash_pattern {
+                w.pair(49, dash * scale);
+            }
+        }
+    }
+    w.pair(98, 1);
+    w.pair(10, points[0].x);
+    w.pair(20, points[0].y);
+    handle
+}
+
+fn write_polyline_path(w: &mut RawEntityWriter, flags: i64, points: &[PointData], bulges: &[f64]) {
+    let has_bulge = bulges.iter().any(|b| *b != 0.0);
+    w.pair(92, flags);
+    w.pair(72, i32::from(has_bulge));
+    w.pair(73, 1);
+    w.pair(93, points.len());
+    for (i, p) in points.iter().enumerate() {
+        w.pair(10, p.x);
+        w.pair(20, p.y);
+        if has_bulge {
+            w.pair(42, bulges.get(i).copied().unwrap_or(0.0));
+        }
+    }
+    w.pair(97, 0);
+}
+
+/// DXF pattern name and line families for a fill. Unknown custom patterns fall
+/// back to diagonal lines; an empty result means a solid fill.
+fn pattern_families(fill: &Fill, patterns: &[HatchPatternData]) -> (String, Vec<LineFamilyData>) {
+    let basic = |pattern_type: &str| {
+        BASIC_PATTERNS
+            .iter()
+            .find(|(basic, _)| *basic == pattern_type)
+            .map(|(basic, families)| {
+                let families = families
+                    .iter()
+                    .map(|&(angle, delta_x, delta_y, dashes)| LineFamilyData {
+                        angle,
+                        delta_x,
+                        delta_y,
+                        dash_pattern: dashes.to_vec(),
+                        ..Default::default()
+                    })
+                    .collect();
+                (basic.to_uppercase(), families)
+            })
+    };
+
+    match fill.pattern_type {
+        "solid" => ("SOLID".to_string(), Vec::new()),
+        "custom" => {
+            let id = fill.custom_pattern_id.unwrap_or_default();
+            match patterns.iter().find(|p| p.id == id) {
+                Some(pattern) => (
+                    sanitize_table_name(&pattern.id).to_uppercase(),
+                    pattern.line_families.clone(),
+                ),
+                None => basic("diagonal").unwrap_or_default(),
+            }
+        }
+        pattern_type => basic(pattern_type).unwrap_or_else(|| ("SOLID".to_string(), Vec::new())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dxf::Drawing;
+    use std::fs;
+
+    #[test]
+    fn pattern_angle_round_trips_in_degrees() {
+        let path = std::env::temp_dir().join(format!("hatch_angle_{}.dxf", std::process::id()));
+        let path = path.to_str().unwrap();
+        Drawing::new().save_file(path).unwrap();
+        let corners = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)];
+        let shape = ShapeData {
+            id: Some("h".to_string()),
+            shape_type: "hatch".to_string(),
+            points: Some(corners.iter().map(|&(x, y)| PointData { x, y }).collect()),
+            pattern_type: Some("diagonal".to_string()),
+            pattern_angle: Some(45.0),
+            pattern_scale: Some(2.0),
+            ..Default::default()
+        };
+        let mut report = ConversionReport::default();
+        let version = AcadVersion::R2000;
+        write_hatches(
+            path,
+            None,
+            &[shape],
+            &HashMap::new(),
+            &[],
+            version,
+            &mut report,
+        )
+        .unwrap();
+        let raw = RawRecords::read(path, |_| Ok(())).unwrap();
+        fs::remove_file(path).unwrap();
+
+        let pairs = raw.entities("HATCH").next().unwrap();
+        let angle = pairs.iter().find(|(code, _)| *code == 52).unwrap();
+        assert_eq!(angle.1.trim().parse::<f64>().unwrap(), 45.0);
+
+        let (shapes, _) = read_hatches(&raw, &HashMap::new(), &mut report);
+        assert_eq!(shapes.len(), 1);
+        assert_eq!(shapes[0].pattern_type.as_deref(), Some("diagonal"));
+        assert_eq!(shapes[0].pattern_angle, Some(45.0));
+        assert_eq!(shapes[0].pattern_scale, Some(2.0));
+    }
+}
@@ -0,0 +1,711 @@
+//! Raster images (IMAGE entities and their IMAGEDEF objects). The `dxf` crate
+//! does not read or write image definitions, so images are handled on the ASCII
+//! group codes directly, like hatches. Image files are embedded on import and
+//! referenced (or extracted next to the drawing) on export.
+
+use super::dxf_raw::{self, ClassDefinition, RawEntityWriter, RawRecords};
+use super::dxf_report::ConversionReport;
+use super::dxf_text::sanitize_table_name;
+use super::dxf_xdata::DxfEntityData;
+use super::shape::{PointData, ShapeData};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use dxf::enums::AcadVersion;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Show image, show when not aligned with the screen, use clipping boundary
+const DISPLAY_FLAGS: i32 = 1 | 2 | 4;
+
+/// Clipping boundary types (group 71)
+const CLIP_RECTANGLE: i64 = 1;
+const CLIP_POLYGON: i64 = 2;
+
+const CLASSES: [ClassDefinition; 3] = [
+    ClassDefinition {
+        name: "IMAGE",
+        class_name: "AcDbRasterImage",
+        application: "ISM",
+        proxy_flags: 2175,
+        is_entity: true,
+    },
+    ClassDefinition {
+        name: "IMAGEDEF",
+        class_name: "AcDbRasterImageDef",
+        application: "ISM",
+        proxy_flags: 0,
+        is_entity: false,
+    },
+    ClassDefinition {
+        name: "IMAGEDEF_REACTOR",
+        class_name: "AcDbRasterImageDefReactor",
+        application: "ISM",
+        proxy_flags: 1,
+        is_entity: false,
+    },
+];
+
+/// IMAGE entities were introduced with R14
+pub fn is_supported(version: AcadVersion) -> bool {
+    version >= AcadVersion::R14
+}
+
+/// Read the model space IMAGE entities of the DXF file at `path` as image shapes.
+/// Image files are looked up as stored, relative to the DXF file and by name in
+/// its folder, and embedded as data URLs; missing files keep only their path.
+pub fn read_images(
+    path: &str,
+    raw: &RawRecords,
+    entity_data: &HashMap<String, DxfEntityData>,
+    report: &mut ConversionReport,
+) -> Vec<ShapeData> {
+    let mut shapes = Vec::new();
+    let definitions: HashMap<String, String> = raw
+        .of_type("IMAGEDEF")
+        .filter_map(|pairs| Some((find(pairs, 5)?.to_uppercase(), find(pairs, 1)?.to_string())))
+        .collect();
+    let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+
+    for pairs in raw.entities("IMAGE") {
+        let handle = find(pairs, 5).map(str::to_uppercase);
+        if find(pairs, 67) == Some("1") {
+            report.skipped("IMAGE", handle, "images on layouts are not imported");
+            continue;
+        }
+        let Some(image) = parse_image(pairs) else {
+            report.skipped("IMAGE", handle, "missing insertion point, vectors or size");
+            continue;
+        };
+        let Some(file) = image
+            .definition
+            .as_ref()
+            .and_then(|definition| definitions.get(definition))
+        else {
+            report.skipped("IMAGE", handle, "image definition not found");
+            continue;
+        };
+
+        let mut shape = image.to_shape();
+        shape.source_file_name = Some(file_name(file).to_string());
+        let mut approximation = image
+            .is_mirrored()
+            .then(|| "mirrored image shown unmirrored".to_string());
+        let embedded =
+            resolve(file, directory).and_then(|found| Some((data_url(&found).ok()?, found)));
+        match embedded {
+            Some((data, found)) => {
+                shape.image_data = Some(data);
+                shape.source_path = Some(found.to_string_lossy().into_owned());
+            }
+            None => {
+                shape.source_path = Some(file.clone());
+                approximation = Some(format!("image file '{}' not found or unreadable", file));
+            }
+        }
+        if let Some(handle) = &handle {
+            shape.dxf_data = entity_data.get(handle).cloned();
+        }
+        match approximation {
+            Some(reason) => report.approximated("IMAGE", handle, reason),
+            None => report.converted("IMAGE"),
+        }
+        shapes.push(shape);
+    }
+    shapes
+}
+
+/// IMAGE entity in world coordinates; pixel coordinates start at the top-left
+/// corner of the image, with the centre of the first pixel at (0, 0)
+struct ImageEntity {
+    layer: Option<String>,
+    insert: PointData,
+    /// Width and height of one pixel, along the bottom and left edges
+    u: PointData,
+    v: PointData,
+    columns: f64,
+    rows: f64,
+    definition: Option<String>,
+    clipping: bool,
+    clip_type: i64,
+    clip_vertices: Vec<PointData>,
+    fade: f64,
+}
+
+fn parse_image(pairs: &[(i32, String)]) -> Option<ImageEntity> {
+    let mut layer = None;
+    let mut points = [None::<f64>; 8];
+    let mut definition = None;
+    let mut clipping = false;
+    let mut clip_type = CLIP_RECTANGLE;
+    let mut clip_vertices: Vec<PointData> = Vec::new();
+    let mut fade = 0.0;
+    for (code, value) in pairs {
+        let value = value.trim();
+        let number = || value.parse::<f64>().ok();
+        match code {
+            8 => layer = Some(value.to_string()),
+            10..=13 => points[(code - 10) as usize * 2] = number(),
+            20..=23 => points[(code - 20) as usize * 2 + 1] = number(),
+            340 => definition = Some(value.to_uppercase()),
+            280 => clipping = value == "1",
+            71 => clip_type = value.parse().unwrap_or(CLIP_RECTANGLE),
+            14 => clip_vertices.push(PointData {
+                x: number()?,
+                y: 0.0,
+            }),
+            24 => clip_vertices.last_mut()?.y = number()?,
+            283 => fade = number().unwrap_or(0.0),
+            _ => {}
+        }
+    }
+    let point = |index: usize| {
+        Some(PointData {
+            x: points[index * 2]?,
+            y: points[index * 2 + 1]?,
+        })
+    };
+    let size = point(3)?;
+    if size.x < 1.0 || size.y < 1.0 {
+        return None;
+    }
+    Some(ImageEntity {
+        layer,
+        insert: point(0)?,
+        u: point(1)?,
+        v: point(2)?,
+        columns: size.x,
+        rows: size.y,
+        definition,
+        clipping,
+        clip_type,
+        clip_vertices,
+        fade,
+    })
+}
+
+impl ImageEntity {
+    /// The v vector points to the right of u instead of to its left
+    fn is_mirrored(&self) -> bool {
+        self.u.x * self.v.y - self.u.y * self.v.x < 0.0
+    }
+
+    fn to_world(&self, pixel: PointData) -> PointData {
+        let a = pixel.x + 0.5;
+        let b = self.rows - pixel.y - 0.5;
+        PointData {
+            x: self.insert.x + self.u.x * a + self.v.x * b,
+            y: self.insert.y + self.u.y * a + self.v.y * b,
+        }
+    }
+
+    fn to_shape(&self) -> ShapeData {
+        // A mirrored image has its top edge at the insertion point
+        let position = if self.is_mirrored() {
+            self.insert
+        } else {
+            PointData {
+                x: self.insert.x + self.v.x * self.rows,
+                y: self.insert.y + self.v.y * self.rows,
+            }
+        };
+        let clip_points = match (self.clip_type, self.clip_vertices.as_slice()) {
+            _ if !self.clipping => None,
+            (CLIP_RECTANGLE, [a, b]) => Some(vec![
+                *a,
+                PointData { x: b.x, y: a.y },
+                *b,
+                PointData { x: a.x, y: b.y },
+            ]),
+            (CLIP_POLYGON, [first, .., last]) if self.clip_vertices.len() >= 3 => {
+                let mut vertices = self.clip_vertices.clone();
+                if (first.x - last.x).abs() < 1e-9 && (first.y - last.y).abs() < 1e-9 {
+                    vertices.pop();
+                }
+                Some(vertices)
+            }
+            _ => None,
+        };
+        ShapeData {
+            shape_type: "image".to_string(),
+            layer: self.layer.clone(),
+            position: Some(position),
+            rotation: Some(self.u.y.atan2(self.u.x)),
+            width: Some(self.u.x.hypot(self.u.y) * self.columns),
+            height: Some(self.v.x.hypot(self.v.y) * self.rows),
+            original_width: Some(self.columns),
+            original_height: Some(self.rows),
+            opacity: Some((1.0 - self.fade / 100.0).clamp(0.0, 1.0)),
+            is_underlay: Some(true),
+            clip_points: clip_points
+                .map(|points| points.into_iter().map(|p| self.to_world(p)).collect()),
+            ..Default::default()
+        }
+    }
+}
+
+fn find(pairs: &[(i32, String)], code: i32) -> Option<&str> {
+    pairs
+        .iter()
+        .find(|(c, _)| *c == code)
+        .map(|(_, value)| value.trim())
+}
+
+/// File name of a path stored on any platform
+fn file_name(file: &str) -> &str {
+    file.rsplit(['/', '\\']).next().unwrap_or(file)
+}
+
+/// Image file as stored, relative to the DXF file or by name in its folder
+fn resolve(file: &str, directory: &Path) -> Option<PathBuf> {
+    let stored = PathBuf::from(file.replace('\\', "/"));
+    [
+        stored.is_absolute().then(|| PathBuf::from(file)),
+        Some(directory.join(&stored)),
+        Some(directory.join(file_name(file))),
+    ]
+    .into_iter()
+    .flatten()
+    .find(|candidate| candidate.is_file())
+}
+
+/// File content as a data URL, with the media type taken from the extension
+pub fn data_url(file: &Path) -> io::Result<String> {
+    let bytes = fs::read(file)?;
+    let extension = file
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let mime = match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "bmp" => "image/bmp",
+        "tif" | "tiff" => "image/tiff",
+        "webp" => "image/webp",
+        _ => "application/octet-stream",
+    };
+    Ok(format!("data:{};base64,{}", mime, STANDARD.encode(bytes)))
+}
+
+/// Corners of the image frame, clockwise from the top-left `position`
+pub fn corners(shape: &ShapeData) -> Option<[PointData; 4]> {
+    let position = shape.position?;
+    let (width, height) = (shape.width?, shape.height?);
+    let (sin, cos) = shape.rotation.unwrap_or(0.0).sin_cos();
+    let along = PointData {
+        x: width * cos,
+        y: width * sin,
+    };
+    let down = PointData {
+        x: height * sin,
+        y: -height * cos,
+    };
+    Some([
+        position,
+        PointData {
+            x: position.x + along.x,
+            y: position.y + along.y,
+        },
+        PointData {
+            x: position.x + along.x + down.x,
+            y: position.y + along.y + down.y,
+        },
+        PointData {
+            x: position.x + down.x,
+            y: position.y + down.y,
+        },
+    ])
+}
+
+/// Image to write, with the file its definition refers to
+struct ExportedImage<'s> {
+    shape: &'s ShapeData,
+    file: String,
+    columns: f64,
+    rows: f64,
+}
+
+/// IMAGEDEF shared by the images of one file
+struct Definition<'a> {
+    image: &'a ExportedImage<'a>,
+    handle: String,
+    /// (reactor, image) handles
+    reactors: Vec<(String, String)>,
+}
+
+/// Write the image shapes as IMAGE entities with their IMAGEDEF objects into an
+/// already saved ASCII DXF file (R14 or later), in model space or in the
+/// definition of `block`. Images whose source file still
+/// exists refer to it; embedded images are written next to the DXF file as
+/// `<name>-<n>.<ext>`. Images draw behind everything else. Returns the handles
+/// of the images written for shapes with application data, for
+/// `dxf_xdata::write_entity_data`.
+pub fn write_images<'s>(
+    path: &str,
+    block: Option<&str>,
+    shapes: &'s [ShapeData],
+    layer_names: &HashMap<String, String>,
+    version: AcadVersion,
+    report: &mut ConversionReport,
+) -> io::Result<Vec<(String, &'s ShapeData)>> {
+    let mut handles = Vec::new();
+    let mut images = Vec::new();
+    let mut extracted: HashMap<&str, String> = HashMap::new();
+    for shape in shapes.iter().filter(|s| s.shape_type == "image") {
+        let (Some(_), Some(width), Some(height)) = (
+            shape.position,
+            shape.width.filter(|w| *w > 0.0),
+            shape.height.filter(|h| *h > 0.0),
+        ) else {
+            report.skipped("image", shape.id.clone(), "missing position or size");
+            continue;
+        };
+        let source = shape
+            .source_path
+            .as_deref()
+            .filter(|source| Path::new(source).is_file());
+        let file = match (source, shape.image_data.as_deref()) {
+            (Some(source), _) => source.to_string(),
+            (None, Some(data)) => match extracted.get(data) {
+                Some(file) => file.clone(),
+                None => {
+                    let Some(file) = extract(path, data, extracted.len() + 1)? else {
+                        report.skipped("image", shape.id.clone(), "unreadable image data");
+                        continue;
+                    };
+                    extracted.insert(data, file.clone());
+                    file
+                }
+            },
+            (None, None) => {
+                report.skipped("image", shape.id.clone(), "no image data or source file");
+                continue;
+            }
+        };
+        // Without the pixel size, one pixel per drawing unit keeps the frame
+        let pixels = shape
+            .original_width
+            .zip(shape.original_height)
+            .filter(|(w, h)| *w >= 1.0 && *h >= 1.0);
+        let (columns, rows) = pixels.unwrap_or((width.round().max(1.0), height.round().max(1.0)));
+        if pixels.is_none() {
+            report.approximated(
+                "image",
+                shape.id.clone(),
+                "pixel size unknown, written as one pixel per drawing unit",
+            );
+        } else {
+            report.converted("image");
+        }
+        images.push(ExportedImage {
+            shape,
+            file,
+            columns,
+            rows,
+        });
+    }
+    if images.is_empty() {
+        return Ok(handles);
+    }
+
+    let mut definitions: Vec<Definition> = Vec::new();
+    let mut dictionary = None;
+    dxf_raw::insert_entities(path, block, version, |w| {
+        dictionary = w.allocate_handle();
+        for image in &images {
+            let index = match definitions.iter().position(|d| d.image.file == image.file) {
+                Some(index) => index,
+                None => {
+                    let Some(handle) = w.allocate_handle() else {
+                        return;
+                    };
+                    definitions.push(Definition {
+                        image,
+                        handle,
+                        reactors: Vec::new(),
+                    });
+                    definitions.len() - 1
+                }
+            };
+            let (Some(handle), Some(reactor)) = (w.start_entity("IMAGE"), w.allocate_handle())
+            else {
+                return;
+            };
+            write_image(w, image, &definitions[index].handle, &reactor, layer_names);
+            definitions[index].reactors.push((reactor, handle.clone()));
+            if image.shape.dxf_data.is_some() {
+                handles.push((handle, image.shape));
+            }
+        }
+    })?;
+    let Some(dictionary) = dictionary else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "DXF file has no handle seed for image definitions",
+        ));
+    };
+
+    dxf_raw::add_classes(path, &CLASSES)?;
+    dxf_raw::append_objects(
+        path,
+        &[("ACAD_IMAGE_DICT", dictionary.as_str())],
+        version,
+        |root, out| {
+            write_definitions(out, root, &dictionary, &definitions);
+        },
+    )?;
+    Ok(handles)
+}
+
+fn write_image(
+    w: &mut RawEntityWriter,
+    image: &ExportedImage,
+    definition: &str,
+    reactor: &str,
+    layer_names: &HashMap<String, String>,
+) {
+    let shape = image.shape;
+    let [top_left, top_right, _, bottom_left] = corners(shape).unwrap_or_default();
+    let u = PointData {
+        x: (top_right.x - top_left.x) / image.columns,
+        y: (top_right.y - top_left.y) / image.columns,
+    };
+    let v = PointData {
+        x: (top_left.x - bottom_left.x) / image.rows,
+        y: (top_left.y - bottom_left.y) / image.rows,
+    };
+
+    w.pair(100, "AcDbEntity");
+    let layer = shape.layer.as_deref().unwrap_or("0");
+    w.pair(8, layer_names.get(layer).map_or(layer, String::as_str));
+    w.pair(100, "AcDbRasterImage");
+    w.pair(90, 0);
+    for (code, point) in [(10, bottom_left), (11, u), (12, v)] {
+        w.pair(code, point.x);
+        w.pair(code + 10, point.y);
+        w.pair(code + 20, 0.0);
+    }
+    w.pair(13, image.columns);
+    w.pair(23, image.rows);
+    w.pair(340, definition);
+    w.pair(70, DISPLAY_FLAGS);
+
+    // Pixel coordinates of the clipping polygon, closed by repeating its start
+    let to_pixel = |p: &PointData| {
+        let (x, y) = (p.x - bottom_left.x, p.y - bottom_left.y);
+        let determinant = u.x * v.y - u.y * v.x;
+        let a = (x * v.y - y * v.x) / determinant;
+        let b = (u.x * y - u.y * x) / determinant;
+        PointData {
+            x: a - 0.5,
+            y: image.rows - b - 0.5,
+        }
+    };
+    let clip: Vec<PointData> = match shape.clip_points.as_deref() {
+        Some(points @ [first, ..]) if points.len() >= 3 => {
+            points.iter().chain([first]).map(to_pixel).collect()
+        }
+        _ => Vec::new(),
+    };
+    w.pair(280, i32::from(!clip.is_empty()));
+    w.pair(281, 50);
+    w.pair(282, 50);
+    let fade = (1.0 - shape.opacity.unwrap_or(1.0)).clamp(0.0, 1.0) * 100.0;
+    w.pair(283, fade.round() as i32);
+    w.pair(360, reactor);
+    if clip.is_empty() {
+        w.pair(71, CLIP_RECTANGLE);
+        w.pair(91, 2);
+        for (x, y) in [(-0.5, -0.5), (image.columns - 0.5, image.rows - 0.5)] {
+            w.pair(14, x);
+            w.pair(24, y);
+        }
+    } else {
+        w.pair(71, CLIP_POLYGON);
+        w.pair(91, clip.len());
+        for point in &clip {
+            w.pair(14, point.x);
+            w.pair(24, point.y);
+        }
+    }
+}
+
+/// ACAD_IMAGE_DICT with one IMAGEDEF per file and one IMAGEDEF_REACTOR per image
+fn write_definitions(
+    out: &mut Vec<String>,
+    root: &str,
+    dictionary: &str,
+    definitions: &[Definition],
+) {
+    use dxf_raw::push_pair;
+
+    push_pair(out, 0, "DICTIONARY");
+    push_pair(out, 5, dictionary);
+    push_pair(out, 102, "{ACAD_REACTORS");
+    push_pair(out, 330, root);
+    push_pair(out, 102, "}");
+    push_pair(out, 330, root);
+    push_pair(out, 100, "AcDbDictionary");
+    push_pair(out, 281, 1);
+    let mut names: Vec<String> = Vec::new();
+    for definition in definitions {
+        let stem = Path::new(&definition.image.file.replace('\\', "/"))
+            .file_stem()
+            .map(|stem| sanitize_table_name(&stem.to_string_lossy()))
+            .unwrap_or_default();
+        let mut name = if stem.is_empty() {
+            "IMAGE".to_string()
+        } else {
+            stem
+        };
+        if names.iter().any(|n| n.eq_ignore_ascii_case(&name)) {
+            name = format!("{}_{}", name, names.len() + 1);
+        }
+        push_pair(out, 3, &name);
+        push_pair(out, 350, &definition.handle);
+        names.push(name);
+    }
+
+    for definition in definitions {
+        let image = definition.image;
+        push_pair(out, 0, "IMAGEDEF");
+        push_pair(out, 5, &definition.handle);
+        push_pair(out, 102, "{ACAD_REACTORS");
+        push_pair(out, 330, dictionary);
+        for (reactor, _) in &definition.reactors {
+            push_pair(out, 330, reactor);
+        }
+        push_pair(out, 102, "}");
+        push_pair(out, 330, dictionary);
+        push_pair(out, 100, "AcDbRasterImageDef");
+        push_pair(out, 90, 0);
+        push_pair(out, 1, &image.file);
+        push_pair(out, 10, image.columns);
+        push_pair(out, 20, image.rows);
+        // Pixel size in AutoCAD units; 0 (no units) below
+        push_pair(out, 11, 1.0);
+        push_pair(out, 21, 1.0);
+        push_pair(out, 280, 1);
+        push_pair(out, 281, 0);
+    }
+
+    for definition in definitions {
+        for (reactor, image) in &definition.reactors {
+            push_pair(out, 0, "IMAGEDEF_REACTOR");
+            push_pair(out, 5, reactor);
+            push_pair(out, 330, image);
+            push_pair(out, 100, "AcDbRasterImageDefReactor");
+            push_pair(out, 90, 2);
+            push_pair(out, 330, image);
+        }
+    }
+}
+
+/// Decode a data URL and write it next to the DXF file. Returns the file name,
+/// which the definition stores relative to the drawing.
+fn extract(path: &str, data: &str, index: usize) -> io::Result<Option<String>> {
+    let Some((header, encoded)) = data.split_once(',') else {
+        return Ok(None);
+    };
+    let Ok(bytes) = STANDARD.decode(encoded.trim()) else {
+        return Ok(None);
+    };
+    let extension = match header.trim_start_matches("data:").split(';').next() {
+        Some("image/jpeg") => "jpg",
+        Some("image/gif") => "gif",
+        Some("image/bmp") => "bmp",
+        Some("image/tiff") => "tif",
+        Some("image/webp") => "webp",
+        _ => "png",
+    };
+    let dxf = Path::new(path);
+    let stem = dxf.file_stem().unwrap_or_default().to_string_lossy();
+    let name = format!("{}-{}.{}", stem, index, extension);
+    fs::write(dxf.with_file_name(&name), bytes)?;
+    Ok(Some(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::temp_dir;
+    use super::*;
+    use dxf::objects::{Dictionary, Object, ObjectType};
+    use dxf::Drawing;
+
+    fn close(a: PointData, b: PointData) -> bool {
+        (a.x - b.x).abs() < 1e-6 && (a.y - b.y).abs() < 1e-6
+    }
+
+    #[test]
+    fn images_round_trip() {
+        let dir = temp_dir("dxf_image_round_trip");
+        let path = dir.join("plan.dxf");
+        let path = path.to_str().unwrap();
+        let mut drawing = Drawing::new();
+        drawing.header.version = AcadVersion::R2018;
+        drawing.add_object(Object::new(ObjectType::Dictionary(Dictionary::default())));
+        drawing.save_file(path).unwrap();
+        let data = format!(
+            "data:image/png;base64,{}",
+            STANDARD.encode(b"not really a png")
+        );
+        let image = ShapeData {
+            shape_type: "image".to_string(),
+            position: Some(PointData { x: 10.0, y: 20.0 }),
+            width: Some(40.0),
+            height: Some(30.0),
+            rotation: Some(0.5),
+            original_width: Some(400.0),
+            original_height: Some(300.0),
+            opacity: Some(0.75),
+            image_data: Some(data.clone()),
+            ..Default::default()
+        };
+        let clipped = ShapeData {
+            position: Some(PointData { x: 0.0, y: 100.0 }),
+            rotation: Some(0.0),
+            clip_points: Some(vec![
+                PointData { x: 5.0, y: 95.0 },
+                PointData { x: 25.0, y: 95.0 },
+                PointData { x: 15.0, y: 80.0 },
+            ]),
+            ..image.clone()
+        };
+        let mut report = ConversionReport::default();
+        let shapes = [image.clone(), clipped.clone()];
+        write_images(
+            path,
+            None,
+            &shapes,
+            &HashMap::new(),
+            AcadVersion::R2018,
+            &mut report,
+        )
+        .unwrap();
+        let raw = RawRecords::read(path, |_| Ok(())).unwrap();
+        let read = read_images(path, &raw, &HashMap::new(), &mut report);
+        let definitions = raw.of_type("IMAGEDEF").count();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(definitions, 1);
+        assert_eq!(read.len(), 2);
+        for (read, written) in read.iter().zip(&shapes) {
+            assert!(close(read.position.unwrap(), written.position.unwrap()));
+            assert!((read.width.unwrap() - 40.0).abs() < 1e-6);
+            assert!((read.height.unwrap() - 30.0).abs() < 1e-6);
+            assert!((read.rotation.unwrap() - written.rotation.unwrap()).abs() < 1e-9);
+            assert_eq!(read.original_width, Some(400.0));
+            assert_eq!(read.opacity, Some(0.75));
+            assert_eq!(read.image_data.as_deref(), Some(data.as_str()));
+            assert_eq!(read.source_file_name.as_deref(), Some("plan-1.png"));
+        }
+        assert!(read[0].clip_points.is_none());
+        let clip = read[1].clip_points.as_ref().unwrap();
+        assert_eq!(clip.len(), 3);
+        for (read, written) in clip.iter().zip(clipped.clip_points.as_ref().unwrap()) {
+            assert!(close(*read, *written));
+        }
+    }
+}
@@ -0,0 +1,1095 @@
+//! Conversion of DXF entities into exchange shapes.
+
+use super::dxf_blocks::{self, GroupData};
+use super::dxf_hatch::BlockHatch;
+use super::dxf_raw::{self, RawRecords};
+use super::dxf_report::ConversionReport;
+use super::dxf_transform::{self, Transform};
+use super::dxf_xdata::{self, DxfEntityData};
+use super::shape::{DxfDocument, PointData, ShapeData};
+use super::{
+    dxf_color, dxf_dimensions, dxf_hatch, dxf_image, dxf_layers, dxf_layouts, dxf_linetypes,
+    dxf_ocs, dxf_origin, dxf_text,
+};
+use dxf::entities::{
+    Ellipse, Entity, EntityCommon, EntityType, Insert, LwPolyline, Polyline, Spline,
+};
+use dxf::enums::Units;
+use dxf::Drawing;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::f64::consts::PI;
+use std::fmt;
+use std::io;
+
+/// Polyline vertex flag marking a spline frame control point (not on the curve)
+const VERTEX_SPLINE_FRAME: i32 = 16;
+
+/// Polyline flags for 3D polylines and for polygon or polyface meshes
+pub const POLYLINE_3D: i32 = 8;
+pub const POLYLINE_MESH: i32 = 16 | 64;
+
+/// Nesting depth after which INSERTs are kept as references (guards recursive blocks)
+const MAX_BLOCK_DEPTH: usize = 32;
+
+/// Import settings chosen in the import dialog
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DxfImportOptions {
+    /// Explode INSERTs into their block contents instead of keeping block references
+    pub explode_blocks: bool,
+    /// Bring ATTRIB values across as text shapes instead of key/value metadata
+    pub attributes_as_text: bool,
+    /// Base point subtracted from model space coordinates once they are in
+    /// millimetres, e.g. the one stored with the project by an earlier import
+    pub origin: Option<PointData>,
+    /// Pick a base point from the extents when no `origin` is given and the
+    /// drawing lies far from the origin
+    pub detect_origin: bool,
+    /// Place the content in an existing drawing instead of keeping its coordinates
+    pub placement: Option<DxfPlacement>,
+    /// Source layer name -> project layer name (source names match case-insensitively)
+    pub layer_map: BTreeMap<String, String>,
+    /// Import only entities on these source layers; `None` imports all layers
+    pub layers: Option<Vec<String>>,
+    /// Import only these paper space layouts; `None` imports all layouts
+    pub layouts: Option<Vec<String>>,
+}
+
+impl Default for DxfImportOptions {
+    fn default() -> Self {
+        Self {
+            explode_blocks: true,
+            attributes_as_text: false,
+            origin: None,
+            detect_origin: true,
+            placement: None,
+            layer_map: BTreeMap::new(),
+            layers: None,
+            layouts: None,
+        }
+    }
+}
+
+impl DxfImportOptions {
+    pub fn includes_layer(&self, layer: &str) -> bool {
+        includes(self.layers.as_deref(), layer)
+    }
+
+    pub fn includes_layout(&self, layout: &str) -> bool {
+        includes(self.layouts.as_deref(), layout)
+    }
+}
+
+fn includes(filter: Option<&[String]>, name: &str) -> bool {
+    match filter {
+        Some(names) => names.iter().any(|n| n.eq_ignore_ascii_case(name)),
+        None => true,
+    }
+}
+
+/// Where and how imported content lands in an existing drawing
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DxfPlacement {
+    /// Project point the source base point (`$INSBASE`) is placed at
+    pub insertion_point: PointData,
+    /// Scale on top of the conversion from the source units
+    pub scale: f64,
+    /// Counter-clockwise rotation in radians
+    pub rotation: f64,
+    /// "mm" | "cm" | "m" | "in" | "ft"; `None` reads `$INSUNITS` (unitless files
+    /// are taken as millimetres)
+    pub source_units: Option<String>,
+}
+
+impl Default for DxfPlacement {
+    fn default() -> Self {
+        Self {
+            insertion_point: PointData { x: 0.0, y: 0.0 },
+            scale: 1.0,
+            rotation: 0.0,
+            source_units: None,
+        }
+    }
+}
+
+/// Source coordinates to project millimetres: the placement when content goes
+/// into an existing drawing, else the `$INSUNITS` conversion
+pub fn model_transform(drawing: &Drawing, options: &DxfImportOptions) -> Result<Transform, String> {
+    match &options.placement {
+        Some(placement) => placement.transform(drawing),
+        None => {
+            let unit = unit_length(drawing.header.default_drawing_units);
+            Ok(Transform::scale(unit, unit))
+        }
+    }
+}
+
+impl DxfPlacement {
+    /// Source coordinates to project millimetres
+    pub fn transform(&self, drawing: &Drawing) -> Result<Transform, String> {
+        let unit = match self.source_units.as_deref() {
+            Some("mm") => 1.0,
+            Some("cm") => 10.0,
+            Some("m") => 1000.0,
+            Some("in") => 25.4,
+            Some("ft") => 304.8,
+            Some(other) => return Err(format!("Unsupported drawing unit '{}'", other)),
+            None => unit_length(drawing.header.default_drawing_units),
+        };
+        if self.scale <= 0.0 {
+            return Err("Import scale must be positive".to_string());
+        }
+        let scale = unit * self.scale;
+        let base = &drawing.header.insertion_base;
+        Ok(
+            Transform::translate(self.insertion_point.x, self.insertion_point.y)
+                .then_after(&Transform::rotate(self.rotation))
+                .then_after(&Transform::scale(scale, scale))
+                .then_after(&Transform::translate(-base.x, -base.y)),
+        )
+    }
+}
+
+/// Length of one `$INSUNITS` unit in millimetres; unitless and rarely used
+/// units count as millimetres
+fn unit_length(units: Units) -> f64 {
+    match units {
+        Units::Inches => 25.4,
+        Units::Feet => 304.8,
+        Units::Yards => 914.4,
+        Units::Miles => 1_609_344.0,
+        Units::Centimeters => 10.0,
+        Units::Decimeters => 100.0,
+        Units::Meters => 1000.0,
+        Units::Kilometers => 1_000_000.0,
+        Units::Microns => 0.001,
+        _ => 1.0,
+    }
+}
+
+/// Shapes converted from a sequence of entities, with the groups created for
+/// exploded INSERTs
+#[derive(Debug, Default)]
+pub struct ImportedShapes {
+    pub shapes: Vec<ShapeData>,
+    pub groups: Vec<GroupData>,
+    pub report: ConversionReport,
+}
+
+/// Model space entities converted between two cancellation checks
+const CHUNK_SIZE: usize = 2000;
+
+/// Why an import stopped early
+#[derive(Debug)]
+pub enum ImportStop {
+    Cancelled,
+    Failed(String),
+}
+
+impl fmt::Display for ImportStop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportStop::Cancelled => f.write_str("DXF import cancelled"),
+            ImportStop::Failed(message) => f.write_str(message),
+        }
+    }
+}
+
+/// Progress reporting and cancellation of `import_drawing`; `()` does neither
+pub trait ImportHooks {
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+
+    /// Bytes read of the pass over the group codes the `dxf` crate skips
+    fn reading(&self, _bytes_read: u64) {}
+
+    /// `processed` of the `total` model space entities converted, in `stage`
+    /// "hatches" | "entities" | "layouts"
+    fn converting(&self, _stage: &'static str, _processed: usize, _total: usize) {}
+
+    /// Hand model space shapes to `shapes` as they are converted instead of
+    /// returning them with the document
+    fn streams_shapes(&self) -> bool {
+        false
+    }
+
+    /// The next model space shapes in drawing order, already placed and with
+    /// their layers renamed, when `streams_shapes`
+    fn shapes(&self, _shapes: Vec<ShapeData>) {}
+}
+
+impl ImportHooks for () {}
+
+fn check(hooks: &impl ImportHooks) -> Result<(), ImportStop> {
+    if hooks.is_cancelled() {
+        Err(ImportStop::Cancelled)
+    } else {
+        Ok(())
+    }
+}
+
+/// Convert a loaded drawing into the document handed to the frontend, shared
+/// by `import_dxf` and the background import, which gets the model space
+/// shapes through `hooks` as they are converted
+pub fn import_drawing(
+    path: &str,
+    drawing: &Drawing,
+    options: &DxfImportOptions,
+    hooks: &impl ImportHooks,
+) -> Result<DxfDocument, ImportStop> {
+    let model = model_transform(drawing, options).map_err(ImportStop::Failed)?;
+
+    // Layer flags, true colours, hatches, images, viewports and application data
+    // are not exposed by the dxf crate; they are read from the group codes
+    let mut report = ConversionReport::default();
+    let raw = read_raw_records(path, hooks, &mut report)?;
+    check(hooks)?;
+
+    // Paper space entities belong to layouts and are read as sheets
+    let entities: Vec<_> = drawing
+        .entities()
+        .filter(|entity| !entity.common.is_in_paper_space)
+        .collect();
+    hooks.converting("hatches", 0, entities.len());
+    // XDATA and extension dictionaries are kept as they are for the export
+    let entity_data = dxf_xdata::read_entity_data(&raw);
+    let mut shapes = dxf_image::read_images(path, &raw, &entity_data, &mut report);
+    let (hatches, mut hatch_patterns) = dxf_hatch::read_hatches(&raw, &entity_data, &mut report);
+    shapes.extend(hatches);
+    shapes.retain(|shape| options.includes_layer(shape.layer.as_deref().unwrap_or("0")));
+    let block_hatches =
+        dxf_hatch::read_block_hatches(&raw, &entity_data, &mut hatch_patterns, &mut report);
+
+    // Project coordinates are millimetres. Content placed into an existing drawing
+    // takes its coordinates; otherwise georeferenced drawings are moved near the
+    // origin for precise rendering. Streamed shapes are placed before all are
+    // known, so their origin comes from the header extents.
+    let streaming = hooks.streams_shapes();
+    let mut origin = match (&options.placement, options.origin) {
+        (Some(_), _) => None,
+        (None, Some(origin)) => Some(origin),
+        (None, None) if options.detect_origin && streaming => header_origin(drawing, &model),
+        (None, None) => None,
+    };
+    let placed = match origin {
+        Some(origin) => Transform::translate(-origin.x, -origin.y).then_after(&model),
+        None => model,
+    };
+    let send = |shapes: &mut Vec<ShapeData>| {
+        if !streaming || shapes.is_empty() {
+            return;
+        }
+        let mut shapes = std::mem::take(shapes);
+        if !placed.is_identity() {
+            dxf_origin::transform_model(&mut shapes, &mut [], &placed);
+        }
+        dxf_layers::rename_shape_layers(&mut shapes, &options.layer_map);
+        hooks.shapes(shapes);
+    };
+    // Images and hatches go first so they draw behind the other geometry
+    send(&mut shapes);
+
+    let mut importer = Importer::new(drawing, options)
+        .with_entity_data(&entity_data)
+        .with_block_hatches(&block_hatches);
+    for (index, chunk) in entities.chunks(CHUNK_SIZE).enumerate() {
+        check(hooks)?;
+        importer.push_entities(chunk.iter().copied());
+        send(&mut importer.shapes);
+        let processed = (index * CHUNK_SIZE + chunk.len()).min(entities.len());
+        hooks.converting("entities", processed, entities.len());
+    }
+    check(hooks)?;
+
+    hooks.converting("layouts", entities.len(), entities.len());
+    let imported = importer.finish();
+    shapes.extend(imported.shapes);
+    report.merge(imported.report);
+    let mut groups = imported.groups;
+    let mut sheets = dxf_layouts::read_layouts(&raw, drawing, options, &mut groups, &mut report);
+
+    if streaming {
+        if !placed.is_identity() {
+            dxf_origin::transform_model(&mut [], &mut sheets, &placed);
+        }
+    } else {
+        if !model.is_identity() {
+            dxf_origin::transform_model(&mut shapes, &mut sheets, &model);
+        }
+        if options.placement.is_none() && options.origin.is_none() && options.detect_origin {
+            origin = dxf_origin::detect(&shapes);
+        }
+        if let Some(origin) = origin {
+            dxf_origin::shift(&mut shapes, &mut sheets, origin);
+        }
+    }
+    let mut document = DxfDocument {
+        layers: dxf_layers::read_layers(drawing, &dxf_raw::layer_groups(&raw)),
+        line_types: dxf_linetypes::read_line_types(drawing),
+        blocks: dxf_blocks::read_blocks(drawing, &entity_data, &block_hatches),
+        groups,
+        hatch_patterns,
+        shapes,
+        sheets,
+        origin,
+        report,
+    };
+    dxf_layers::rename_layers(&mut document, &options.layer_map);
+    Ok(document)
+}
+
+/// Base point from the `$EXTMIN`/`$EXTMAX` header extents in project
+/// millimetres; files that do not keep them get none
+fn header_origin(drawing: &Drawing, model: &Transform) -> Option<PointData> {
+    let corners = [
+        &drawing.header.minimum_drawing_extents,
+        &drawing.header.maximum_drawing_extents,
+    ]
+    .map(|corner| model.apply(to_point_data(corner)));
+    let min = PointData {
+        x: corners[0].x.min(corners[1].x),
+        y: corners[0].y.min(corners[1].y),
+    };
+    let max = PointData {
+        x: corners[0].x.max(corners[1].x),
+        y: corners[0].y.max(corners[1].y),
+    };
+    dxf_origin::detect_extents(min, max)
+}
+
+/// Read the records of `path` the `dxf` crate does not expose. Binary and
+/// unreadable files yield none, which is reported since their hatches, images,
+/// viewports and application data are then lost.
+fn read_raw_records(
+    path: &str,
+    hooks: &impl ImportHooks,
+    report: &mut ConversionReport,
+) -> Result<RawRecords, ImportStop> {
+    const LOST: &str = "hatches, images, viewports, XDATA, extension dictionaries and \
+                        layer flags were not read";
+    let progress = |bytes_read| {
+        if hooks.is_cancelled() {
+            return Err(io::Error::other("import cancelled"));
+        }
+        hooks.reading(bytes_read);
+        Ok(())
+    };
+    match RawRecords::read(path, progress) {
+        Ok(raw) if raw.binary => {
+            let reason = format!(
+                "binary DXF: {}; save the file as ASCII DXF to keep them",
+                LOST
+            );
+            report.skipped("DXF", None, reason);
+            Ok(raw)
+        }
+        Ok(raw) => {
+            for (entity_type, handle) in &raw.unread_entities {
+                report.skipped(entity_type, handle.clone(), "entity type not supported");
+            }
+            Ok(raw)
+        }
+        Err(_) if hooks.is_cancelled() => Err(ImportStop::Cancelled),
+        Err(e) => {
+            report.skipped("DXF", None, format!("{}: {}", e, LOST));
+            Ok(RawRecords::default())
+        }
+    }
+}
+
+/// Layer, colour and linetype of an entity after resolving layer "0" and ByBlock
+/// against the INSERT it is placed by
+#[derive(Debug, Clone)]
+struct Appearance {
+    layer: String,
+    color: Option<String>,
+    line_type: Option<String>,
+}
+
+impl Appearance {
+    fn resolve(common: &EntityCommon, parent: Option<&Appearance>) -> Self {
+        let layer = match parent {
+            Some(parent) if common.layer == "0" => parent.layer.clone(),
+            _ => common.layer.clone(),
+        };
+        let color = if common.color.is_by_block() {
+            parent.and_then(|p| p.color.clone())
+        } else {
+            dxf_color::entity_color(&common.color, common.color_24_bit)
+        };
+        let line_type = match common.line_type_name.to_ascii_uppercase().as_str() {
+            "BYLAYER" => None,
+            "BYBLOCK" => parent.and_then(|p| p.line_type.clone()),
+            _ => Some(common.line_type_name.clone()),
+        };
+        Self {
+            layer,
+            color,
+            line_type,
+        }
+    }
+
+    fn apply(&self, shape: &mut ShapeData) {
+        shape.layer = Some(self.layer.clone());
+        shape.color = self.color.clone();
+        shape.line_type = self.line_type.clone();
+    }
+}
+
+/// Walks entities, expanding INSERTs into their block contents
+pub struct Importer<'a> {
+    drawing: &'a Drawing,
+    options: &'a DxfImportOptions,
+    /// Application data of top-level entities, by handle
+    entity_data: Option<&'a HashMap<String, DxfEntityData>>,
+    /// Hatches of the block definitions, placed with exploded INSERTs
+    block_hatches: Option<&'a HashMap<String, Vec<BlockHatch>>>,
+    shapes: Vec<ShapeData>,
+    groups: Vec<GroupData>,
+    report: ConversionReport,
+}
+
+impl<'a> Importer<'a> {
+    pub fn new(drawing: &'a Drawing, options: &'a DxfImportOptions) -> Self {
+        Self {
+            drawing,
+            options,
+            entity_data: None,
+            block_hatches: None,
+            shapes: Vec::new(),
+            groups: Vec::new(),
+            report: ConversionReport::default(),
+        }
+    }
+
+    pub fn finish(self) -> ImportedShapes {
+        ImportedShapes {
+            shapes: self.shapes,
+            groups: self.groups,
+            report: self.report,
+        }
+    }
+
+    /// Keep the XDATA and extension dictionaries of top-level entities on their shapes
+    pub fn with_entity_data(mut self, entity_data: &'a HashMap<String, DxfEntityData>) -> Self {
+        self.entity_data = Some(entity_data);
+        self
+    }
+
+    /// Place the hatches of block definitions, which the dxf crate does not
+    /// read, with the contents of exploded INSERTs
+    pub fn with_block_hatches(
+        mut self,
+        block_hatches: &'a HashMap<String, Vec<BlockHatch>>,
+    ) -> Self {
+        self.block_hatches = Some(block_hatches);
+        self
+    }
+
+    /// Application data of a top-level entity of model space or a block
+    /// definition; block contents placed by an exploded INSERT would repeat it
+    /// for every copy
+    fn entity_data(&self, entity: &Entity, depth: usize) -> Option<DxfEntityData> {
+        if depth > 0 {
+            return None;
+        }
+        self.entity_data?.get(&handle(entity)?).cloned()
+    }
+
+    /// Convert top-level entities (model space or a block definition)
+    pub fn push_entities<'e>(&mut self, entities: impl Iterator<Item = &'e Entity>) {
+        for entity in entities {
+            self.push_entity(entity, &Transform::identity(), None, None, 0);
+        }
+    }
+
+    /// Convert one entity placed by `xf`, inheriting from the INSERT that placed it
+    fn push_entity(
+        &mut self,
+        entity: &Entity,
+        xf: &Transform,
+        parent: Option<&Appearance>,
+        group_id: Option<&str>,
+        depth: usize,
+    ) {
+        let appearance = Appearance::resolve(&entity.common, parent);
+        if !self.options.includes_layer(&appearance.layer) {
+            return;
+        }
+        match &entity.specific {
+            EntityType::Insert(insert) => {
+                self.push_insert(entity, insert, &appearance, xf, group_id, depth);
+            }
+            // ATTDEFs are templates; the values come from the INSERT's ATTRIBs
+            EntityType::AttributeDefinition(_) => {}
+            // Model space images are read with their definitions by `dxf_image`
+            EntityType::Image(_) if depth == 0 => {}
+            EntityType::Image(_) => {
+                self.report.skipped(
+                    "IMAGE",
+                    handle(entity),
+                    "images inside blocks are not imported",
+                );
+            }
+            specific => {
+                let entity_type = entity_type_name(specific);
+                let Some(mut shape) = entity_to_shape(entity, self.drawing) else {
+                    let reason = if is_supported(specific) {
+                        "degenerate geometry"
+                    } else {
+                        "entity type not supported"
+                    };
+                    self.report.skipped(&entity_type, handle(entity), reason);
+                    return;
+                };
+                match approximation(specific) {
+                    Some(reason) => self
+                        .report
+                        .approximated(&entity_type, handle(entity), reason),
+                    None => self.report.converted(&entity_type),
+                }
+                appearance.apply(&mut shape);
+                if entity.common.line_type_scale != 1.0 {
+                    shape.line_type_scale = Some(entity.common.line_type_scale);
+                }
+                shape.dxf_data = self.entity_data(entity, depth);
+                // Coordinates in an OCS are projected before the INSERT placement
+                match dxf_ocs::entity_ocs(specific) {
+                    Some(ocs) => self.push_shape(shape, &xf.then_after(&ocs), group_id),
+                    None => self.push_shape(shape, xf, group_id),
+                }
+            }
+        }
+    }
+
+    fn push_shape(&mut self, mut shape: ShapeData, xf: &Transform, group_id: Option<&str>) {
+        if !xf.is_identity() {
+            dxf_transform::transform_shape(&mut shape, xf);
+        }
+        shape.group_id = group_id.map(str::to_string);
+        self.shapes.push(shape);
+    }
+
+    fn push_insert(
+        &mut self,
+        entity: &Entity,
+        insert: &Insert,
+        appearance: &Appearance,
+        xf: &Transform,
+        group_id: Option<&str>,
+        depth: usize,
+    ) {
+        let handle = handle(entity);
+        let mut dxf_data = self.entity_data(entity, depth);
+        let drawing = self.drawing;
+        let Some(block) = dxf_blocks::find_block(drawing, &insert.name) else {
+            let reason = format!("block '{}' is not defined", insert.name);
+            self.report.skipped("INSERT", handle, reason);
+            return;
+        };
+        // Groups are not exported, so INSERTs with application data stay block
+        // references to write it back
+        let explode = self.options.explode_blocks && depth < MAX_BLOCK_DEPTH && dxf_data.is_none();
+        if self.options.explode_blocks && depth >= MAX_BLOCK_DEPTH {
+            let reason = format!(
+                "blocks nested deeper than {} levels are kept as block references",
+                MAX_BLOCK_DEPTH
+            );
+            self.report.approximated("INSERT", handle, reason);
+        } else if self.options.explode_blocks && !explode {
+            let reason = "kept as a block reference to keep its XDATA or extension dictionary";
+            self.report.approximated("INSERT", handle, reason);
+        } else {
+            self.report.converted("INSERT");
+        }
+        let attributes = dxf_blocks::attribute_map(insert);
+
+        let group_id = if explode {
+            let id = format!("dxf-insert-{}", self.groups.len() + 1);
+            self.groups.push(GroupData {
+                id: id.clone(),
+                block_name: insert.name.clone(),
+                attributes: if self.options.attributes_as_text {
+                    BTreeMap::new()
+                } else {
+                    attributes.clone()
+                },
+            });
+            Some(id)
+        } else {
+            group_id.map(str::to_string)
+        };
+
+        if self.options.attributes_as_text {
+            for attribute in insert.attributes().filter(|a| !a.is_invisible()) {
+                if let Some(mut shape) = dxf_blocks::attribute_to_shape(attribute) {
+                    appearance.apply(&mut shape);
+                    self.push_shape(shape, xf, group_id.as_deref());
+                }
+            }
+        }
+
+        for (row, column) in dxf_blocks::array_cells(insert) {
+            if explode {
+                let placed =
+                    xf.then_after(&dxf_blocks::insert_transform(insert, block, row, column));
+                self.push_block_hatches(&block.name, &placed, appearance, group_id.as_deref());
+                for entity in &block.entities {
+                    self.push_entity(
+                        entity,
+                        &placed,
+                        Some(appearance),
+                        group_id.as_deref(),
+                        depth + 1,
+                    );
+                }
+            } else {
+                let mut shape = dxf_blocks::block_reference(insert, row, column);
+                if !self.options.attributes_as_text && !attributes.is_empty() {
+                    shape.attributes = Some(attributes.clone());
+                }
+                // MINSERT cells share one entity; its data goes with the first cell
+                shape.dxf_data = dxf_data.take();
+                appearance.apply(&mut shape);
+                self.push_shape(shape, xf, group_id.as_deref());
+            }
+        }
+    }
+
+    /// Hatches of block `name` placed by `xf`, resolving layer "0" and ByBlock
+    /// colour like the other block contents
+    fn push_block_hatches(
+        &mut self,
+        name: &str,
+        xf: &Transform,
+        parent: &Appearance,
+        group_id: Option<&str>,
+    ) {
+        let Some(hatches) = self
+            .block_hatches
+            .and_then(|hatches| hatches.get(&name.to_ascii_uppercase()))
+        else {
+            return;
+        };
+        for hatch in hatches {
+            for shape in &hatch.shapes {
+                let layer = match shape.layer.as_deref() {
+                    Some(layer) if layer != "0" => layer,
+                    _ => &parent.layer,
+                };
+                if !self.options.includes_layer(layer) {
+                    continue;
+                }
+                let mut shape = shape.clone();
+                shape.layer = Some(layer.to_string());
+                if hatch.color_by_block {
+                    shape.color = parent.color.clone();
+                }
+                shape.dxf_data = None;
+                self.push_shape(shape, xf, group_id);
+            }
+        }
+    }
+}
+
+/// Convert a single DXF entity into the matching frontend shape type
+pub fn entity_to_shape(entity: &Entity, drawing: &Drawing) -> Option<ShapeData> {
+    match &entity.specific {
+        EntityType::Line(line) => Some(ShapeData {
+            shape_type: "line".to_string(),
+            start: Some(to_point_data(&line.p1)),
+            end: Some(to_point_data(&line.p2)),
+            ..Default::default()
+        }),
+        EntityType::Circle(circle) => Some(ShapeData {
+            shape_type: "circle".to_string(),
+            center: Some(to_point_data(&circle.center)),
+            radius: Some(circle.radius),
+            ..Default::default()
+        }),
+        EntityType::Arc(arc) => Some(ShapeData {
+            shape_type: "arc".to_string(),
+            center: Some(to_point_data(&arc.center)),
+            radius: Some(arc.radius),
+            start_angle: Some(arc.start_angle.to_radians()),
+            end_angle: Some(arc.end_angle.to_radians()),
+            ..Default::default()
+        }),
+        EntityType::Ellipse(ellipse) => ellipse_to_shape(ellipse),
+        EntityType::LwPolyline(polyline) => lw_polyline_to_shape(polyline),
+        EntityType::Polyline(polyline) => polyline_to_shape(polyline),
+        EntityType::Spline(spline) => spline_to_shape(spline),
+        EntityType::Text(text) => dxf_text::text_to_shape(text, drawing),
+        EntityType::MText(mtext) => dxf_text::mtext_to_shape(mtext, drawing),
+        EntityType::RotatedDimension(_)
+        | EntityType::RadialDimension(_)
+        | EntityType::DiameterDimension(_)
+        | EntityType::AngularThreePointDimension(_) => {
+            dxf_dimensions::dimension_to_shape(&entity.specific, drawing)
+        }
+        EntityType::ModelPoint(point) => Some(ShapeData {
+            shape_type: "point".to_string(),
+            position: Some(to_point_data(&point.location)),
+            ..Default::default()
+        }),
+        _ => None,
+    }
+}
+
+fn ellipse_to_shape(ellipse: &Ellipse) -> Option<ShapeData> {
+    let major = &ellipse.major_axis;
+    let radius_x = (major.x * major.x + major.y * major.y).sqrt();
+    if radius_x <= 0.0 {
+        return None;
+    }
+    let is_full = ((ellipse.end_parameter - ellipse.start_parameter).abs() - 2.0 * PI).abs() < 1e-6;
+    // Ellipses are in world coordinates, but a reversed normal puts the minor
+    // axis clockwise of the major one: the arc runs the other way round
+    let (start, end) = if ellipse.normal.z < 0.0 {
+        (-ellipse.end_parameter, -ellipse.start_parameter)
+    } else {
+        (ellipse.start_parameter, ellipse.end_parameter)
+    };
+    Some(ShapeData {
+        shape_type: "ellipse".to_string(),
+        center: Some(to_point_data(&ellipse.center)),
+        radius_x: Some(radius_x),
+        radius_y: Some(radius_x * ellipse.minor_axis_ratio),
+        rotation: Some(major.y.atan2(major.x)),
+        start_angle: (!is_full).then_some(start),
+        end_angle: (!is_full).then_some(end),
+        ..Default::default()
+    })
+}
+
+fn lw_polyline_to_shape(polyline: &LwPolyline) -> Option<ShapeData> {
+    if polyline.vertices.len() < 2 {
+        return None;
+    }
+    let points = polyline
+        .vertices
+        .iter()
+        .map(|v| PointData { x: v.x, y: v.y })
+        .collect();
+    let bulge: Vec<f64> = polyline.vertices.iter().map(|v| v.bulge).collect();
+    Some(polyline_shape(points, bulge, polyline.is_closed()))
+}
+
+fn polyline_to_shape(polyline: &Polyline) -> Option<ShapeData> {
+    let vertices: Vec<_> = polyline
+        .vertices()
+        .filter(|v| v.flags & VERTEX_SPLINE_FRAME == 0)
+        .collect();
+    if vertices.len() < 2 {
+        return None;
+    }
+    let points = vertices
+        .iter()
+        .map(|v| to_point_data(&v.location))
+        .collect();
+    let bulge = vertices.iter().map(|v| v.bulge).collect();
+    Some(polyline_shape(points, bulge, polyline.is_closed()))
+}
+
+fn polyline_shape(points: Vec<PointData>, bulge: Vec<f64>, closed: bool) -> ShapeData {
+    let has_bulge = bulge.iter().any(|b| *b != 0.0);
+    ShapeData {
+        shape_type: "polyline".to_string(),
+        points: Some(points),
+        closed: Some(closed),
+        bulge: has_bulge.then_some(bulge),
+        ..Default::default()
+    }
+}
+
+fn spline_to_shape(spline: &Spline) -> Option<ShapeData> {
+    // Splines defined only by fit points carry no control polygon; the fit
+    // points are the closest representation the frontend can draw.
+    let (points, knots) = if spline.control_points.len() >= 2 {
+        (&spline.control_points, Some(spline.knot_values.clone()))
+    } else {
+        (&spline.fit_points, None)
+    };
+    if points.len() < 2 {
+        return None;
+    }
+    Some(ShapeData {
+        shape_type: "spline".to_string(),
+        points: Some(points.iter().map(to_point_data).collect()),
+        closed: Some(spline.is_closed()),
+        degree: Some(spline.degree_of_curve),
+        knots: knots.filter(|k| !k.is_empty()),
+        weights: (!spline.weight_values.is_empty()).then(|| spline.weight_values.clone()),
+        ..Default::default()
+    })
+}
+
+/// DXF name of an entity type, as used in the conversion report
+pub fn entity_type_name(specific: &EntityType) -> String {
+    let name = match specific {
+        EntityType::Line(_) => "LINE",
+        EntityType::Circle(_) => "CIRCLE",
+        EntityType::Arc(_) => "ARC",
+        EntityType::Ellipse(_) => "ELLIPSE",
+        EntityType::LwPolyline(_) => "LWPOLYLINE",
+        EntityType::Polyline(_) => "POLYLINE",
+        EntityType::Spline(_) => "SPLINE",
+        EntityType::Text(_) => "TEXT",
+        EntityType::MText(_) => "MTEXT",
+        EntityType::ModelPoint(_) => "POINT",
+        EntityType::Insert(_) => "INSERT",
+        EntityType::RotatedDimension(_)
+        | EntityType::RadialDimension(_)
+        | EntityType::DiameterDimension(_)
+        | EntityType::AngularThreePointDimension(_)
+        | EntityType::OrdinateDimension(_) => "DIMENSION",
+        EntityType::Face3D(_) => "3DFACE",
+        EntityType::Solid3D(_) => "3DSOLID",
+        EntityType::ProxyEntity(_) => "ACAD_PROXY_ENTITY",
+        EntityType::ArcAlignedText(_) => "ARCALIGNEDTEXT",
+        EntityType::AttributeDefinition(_) => "ATTDEF",
+        EntityType::Attribute(_) => "ATTRIB",
+        EntityType::Body(_) => "BODY",
+        EntityType::Helix(_) => "HELIX",
+        EntityType::Image(_) => "IMAGE",
+        EntityType::Leader(_) => "LEADER",
+        EntityType::Light(_) => "LIGHT",
+        EntityType::MLine(_) => "MLINE",
+        EntityType::OleFrame(_) => "OLEFRAME",
+        EntityType::Ole2Frame(_) => "OLE2FRAME",
+        EntityType::Ray(_) => "RAY",
+        EntityType::Region(_) => "REGION",
+        EntityType::RText(_) => "RTEXT",
+        EntityType::Section(_) => "SECTION",
+        EntityType::Seqend(_) => "SEQEND",
+        EntityType::Shape(_) => "SHAPE",
+        EntityType::Solid(_) => "SOLID",
+        EntityType::Tolerance(_) => "TOLERANCE",
+        EntityType::Trace(_) => "TRACE",
+        EntityType::DgnUnderlay(_) => "DGNUNDERLAY",
+        EntityType::DwfUnderlay(_) => "DWFUNDERLAY",
+        EntityType::PdfUnderlay(_) => "PDFUNDERLAY",
+        EntityType::Vertex(_) => "VERTEX",
+        EntityType::Wipeout(_) => "WIPEOUT",
+        EntityType::XLine(_) => "XLINE",
+    };
+    name.to_string()
+}
+
+/// Entity types `entity_to_shape` converts
+fn is_supported(specific: &EntityType) -> bool {
+    matches!(
+        specific,
+        EntityType::Line(_)
+            | EntityType::Circle(_)
+            | EntityType::Arc(_)
+            | EntityType::Ellipse(_)
+            | EntityType::LwPolyline(_)
+            | EntityType::Polyline(_)
+            | EntityType::Spline(_)
+            | EntityType::Text(_)
+            | EntityType::MText(_)
+            | EntityType::ModelPoint(_)
+            | EntityType::RotatedDimension(_)
+            | EntityType::RadialDimension(_)
+            | EntityType::DiameterDimension(_)
+            | EntityType::AngularThreePointDimension(_)
+    )
+}
+
+/// Why a converted entity does not match its source exactly
+fn approximation(specific: &EntityType) -> Option<&'static str> {
+    match specific {
+        EntityType::RotatedDimension(dim) => {
+            let angle = dim.rotation_angle.rem_euclid(90.0);
+            (angle > 1e-6 && 90.0 - angle > 1e-6)
+                .then_some("rotated dimension at an arbitrary angle imported as aligned")
+        }
+        EntityType::Polyline(polyline) if polyline.flags & POLYLINE_MESH != 0 => {
+            Some("mesh imported as its vertex outline")
+        }
+        EntityType::Polyline(polyline) if polyline.flags & POLYLINE_3D != 0 => {
+            Some("3D polyline flattened to 2D")
+        }
+        EntityType::Spline(spline) if spline.control_points.len() < 2 => {
+            Some("fit-point spline imported through its fit points")
+        }
+        _ => None,
+    }
+}
+
+/// Hexadecimal entity handle, if the file has handles
+pub fn handle(entity: &Entity) -> Option<String> {
+    let handle = entity.common.handle.0;
+    (handle != 0).then(|| format!("{:X}", handle))
+}
+
+pub fn to_point_data(p: &dxf::Point) -> PointData {
+    PointData { x: p.x, y: p.y }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dxf::entities::Line;
+    use dxf::enums::AcadVersion;
+    use dxf::{Block, Point};
+
+    /// Drawing with block "B" holding one line, placed by two INSERTs. Returns
+    /// the handles of the second INSERT and of the line.
+    fn drawing_with_inserts() -> (Drawing, String, String) {
+        let mut drawing = Drawing::new();
+        let line = Line::new(Point::new(0.0, 0.0, 0.0), Point::new(1.0, 0.0, 0.0));
+        drawing.add_block(Block {
+            name: "B".to_string(),
+            entities: vec![Entity::new(EntityType::Line(line))],
+            ..Default::default()
+        });
+        let mut handles = Vec::new();
+        for x in [0.0, 10.0] {
+            let insert = Insert {
+                name: "B".to_string(),
+                location: Point::new(x, 0.0, 0.0),
+                ..Default::default()
+            };
+            let entity = drawing.add_entity(Entity::new(EntityType::Insert(insert)));
+            handles.push(handle(entity).unwrap());
+        }
+        let line = &drawing.blocks().next().unwrap().entities[0];
+        let line_handle = handle(line).unwrap();
+        (drawing, handles.remove(1), line_handle)
+    }
+
+    fn data() -> DxfEntityData {
+        DxfEntityData {
+            xdata: vec![(1001, "APP".to_string()), (1000, "value".to_string())],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn inserts_with_data_stay_block_references() {
+        let (drawing, tagged, _) = drawing_with_inserts();
+        let entity_data = HashMap::from([(tagged, data())]);
+        let options = DxfImportOptions::default();
+        let mut importer = Importer::new(&drawing, &options).with_entity_data(&entity_data);
+        importer.push_entities(drawing.entities());
+        let imported = importer.finish();
+
+        let types: Vec<_> = imported
+            .shapes
+            .iter()
+            .map(|s| s.shape_type.as_str())
+            .collect();
+        assert_eq!(types, ["line", "block"]);
+        assert_eq!(imported.groups.len(), 1);
+        let block = &imported.shapes[1];
+        assert_eq!(block.dxf_data.as_ref().unwrap().xdata, data().xdata);
+    }
+
+    #[test]
+    fn block_definitions_keep_entity_data() {
+        let (drawing, _, line_handle) = drawing_with_inserts();
+        let entity_data = HashMap::from([(line_handle, data())]);
+        let blocks = dxf_blocks::read_blocks(&drawing, &entity_data, &HashMap::new());
+
+        let block = blocks.iter().find(|b| b.name == "B").unwrap();
+        assert_eq!(
+            block.shapes[0].dxf_data.as_ref().unwrap().xdata,
+            data().xdata
+        );
+    }
+
+    #[test]
+    fn block_hatches_follow_exploded_inserts() {
+        let (drawing, _, _) = drawing_with_inserts();
+        let path = std::env::temp_dir().join(format!("block_hatch_{}.dxf", std::process::id()));
+        let path = path.to_str().unwrap();
+        drawing.save_file(path).unwrap();
+        let hatch = ShapeData {
+            shape_type: "hatch".to_string(),
+            points: Some(vec![
+                PointData { x: 0.0, y: 0.0 },
+                PointData { x: 1.0, y: 0.0 },
+                PointData { x: 1.0, y: 1.0 },
+            ]),
+            pattern_type: Some("solid".to_string()),
+            ..Default::default()
+        };
+        let mut report = ConversionReport::default();
+        dxf_hatch::write_hatches(
+            path,
+            Some("B"),
+            &[hatch],
+            &HashMap::new(),
+            &[],
+            AcadVersion::R2000,
+            &mut report,
+        )
+        .unwrap();
+        let drawing = Drawing::load_file(path).unwrap();
+        let options = DxfImportOptions {
+            detect_origin: false,
+            ..Default::default()
+        };
+        let document = import_drawing(path, &drawing, &options, &()).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let hatches: Vec<_> = document
+            .shapes
+            .iter()
+            .filter(|s| s.shape_type == "hatch")
+            .collect();
+        assert_eq!(hatches.len(), 2);
+        let corners: Vec<_> = hatches
+            .iter()
+            .map(|s| s.points.as_ref().unwrap()[1].x)
+            .collect();
+        assert_eq!(corners, [1.0, 11.0]);
+        assert!(hatches.iter().all(|s| s.group_id.is_some()));
+        let block = document.blocks.iter().find(|b| b.name == "B").unwrap();
+        assert_eq!(block.shapes[0].shape_type, "hatch");
+    }
+
+    /// Hooks that collect the streamed shapes
+    #[derive(Default)]
+    struct Collected(std::cell::RefCell<Vec<ShapeData>>);
+
+    impl ImportHooks for Collected {
+        fn streams_shapes(&self) -> bool {
+            true
+        }
+
+        fn shapes(&self, shapes: Vec<ShapeData>) {
+            self.0.borrow_mut().extend(shapes);
+        }
+    }
+
+    #[test]
+    fn streamed_shapes_are_placed_by_the_header_extents() {
+        let (x, y) = (5.0e6, 2.0e6);
+        let mut drawing = Drawing::new();
+        let line = Line::new(Point::new(x, y, 0.0), Point::new(x + 10.0, y, 0.0));
+        drawing.add_entity(Entity::new(EntityType::Line(line)));
+        drawing.header.minimum_drawing_extents = Point::new(x, y, 0.0);
+        drawing.header.maximum_drawing_extents = Point::new(x + 10.0, y, 0.0);
+        let path = std::env::temp_dir().join(format!("stream_{}.dxf", std::process::id()));
+        let path = path.to_str().unwrap();
+        drawing.save_file(path).unwrap();
+        let hooks = Collected::default();
+        let document = import_drawing(path, &drawing, &DxfImportOptions::default(), &hooks);
+        std::fs::remove_file(path).unwrap();
+
+        let document = document.unwrap();
+        assert!(document.shapes.is_empty());
+        let origin = document.origin.unwrap();
+        let shapes = hooks.0.into_inner();
+        assert_eq!(shapes.len(), 1);
+        let start = shapes[0].start.unwrap();
+        assert_eq!((start.x + origin.x, start.y + origin.y), (x, y));
+        assert!(start.x.abs() < 1000.0 && start.y.abs() < 1000.0);
+    }
+}
@@ -0,0 +1,292 @@
+//! DXF LAYER table conversion to and from project layers.
+
+use super::dxf_raw::{self, LayerGroups};
+use super::dxf_text::sanitize_table_name;
+use super::shape::{DxfDocument, ShapeData};
+use super::{dxf_color, dxf_linetypes};
+use dxf::enums::AcadVersion;
+use dxf::tables::Layer;
+use dxf::{Color, Drawing};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io;
+
+/// LAYER flag bits (group 70)
+const FROZEN: i32 = 1;
+const LOCKED: i32 = 4;
+
+/// Group 370 value for the default lineweight
+const DEFAULT_LINEWEIGHT: i16 = -3;
+
+/// Layer as exchanged with the frontend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayerData {
+    pub name: String,
+    /// `#rrggbb`
+    pub color: String,
+    /// Layer on/off
+    pub visible: bool,
+    pub frozen: bool,
+    pub locked: bool,
+    /// DXF linetype name ("Continuous", "DASHED", ...) or project line style
+    pub line_type: String,
+    /// Lineweight in millimetres; `None` means the DXF default
+    pub line_weight: Option<f64>,
+}
+
+/// Read the LAYER table. `raw` holds the flags and true colours the `dxf` crate
+/// skips, from `dxf_raw::layer_groups`.
+pub fn read_layers(drawing: &Drawing, raw: &HashMap<String, LayerGroups>) -> Vec<LayerData> {
+    drawing
+        .layers()
+        .map(|layer| {
+            let groups = raw.get(&layer.name).copied().unwrap_or_default();
+            // The dxf crate makes the index of turned-off layers positive again
+            let rgb = groups
+                .true_color
+                .unwrap_or_else(|| dxf_color::aci_to_rgb(layer.color.index().unwrap_or(7)));
+            LayerData {
+                name: layer.name.clone(),
+                color: dxf_color::to_hex(rgb),
+                visible: layer.is_layer_on,
+                frozen: groups.flags & FROZEN != 0,
+                locked: groups.flags & LOCKED != 0,
+                line_type: layer.line_type_name.clone(),
+                line_weight: lineweight_to_mm(layer.line_weight.raw_value()),
+            }
+        })
+        .collect()
+}
+
+/// Write the project layers into the LAYER table, plus any layer that shapes
+/// reference without a definition. Returns the DXF name for each project layer name.
+pub fn write_layers(
+    drawing: &mut Drawing,
+    layers: &[LayerData],
+    shapes: &[ShapeData],
+) -> HashMap<String, String> {
+    let mut names = HashMap::new();
+    for data in layers {
+        let name = sanitize_table_name(&data.name);
+        let rgb = dxf_color::from_hex(&data.color).unwrap_or(0xFFFFFF);
+        // Flags, lineweight and true colour are set by `write_layer_groups`
+        let layer = Layer {
+            name: name.clone(),
+            color: Color::from_index(dxf_color::nearest_aci(rgb)),
+            line_type_name: dxf_linetypes::dxf_line_type_name(&data.line_type),
+            is_layer_on: data.visible,
+            ..Default::default()
+        };
+        add_layer_once(drawing, layer);
+        names.insert(data.name.clone(), name);
+    }
+
+    for layer_name in shapes.iter().filter_map(|s| s.layer.as_ref()) {
+        if !names.contains_key(layer_name) {
+            let name = sanitize_table_name(layer_name);
+            add_layer_once(
+                drawing,
+                Layer {
+                    name: name.clone(),
+                    ..Default::default()
+                },
+            );
+            names.insert(layer_name.clone(), name);
+        }
+    }
+    names
+}
+
+/// Write the layer groups the `dxf` crate can't into an already saved ASCII DXF
+/// file: the frozen and locked flags, the lineweight (R2000) and the true colour
+/// (R2004) where the colour index is only an approximation. `names` maps project
+/// layer names to DXF names as returned by `write_layers`.
+pub fn write_layer_groups(
+    path: &str,
+    layers: &[LayerData],
+    names: &HashMap<String, String>,
+    version: AcadVersion,
+) -> io::Result<()> {
+    let by_name: HashMap<String, &LayerData> = layers
+        .iter()
+        .filter_map(|data| Some((names.get(&data.name)?.to_ascii_uppercase(), data)))
+        .collect();
+    dxf_raw::edit_records(path, "LAYER", version, |pairs| {
+        let name = pairs
+            .iter()
+            .find(|(code, _)| *code == 2)
+            .map(|(_, name)| name.trim().to_ascii_uppercase());
+        let data = name.and_then(|name| by_name.get(&name).copied());
+        if let Some(data) = data {
+            let mut flags = 0;
+            if data.frozen {
+                flags |= FROZEN;
+            }
+            if data.locked {
+                flags |= LOCKED;
+            }
+            dxf_raw::set_group(pairs, 70, flags);
+        }
+        if version >= AcadVersion::R2000 {
+            // The dxf crate writes 0 (0.00 mm) when no lineweight is set
+            let weight = data
+                .and_then(|data| data.line_weight)
+                .map_or(DEFAULT_LINEWEIGHT, mm_to_lineweight);
+            dxf_raw::set_group(pairs, 370, weight);
+        }
+        let rgb = data.and_then(|data| dxf_color::from_hex(&data.color));
+        if let Some(rgb) = rgb.filter(|_| version >= AcadVersion::R2004) {
+            if dxf_color::aci_to_rgb(dxf_color::nearest_aci(rgb)) != rgb {
+                let at = pairs
+                    .iter()
+                    .position(|(code, _)| *code == 62)
+                    .map_or(pairs.len(), |index| index + 1);
+                pairs.insert(at, (420, rgb.to_string()));
+            }
+        }
+    })
+}
+
+fn add_layer_once(drawing: &mut Drawing, layer: Layer) {
+    if !drawing
+        .layers()
+        .any(|l| l.name.eq_ignore_ascii_case(&layer.name))
+    {
+        drawing.add_layer(layer);
+    }
+}
+
+/// DXF lineweights are stored in hundredths of a millimetre; negative values are
+/// ByLayer / ByBlock / Default
+fn lineweight_to_mm(raw: i16) -> Option<f64> {
+    (raw >= 0).then_some(f64::from(raw) / 100.0)
+}
+
+/// Snap to the nearest standard DXF lineweight
+fn mm_to_lineweight(mm: f64) -> i16 {
+    const STANDARD: [i16; 24] = [
+        0, 5, 9, 13, 15, 18, 20, 25, 30, 35, 40, 50, 53, 60, 70, 80, 90, 100, 106, 120, 140, 158,
+        200, 211,
+    ];
+    let hundredths = (mm * 100.0).round() as i32;
+    STANDARD
+        .iter()
+        .copied()
+        .min_by_key(|w| (i32::from(*w) - hundredths).abs())
+        .unwrap_or(25)
+}
+
+/// Rename source layers to project layers throughout an imported document.
+/// Layers mapped onto the same target are merged, keeping the first definition.
+pub fn rename_layers(document: &mut DxfDocument, map: &BTreeMap<String, String>) {
+    if map.is_empty() {
+        return;
+    }
+    let mut seen = HashSet::new();
+    document.layers.retain_mut(|layer| {
+        rename(&mut layer.name, map);
+        seen.insert(layer.name.to_ascii_uppercase())
+    });
+    rename_shape_layers(&mut document.shapes, map);
+    for block in &mut document.blocks {
+        rename_shape_layers(&mut block.shapes, map);
+    }
+    for sheet in &mut document.sheets {
+        rename_shape_layers(&mut sheet.shapes, map);
+        for layer in sheet
+            .viewports
+            .iter_mut()
+            .flat_map(|v| &mut v.frozen_layers)
+        {
+            rename(layer, map);
+        }
+    }
+}
+
+/// Rename the layers of shapes, e.g. of one chunk of a background import
+pub fn rename_shape_layers(shapes: &mut [ShapeData], map: &BTreeMap<String, String>) {
+    if map.is_empty() {
+        return;
+    }
+    for layer in shapes.iter_mut().filter_map(|shape| shape.layer.as_mut()) {
+        rename(layer, map);
+    }
+}
+
+/// Source layer names match the mapping case-insensitively
+fn rename(name: &mut String, map: &BTreeMap<String, String>) {
+    if let Some((_, target)) = map
+        .iter()
+        .find(|(source, _)| source.eq_ignore_ascii_case(name))
+    {
+        name.clone_from(target);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dxf_raw::RawRecords;
+    use std::fs;
+
+    fn layer(name: &str, color: &str) -> LayerData {
+        LayerData {
+            name: name.to_string(),
+            color: color.to_string(),
+            visible: true,
+            frozen: false,
+            locked: false,
+            line_type: "Continuous".to_string(),
+            line_weight: None,
+        }
+    }
+
+    fn round_trip(layers: &[LayerData], version: AcadVersion) -> Vec<LayerData> {
+        let path =
+            std::env::temp_dir().join(format!("layers_{:?}_{}.dxf", version, std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut drawing = Drawing::new();
+        drawing.header.version = version;
+        let names = write_layers(&mut drawing, layers, &[]);
+        drawing.save_file(path).unwrap();
+        write_layer_groups(path, layers, &names, version).unwrap();
+        let drawing = Drawing::load_file(path).unwrap();
+        let raw = RawRecords::read(path, |_| Ok(())).unwrap();
+        fs::remove_file(path).unwrap();
+        read_layers(&drawing, &dxf_raw::layer_groups(&raw))
+    }
+
+    #[test]
+    fn layers_round_trip() {
+        let layers = [
+            LayerData {
+                frozen: true,
+                line_weight: Some(0.35),
+                ..layer("Walls", "#ff0000")
+            },
+            LayerData {
+                visible: false,
+                locked: true,
+                line_type: "DASHED".to_string(),
+                ..layer("Hidden", "#123456")
+            },
+        ];
+        let read = round_trip(&layers, AcadVersion::R2018);
+        for written in &layers {
+            let read = read.iter().find(|l| l.name == written.name).unwrap();
+            assert_eq!(read.color, written.color);
+            assert_eq!(read.visible, written.visible);
+            assert_eq!(read.frozen, written.frozen);
+            assert_eq!(read.locked, written.locked);
+            assert_eq!(read.line_type, written.line_type);
+            assert_eq!(read.line_weight, written.line_weight);
+        }
+
+        // Before R2004 true colours fall back to the nearest colour index
+        let read = round_trip(&layers, AcadVersion::R2000);
+        let hidden = read.iter().find(|l| l.name == "Hidden").unwrap();
+        let aci = dxf_color::nearest_aci(0x123456);
+        assert_eq!(hidden.color, dxf_color::to_hex(dxf_color::aci_to_rgb(aci)));
+        assert!(hidden.locked && !hidden.visible);
+    }
+}
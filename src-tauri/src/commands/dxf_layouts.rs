@@ -0,0 +1,758 @@
+//! Paper space layouts exchanged as sheets, with their viewports and title block.
+//!
+//! Sheet geometry is in paper millimetres with the origin at the lower left corner
+//! of the paper and Y up, like paper space itself.
+
+use super::dxf_blocks::{self, GroupData};
+use super::dxf_export;
+use super::dxf_import::{self, DxfImportOptions, Importer};
+use super::dxf_raw::{self, RawEntityWriter, RawRecords};
+use super::dxf_report::ConversionReport;
+use super::dxf_text::{self, sanitize_table_name};
+use super::dxf_transform::{self, Transform};
+use super::shape::{PointData, ShapeData};
+use dxf::entities::{Attribute, AttributeDefinition, Entity, EntityType, Insert};
+use dxf::enums::AcadVersion;
+use dxf::objects::{Layout, Object, ObjectType};
+use dxf::{Block, Drawing, Point};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+
+/// Standard paper sizes in mm (portrait), as in `sheetService.ts`
+const PAPER_SIZES: [(&str, f64, f64); 8] = [
+    ("A4", 210.0, 297.0),
+    ("A3", 297.0, 420.0),
+    ("A2", 420.0, 594.0),
+    ("A1", 594.0, 841.0),
+    ("A0", 841.0, 1189.0),
+    ("Letter", 216.0, 279.0),
+    ("Legal", 216.0, 356.0),
+    ("Tabloid", 279.0, 432.0),
+];
+
+/// Viewport ID of the paper space view itself; sheet viewports start after it
+const PAPER_VIEWPORT_ID: i32 = 1;
+
+/// Distance between a title block cell border and its text
+const CELL_PADDING: f64 = 1.0;
+
+/// Sheet as exchanged with the frontend
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SheetData {
+    pub id: Option<String>,
+    pub name: String,
+    /// "A4" ... "Tabloid", or "Custom" with `paper_width` and `paper_height`
+    pub paper_size: String,
+    /// "portrait" or "landscape"
+    pub orientation: String,
+    pub paper_width: Option<f64>,
+    pub paper_height: Option<f64>,
+    #[serde(default)]
+    pub viewports: Vec<ViewportData>,
+    pub title_block: Option<TitleBlockData>,
+    /// Annotations drawn on the sheet itself
+    #[serde(default)]
+    pub shapes: Vec<ShapeData>,
+}
+
+/// Window onto model space
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ViewportData {
+    pub id: Option<String>,
+    /// Centre on the paper
+    pub center: PointData,
+    pub width: f64,
+    pub height: f64,
+    /// Model space point shown at the centre
+    pub view_center: PointData,
+    /// Paper length per model length, e.g. 0.01 for 1:100
+    pub scale: f64,
+    pub visible: Option<bool>,
+    /// Layers frozen in this viewport only
+    #[serde(default)]
+    pub frozen_layers: Vec<String>,
+}
+
+/// Title block placed by its lower left corner
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TitleBlockData {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    #[serde(default)]
+    pub fields: Vec<TitleBlockFieldData>,
+}
+
+/// Title block cell, positioned by its lower left corner relative to the title block.
+/// Cells read back from DXF only know where their text starts, not their size.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TitleBlockFieldData {
+    pub id: String,
+    pub label: String,
+    pub value: String,
+    pub x: f64,
+    pub y: f64,
+    pub width: Option<f64>,
+    pub height: Option<f64>,
+    /// Text height in mm
+    pub font_size: f64,
+}
+
+/// VIEWPORT entities of one layout, which the `dxf` crate neither reads nor
+/// writes
+#[derive(Debug, Clone, Default)]
+pub struct LayoutViewports {
+    /// `*Paper_Space` for the active layout, else its `*Paper_Space{n}` block
+    block: String,
+    viewports: Vec<RawViewport>,
+}
+
+/// The VIEWPORT group codes sheets use, in paper units
+#[derive(Debug, Clone, Default)]
+struct RawViewport {
+    handle: Option<String>,
+    center: PointData,
+    width: f64,
+    height: f64,
+    status: i32,
+    id: i32,
+    view_center: PointData,
+    view_height: f64,
+    /// Layer names
+    frozen_layers: Vec<String>,
+}
+
+impl SheetData {
+    /// Paper width and height in mm after applying the orientation
+    pub fn paper_dimensions(&self) -> (f64, f64) {
+        let standard = PAPER_SIZES
+            .iter()
+            .find(|(name, _, _)| name.eq_ignore_ascii_case(&self.paper_size))
+            .map(|&(_, width, height)| (width, height));
+        let (width, height) = match (standard, self.paper_width, self.paper_height) {
+            (_, Some(width), Some(height)) if self.paper_size == "Custom" => (width, height),
+            (Some(size), _, _) => size,
+            (None, Some(width), Some(height)) => (width, height),
+            _ => (PAPER_SIZES[0].1, PAPER_SIZES[0].2),
+        };
+        let landscape = self.orientation == "landscape";
+        if landscape == (width < height) {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+}
+
+/// Write every sheet as a paper space layout. The first sheet is the active layout
+/// in the ENTITIES section; the others are `*Paper_Space{n}` blocks, which need
+/// R2000 or later.
+///
+/// Sheets stay in paper millimetres; viewport views follow `model`, the transform
+/// applied to the model space shapes.
+///
+/// The `dxf` crate cannot write VIEWPORT entities; they are returned for
+/// `write_viewports` once the file has been saved.
+pub fn write_layouts(
+    drawing: &mut Drawing,
+    sheets: &[SheetData],
+    model: &Transform,
+    layer_names: &HashMap<String, String>,
+    report: &mut ConversionReport,
+) -> Vec<LayoutViewports> {
+    let mut layouts = Vec::new();
+    let mut next_id = PAPER_VIEWPORT_ID + 1;
+    let single_layout = drawing.header.version < AcadVersion::R2000;
+    for (index, sheet) in sheets.iter().enumerate() {
+        if single_layout && index > 0 {
+            let reason = "multiple layouts need R2000 or later; only the first sheet was written";
+            report.skipped("sheet", sheet.id.clone(), reason);
+            continue;
+        }
+        let (width, height) = sheet.paper_dimensions();
+        let mut viewports = vec![paper_viewport(width, height)];
+        for viewport in &sheet.viewports {
+            if viewport.scale <= 0.0 || viewport.width <= 0.0 || viewport.height <= 0.0 {
+                report.skipped("viewport", viewport.id.clone(), "zero size or scale");
+                continue;
+            }
+            let id = next_id;
+            next_id += 1;
+            viewports.push(sheet_viewport(viewport, id, model, layer_names));
+            report.converted("viewport");
+        }
+        let mut entities = Vec::new();
+        if let Some(title_block) = &sheet.title_block {
+            let block_name = format!("TITLEBLOCK-{}", sanitize_table_name(&sheet.name));
+            entities.push(title_block_insert(drawing, title_block, &block_name));
+        }
+        for shape in &sheet.shapes {
+            if shape.shape_type == "hatch" {
+                let reason =
+                    "hatch fills on sheets are not supported; only the outline was written";
+                report.approximated("hatch", shape.id.clone(), reason);
+            }
+            entities.extend(dxf_export::shape_entities(
+                drawing,
+                shape,
+                layer_names,
+                report,
+            ));
+        }
+        for entity in &mut entities {
+            entity.common.is_in_paper_space = true;
+        }
+
+        let block = match index {
+            0 => "*Paper_Space".to_string(),
+            n => format!("*Paper_Space{}", n - 1),
+        };
+        if index == 0 {
+            for entity in entities {
+                drawing.add_entity(entity);
+            }
+        } else {
+            drawing.add_block(Block {
+                name: block.clone(),
+                layer: "0".to_string(),
+                entities,
+                ..Default::default()
+            });
+        }
+        layouts.push(LayoutViewports { block, viewports });
+        drawing.add_object(Object::new(ObjectType::Layout(Layout {
+            layout_name: sheet.name.clone(),
+            tab_order: index as i32 + 1,
+            minimum_limits: Point::origin(),
+            maximum_limits: Point::new(width, height, 0.0),
+            minimum_extents: Point::origin(),
+            maximum_extents: Point::new(width, height, 0.0),
+            ..Default::default()
+        })));
+        report.converted("sheet");
+    }
+    layouts
+}
+
+/// The layout's own view of the paper, which every layout carries as viewport 1
+fn paper_viewport(width: f64, height: f64) -> RawViewport {
+    let center = PointData {
+        x: width / 2.0,
+        y: height / 2.0,
+    };
+    RawViewport {
+        center,
+        width,
+        height,
+        status: 1,
+        id: PAPER_VIEWPORT_ID,
+        view_center: center,
+        view_height: height,
+        ..Default::default()
+    }
+}
+
+/// `model` takes project model coordinates to the DXF model space
+fn sheet_viewport(
+    viewport: &ViewportData,
+    id: i32,
+    model: &Transform,
+    layer_names: &HashMap<String, String>,
+) -> RawViewport {
+    let (_, model_scale) = model.scale_factors();
+    RawViewport {
+        center: viewport.center,
+        width: viewport.width,
+        height: viewport.height,
+        // Status is the stacking order of visible viewports; 0 turns the viewport off
+        status: if viewport.visible.unwrap_or(true) {
+            id
+        } else {
+            0
+        },
+        id,
+        view_center: model.apply(viewport.view_center),
+        view_height: viewport.height / viewport.scale * model_scale,
+        frozen_layers: viewport
+            .frozen_layers
+            .iter()
+            .map(|layer| layer_names.get(layer).unwrap_or(layer).clone())
+            .collect(),
+        ..Default::default()
+    }
+}
+
+/// Add the VIEWPORT entities of every layout to a saved ASCII file, in front of
+/// the other paper space entities, then freeze their layers
+pub fn write_viewports(
+    path: &str,
+    layouts: &[LayoutViewports],
+    version: AcadVersion,
+) -> io::Result<()> {
+    let mut frozen = HashMap::new();
+    for layout in layouts {
+        dxf_raw::insert_entities(path, Some(&layout.block), version, |w| {
+            for viewport in &layout.viewports {
+                write_viewport(w, viewport, version);
+            }
+        })?;
+        for viewport in &layout.viewports {
+            if !viewport.frozen_layers.is_empty() {
+                frozen.insert(viewport.id, viewport.frozen_layers.clone());
+            }
+        }
+    }
+    dxf_raw::freeze_viewport_layers(path, &frozen)
+}
+
+fn write_viewport(w: &mut RawEntityWriter, viewport: &RawViewport, version: AcadVersion) {
+    let subclasses = version >= AcadVersion::R13;
+    w.start_entity("VIEWPORT");
+    if subclasses {
+        w.pair(100, "AcDbEntity");
+    }
+    w.pair(67, 1);
+    w.pair(8, "0");
+    if subclasses {
+        w.pair(100, "AcDbViewport");
+    }
+    w.pair(10, viewport.center.x);
+    w.pair(20, viewport.center.y);
+    w.pair(30, 0.0);
+    w.pair(40, viewport.width);
+    w.pair(41, viewport.height);
+    w.pair(68, viewport.status);
+    w.pair(69, viewport.id);
+    if !subclasses {
+        // R12 keeps the view in the ACAD application data, which is not written
+        return;
+    }
+    w.pair(12, viewport.view_center.x);
+    w.pair(22, viewport.view_center.y);
+    // Plan view, looking down the Z axis
+    w.pair(16, 0.0);
+    w.pair(26, 0.0);
+    w.pair(36, 1.0);
+    w.pair(42, 50.0);
+    w.pair(45, viewport.view_height);
+    w.pair(72, 1000);
+    w.pair(90, 0);
+}
+
+/// Define the title block as a block with one attribute per field and return
+/// its INSERT. Field values become ATTRIBs so they can be edited in CAD.
+fn title_block_insert(drawing: &mut Drawing, title_block: &TitleBlockData, name: &str) -> Entity {
+    let mut entities: Vec<Entity> = rectangle(0.0, 0.0, title_block.width, title_block.height)
+        .into_iter()
+        .collect();
+    let mut attributes = Vec::new();
+    for field in &title_block.fields {
+        let (width, height) = (field.width.unwrap_or(0.0), field.height.unwrap_or(0.0));
+        if width > 0.0 && height > 0.0 {
+            entities.extend(rectangle(field.x, field.y, width, height));
+        }
+        let location = Point::new(field.x + CELL_PADDING, field.y + CELL_PADDING, 0.0);
+        let tag = sanitize_table_name(&field.id)
+            .replace(' ', "_")
+            .to_uppercase();
+        entities.push(Entity::new(EntityType::AttributeDefinition(
+            AttributeDefinition {
+                location: location.clone(),
+                text_height: field.font_size,
+                value: field.value.clone(),
+                prompt: field.label.clone(),
+                text_tag: tag.clone(),
+                ..Default::default()
+            },
+        )));
+        attributes.push(Attribute {
+            location: Point::new(title_block.x + location.x, title_block.y + location.y, 0.0),
+            text_height: field.font_size,
+            value: field.value.clone(),
+            attribute_tag: tag,
+            ..Default::default()
+        });
+    }
+    for entity in &mut entities {
+        entity.common.layer = "0".to_string();
+    }
+    drawing.add_block(Block {
+        name: name.to_string(),
+        layer: "0".to_string(),
+        entities,
+        ..Default::default()
+    });
+
+    let mut insert = Insert {
+        name: name.to_string(),
+        location: Point::new(title_block.x, title_block.y, 0.0),
+        ..Default::default()
+    };
+    for attribute in attributes {
+        insert.add_attribute(drawing, attribute);
+    }
+    Entity::new(EntityType::Insert(insert))
+}
+
+fn rectangle(x: f64, y: f64, width: f64, height: f64) -> Option<Entity> {
+    let corners = [
+        PointData { x, y },
+        PointData { x: x + width, y },
+        PointData {
+            x: x + width,
+            y: y + height,
+        },
+        PointData { x, y: y + height },
+    ];
+    let shape = ShapeData {
+        shape_type: "polyline".to_string(),
+        points: Some(corners.to_vec()),
+        closed: Some(true),
+        ..Default::default()
+    };
+    dxf_export::polyline_to_entity(&shape).map(|polyline| {
+        let mut entity = Entity::new(EntityType::LwPolyline(polyline));
+        entity.common.layer = "0".to_string();
+        entity
+    })
+}
+
+/// Convert every paper space layout that has content into a sheet. INSERTs are
+/// exploded into groups added to `groups`; the first INSERT with attributes is
+/// taken as the title block.
+pub fn read_layouts(
+    raw: &RawRecords,
+    drawing: &Drawing,
+    options: &DxfImportOptions,
+    groups: &mut Vec<GroupData>,
+    report: &mut ConversionReport,
+) -> Vec<SheetData> {
+    let layout_blocks = layout_block_names(raw);
+    let mut viewports = read_viewports(raw);
+
+    let mut layouts: Vec<&Layout> = drawing
+        .objects()
+        .filter_map(|object| match &object.specific {
+            ObjectType::Layout(layout) if !layout.layout_name.eq_ignore_ascii_case("Model") => {
+                Some(layout)
+            }
+            _ => None,
+        })
+        .collect();
+    layouts.sort_by_key(|layout| layout.tab_order);
+
+    let mut sheets = Vec::new();
+    for (index, layout) in layouts.into_iter().enumerate() {
+        if !options.includes_layout(&layout.layout_name) {
+            continue;
+        }
+        // Without the owner links, layouts are assumed to follow the block numbering
+        let block_name = layout_blocks
+            .get(&layout.layout_name.to_ascii_uppercase())
+            .cloned()
+            .unwrap_or_else(|| match index {
+                0 => "*Paper_Space".to_string(),
+                n => format!("*Paper_Space{}", n - 1),
+            });
+        let entities: Vec<&Entity> = if block_name.eq_ignore_ascii_case("*Paper_Space") {
+            drawing
+                .entities()
+                .filter(|entity| entity.common.is_in_paper_space)
+                .collect()
+        } else {
+            dxf_blocks::find_block(drawing, &block_name)
+                .map(|block| block.entities.iter().collect())
+                .unwrap_or_default()
+        };
+        let layout_viewports = viewports
+            .remove(&block_name.to_ascii_uppercase())
+            .unwrap_or_default();
+        if let Some(sheet) = read_layout(
+            drawing,
+            layout,
+            &entities,
+            layout_viewports,
+            options,
+            groups,
+            report,
+        ) {
+            sheets.push(sheet);
+        }
+    }
+    sheets
+}
+
+fn read_layout(
+    drawing: &Drawing,
+    layout: &Layout,
+    entities: &[&Entity],
+    raw_viewports: Vec<RawViewport>,
+    options: &DxfImportOptions,
+    groups: &mut Vec<GroupData>,
+    report: &mut ConversionReport,
+) -> Option<SheetData> {
+    let mut viewports = Vec::new();
+    let mut paper_view = None;
+    for viewport in raw_viewports {
+        if viewport.id == PAPER_VIEWPORT_ID {
+            paper_view = Some(viewport);
+            continue;
+        }
+        if viewport.view_height <= 0.0 {
+            report.skipped("VIEWPORT", viewport.handle, "zero view height");
+            continue;
+        }
+        viewports.push(ViewportData {
+            id: viewport.handle,
+            center: viewport.center,
+            width: viewport.width,
+            height: viewport.height,
+            view_center: viewport.view_center,
+            scale: viewport.height / viewport.view_height,
+            visible: Some(viewport.status != 0),
+            frozen_layers: viewport.frozen_layers,
+        });
+        report.converted("VIEWPORT");
+    }
+
+    let mut title_block = None;
+    let mut others = Vec::new();
+    for &entity in entities {
+        match &entity.specific {
+            EntityType::Insert(insert)
+                if title_block.is_none() && insert.attributes().next().is_some() =>
+            {
+                if let Some(block) = read_title_block(drawing, insert) {
+                    title_block = Some(block);
+                    report.converted("INSERT");
+                } else {
+                    others.push(entity);
+                }
+            }
+            _ => others.push(entity),
+        }
+    }
+
+    let mut importer = Importer::new(drawing, options);
+    importer.push_entities(others.into_iter());
+    let imported = importer.finish();
+    report.merge(imported.report);
+    groups.extend(imported.groups);
+    let mut shapes = imported.shapes;
+    if viewports.is_empty() && title_block.is_none() && shapes.is_empty() {
+        return None;
+    }
+
+    // Paper size from the layout limits, else from the paper view
+    let (min, max) = if layout.maximum_limits.x > layout.minimum_limits.x
+        && layout.maximum_limits.y > layout.minimum_limits.y
+    {
+        (
+            dxf_import::to_point_data(&layout.minimum_limits),
+            dxf_import::to_point_data(&layout.maximum_limits),
+        )
+    } else if let Some(view) = paper_view.filter(|view| view.height > 0.0) {
+        let half_height = view.view_height / 2.0;
+        let half_width = half_height * view.width / view.height;
+        let center = view.view_center;
+        (
+            PointData {
+                x: center.x - half_width,
+                y: center.y - half_height,
+            },
+            PointData {
+                x: center.x + half_width,
+                y: center.y + half_height,
+            },
+        )
+    } else {
+        dxf_export::extents(&shapes).unwrap_or((
+            PointData { x: 0.0, y: 0.0 },
+            PointData {
+                x: PAPER_SIZES[1].2,
+                y: PAPER_SIZES[1].1,
+            },
+        ))
+    };
+
+    // Move the paper origin to the lower left corner
+    let to_paper = Transform::translate(-min.x, -min.y);
+    for shape in &mut shapes {
+        dxf_transform::transform_shape(shape, &to_paper);
+    }
+    for viewport in &mut viewports {
+        viewport.center = to_paper.apply(viewport.center);
+    }
+    if let Some(title_block) = &mut title_block {
+        title_block.x -= min.x;
+        title_block.y -= min.y;
+    }
+
+    let (width, height) = (max.x - min.x, max.y - min.y);
+    let (paper_size, orientation) = match_paper_size(width, height);
+    Some(SheetData {
+        id: None,
+        name: layout.layout_name.clone(),
+        paper_size: paper_size.to_string(),
+        orientation: orientation.to_string(),
+        paper_width: Some(width),
+        paper_height: Some(height),
+        viewports,
+        title_block,
+        shapes,
+    })
+}
+
+/// Title block outline from the block geometry, fields from the ATTRIBs
+fn read_title_block(drawing: &Drawing, insert: &Insert) -> Option<TitleBlockData> {
+    let block = dxf_blocks::find_block(drawing, &insert.name)?;
+    let options = DxfImportOptions {
+        explode_blocks: true,
+        ..Default::default()
+    };
+    let mut importer = Importer::new(drawing, &options);
+    importer.push_entities(block.entities.iter());
+    let mut outline = importer.finish().shapes;
+    let xf = dxf_blocks::insert_transform(insert, block, 0, 0);
+    for shape in &mut outline {
+        dxf_transform::transform_shape(shape, &xf);
+    }
+    let (min, max) = dxf_export::extents(&outline)?;
+
+    let prompts: HashMap<String, String> = block
+        .entities
+        .iter()
+        .filter_map(|entity| match &entity.specific {
+            EntityType::AttributeDefinition(definition) if !definition.prompt.is_empty() => Some((
+                definition.text_tag.to_ascii_uppercase(),
+                definition.prompt.clone(),
+            )),
+            _ => None,
+        })
+        .collect();
+    let fields = insert
+        .attributes()
+        .map(|attribute| TitleBlockFieldData {
+            id: attribute.attribute_tag.to_lowercase(),
+            label: prompts
+                .get(&attribute.attribute_tag.to_ascii_uppercase())
+                .cloned()
+                .unwrap_or_else(|| attribute.attribute_tag.clone()),
+            value: dxf_text::parse_text(&attribute.value).text,
+            x: attribute.location.x - min.x - CELL_PADDING,
+            y: attribute.location.y - min.y - CELL_PADDING,
+            width: None,
+            height: None,
+            font_size: attribute.text_height,
+        })
+        .collect();
+    Some(TitleBlockData {
+        x: min.x,
+        y: min.y,
+        width: max.x - min.x,
+        height: max.y - min.y,
+        fields,
+    })
+}
+
+/// Standard size name and orientation for a paper size, within a millimetre
+fn match_paper_size(width: f64, height: f64) -> (&'static str, &'static str) {
+    let orientation = if width > height {
+        "landscape"
+    } else {
+        "portrait"
+    };
+    let (short, long) = (width.min(height), width.max(height));
+    let name = PAPER_SIZES
+        .iter()
+        .find(|(_, w, h)| (w - short).abs() < 1.0 && (h - long).abs() < 1.0)
+        .map_or("Custom", |&(name, _, _)| name);
+    (name, orientation)
+}
+
+/// Layout name (upper case) to the name of the block holding its entities.
+/// LAYOUT objects point to their BLOCK_RECORD through group 330 in the AcDbLayout
+/// subclass; the `dxf` crate does not resolve that link.
+fn layout_block_names(raw: &RawRecords) -> HashMap<String, String> {
+    let record_names = handle_names(raw, "BLOCK_RECORD");
+    let mut blocks = HashMap::new();
+    for pairs in raw.of_type("LAYOUT") {
+        let Some(marker) = pairs
+            .iter()
+            .position(|(code, value)| *code == 100 && value.trim() == "AcDbLayout")
+        else {
+            continue;
+        };
+        let subclass = &pairs[marker..];
+        let name = subclass.iter().find(|(code, _)| *code == 1);
+        let owner = subclass.iter().find(|(code, _)| *code == 330);
+        if let (Some((_, name)), Some((_, owner))) = (name, owner) {
+            if let Some(block) = record_names.get(&owner.trim().to_ascii_uppercase()) {
+                blocks.insert(name.trim().to_ascii_uppercase(), block.clone());
+            }
+        }
+    }
+    blocks
+}
+
+/// Upper case handle to name (group 2) of every `record_type` table entry
+fn handle_names(raw: &RawRecords, record_type: &str) -> HashMap<String, String> {
+    raw.of_type(record_type)
+        .filter_map(|pairs| {
+            let handle = pairs.iter().find(|(code, _)| *code == 5)?;
+            let name = pairs.iter().find(|(code, _)| *code == 2)?;
+            Some((
+                handle.1.trim().to_ascii_uppercase(),
+                name.1.trim().to_string(),
+            ))
+        })
+        .collect()
+}
+
+/// VIEWPORT entities keyed by the upper case name of the block that owns them.
+/// Without owner links, as in R12, paper space viewports belong to `*Paper_Space`.
+fn read_viewports(raw: &RawRecords) -> HashMap<String, Vec<RawViewport>> {
+    let layer_names = handle_names(raw, "LAYER");
+    let block_names = handle_names(raw, "BLOCK_RECORD");
+
+    let mut viewports: HashMap<String, Vec<RawViewport>> = HashMap::new();
+    for pairs in raw.of_type("VIEWPORT") {
+        let mut viewport = RawViewport::default();
+        let mut owner = None;
+        let mut in_paper_space = false;
+        let mut in_group = false;
+        for (code, value) in pairs {
+            let value = value.trim();
+            let number = || value.parse::<f64>().unwrap_or(0.0);
+            match code {
+                // Reactors and dictionaries also use group 330
+                102 => in_group = value.starts_with('{'),
+                5 => viewport.handle = Some(value.to_string()),
+                330 if !in_group && owner.is_none() => owner = Some(value.to_ascii_uppercase()),
+                67 => in_paper_space = value == "1",
+                10 => viewport.center.x = number(),
+                20 => viewport.center.y = number(),
+                40 => viewport.width = number(),
+                41 => viewport.height = number(),
+                68 => viewport.status = value.parse().unwrap_or(0),
+                69 => viewport.id = value.parse().unwrap_or(0),
+                12 => viewport.view_center.x = number(),
+                22 => viewport.view_center.y = number(),
+                45 => viewport.view_height = number(),
+                331 => viewport
+                    .frozen_layers
+                    .extend(layer_names.get(&value.to_ascii_uppercase()).cloned()),
+                _ => {}
+            }
+        }
+        let block = match owner.and_then(|owner| block_names.get(&owner)) {
+            Some(block) => block.to_ascii_uppercase(),
+            None if in_paper_space => "*PAPER_SPACE".to_string(),
+            None => continue,
+        };
+        viewports.entry(block).or_default().push(viewport);
+    }
+    viewports
+}
@@ -0,0 +1,194 @@
+//! DXF LTYPE table conversion to and from project line style dash patterns.
+
+use super::dxf_text::sanitize_table_name;
+use super::shape::ShapeData;
+use dxf::tables::LineType;
+use dxf::Drawing;
+use serde::{Deserialize, Serialize};
+
+/// Built-in project line styles with their DXF names, descriptions and patterns.
+/// Lengths mirror `LINE_DASH_PATTERNS` in `src/engine/renderer/types.ts`.
+const PROJECT_LINE_STYLES: [(&str, &str, &str, &[f64]); 3] = [
+    ("dashed", "DASHED", "Dashed __ __ __ __", &[500.0, -250.0]),
+    ("dotted", "DOT", "Dot . . . . . . .", &[100.0, -150.0]),
+    (
+        "dashdot",
+        "DASHDOT",
+        "Dash dot __ . __ . __",
+        &[500.0, -150.0, 100.0, -150.0],
+    ),
+];
+
+/// Linetype as exchanged with the frontend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LineTypeData {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Canvas dash array (alternating dash and gap lengths, dots as 0), already
+    /// multiplied by `$LTSCALE`. Empty for continuous lines.
+    pub dash_array: Vec<f64>,
+    /// Closest project line style: "solid" | "dashed" | "dotted" | "dashdot"
+    #[serde(default)]
+    pub line_style: Option<String>,
+}
+
+/// Read the LTYPE table. `$CELTSCALE` only applies to entities created after the
+/// file was saved, so only the global `$LTSCALE` is baked into the dash arrays.
+pub fn read_line_types(drawing: &Drawing) -> Vec<LineTypeData> {
+    let scale = positive_or_one(drawing.header.line_type_scale);
+    drawing
+        .line_types()
+        .filter(|lt| !is_special(&lt.name))
+        .map(|lt| {
+            let dash_array = to_dash_array(&lt.dash_dot_space_lengths, scale);
+            LineTypeData {
+                name: lt.name.clone(),
+                description: lt.description.clone(),
+                line_style: Some(classify(&dash_array).to_string()),
+                dash_array,
+            }
+        })
+        .collect()
+}
+
+/// Write LTYPE entries for the given line types and for every project line style
+/// referenced by layers or shapes. Patterns are written at full size, so both
+/// `$LTSCALE` and `$CELTSCALE` are reset to 1.
+pub fn write_line_types(
+    drawing: &mut Drawing,
+    line_types: &[LineTypeData],
+    referenced: &[&str],
+    shapes: &[ShapeData],
+) {
+    drawing.header.line_type_scale = 1.0;
+    drawing.header.current_entity_line_type_scale = 1.0;
+
+    for data in line_types {
+        let pattern: Vec<f64> = data
+            .dash_array
+            .iter()
+            .enumerate()
+            // Even positions are dashes, odd positions are gaps
+            .map(|(i, v)| if i % 2 == 0 { v.abs() } else { -v.abs() })
+            .collect();
+        add_line_type(
+            drawing,
+            &sanitize_table_name(&data.name),
+            &data.description,
+            &pattern,
+        );
+    }
+
+    let shape_styles = shapes.iter().filter_map(|s| s.line_type.as_deref());
+    for style in referenced.iter().copied().chain(shape_styles) {
+        if let Some((_, name, description, pattern)) = PROJECT_LINE_STYLES
+            .iter()
+            .find(|(project, ..)| project.eq_ignore_ascii_case(style))
+        {
+            add_line_type(drawing, name, description, pattern);
+        }
+    }
+}
+
+/// DXF linetype name for a project line style or DXF name.
+/// "solid" maps to CONTINUOUS; unknown names are passed through.
+pub fn dxf_line_type_name(style: &str) -> String {
+    if style.is_empty() || style.eq_ignore_ascii_case("solid") {
+        return "CONTINUOUS".to_string();
+    }
+    PROJECT_LINE_STYLES
+        .iter()
+        .find(|(project, ..)| project.eq_ignore_ascii_case(style))
+        .map(|(_, name, ..)| name.to_string())
+        .unwrap_or_else(|| sanitize_table_name(style))
+}
+
+fn add_line_type(drawing: &mut Drawing, name: &str, description: &str, pattern: &[f64]) {
+    if is_special(name)
+        || drawing
+            .line_types()
+            .any(|lt| lt.name.eq_ignore_ascii_case(name))
+    {
+        return;
+    }
+    drawing.add_line_type(LineType {
+        name: name.to_string(),
+        description: description.to_string(),
+        total_pattern_length: pattern.iter().map(|v| v.abs()).sum(),
+        dash_dot_space_lengths: pattern.to_vec(),
+        ..Default::default()
+    });
+}
+
+/// ByLayer, ByBlock and Continuous are implicit and never carry a pattern
+fn is_special(name: &str) -> bool {
+    ["BYLAYER", "BYBLOCK", "CONTINUOUS"]
+        .iter()
+        .any(|special| name.eq_ignore_ascii_case(special))
+}
+
+/// Convert DXF pattern elements (positive dash, negative gap, zero dot) into an
+/// alternating canvas dash array that always starts with a dash
+fn to_dash_array(pattern: &[f64], scale: f64) -> Vec<f64> {
+    let mut dashes: Vec<(bool, f64)> = Vec::new();
+    for &element in pattern {
+        let is_gap = element < 0.0;
+        match dashes.last_mut() {
+            // Consecutive dashes or gaps merge into one
+            Some((last_gap, length)) if *last_gap == is_gap => *length += element.abs(),
+            _ => dashes.push((is_gap, element.abs())),
+        }
+    }
+    if dashes.iter().all(|(is_gap, _)| *is_gap) {
+        return Vec::new();
+    }
+    // The pattern repeats, so a leading gap joins the end and a trailing dash
+    // joins the start
+    if let Some(&(true, gap)) = dashes.first() {
+        dashes.remove(0);
+        match dashes.last_mut() {
+            Some((true, length)) => *length += gap,
+            _ => dashes.push((true, gap)),
+        }
+    }
+    if dashes.len() > 1 && dashes.last().is_some_and(|(is_gap, _)| !*is_gap) {
+        if let Some((_, length)) = dashes.pop() {
+            dashes[0].1 += length;
+        }
+    }
+    if dashes.len() == 1 {
+        // A single dash without a gap is continuous
+        return Vec::new();
+    }
+    dashes
+        .into_iter()
+        .map(|(_, length)| length * scale)
+        .collect()
+}
+
+/// Closest built-in project line style for a canvas dash array
+fn classify(dash_array: &[f64]) -> &'static str {
+    let dashes: Vec<f64> = dash_array.iter().step_by(2).copied().collect();
+    match dashes.len() {
+        0 => "solid",
+        1 if dashes[0] <= dash_array[1] * 0.5 => "dotted",
+        1 => "dashed",
+        _ => {
+            let longest = dashes.iter().copied().fold(0.0, f64::max);
+            if dashes.iter().any(|d| *d <= longest * 0.3) {
+                "dashdot"
+            } else {
+                "dashed"
+            }
+        }
+    }
+}
+
+fn positive_or_one(value: f64) -> f64 {
+    if value > 0.0 {
+        value
+    } else {
+        1.0
+    }
+}
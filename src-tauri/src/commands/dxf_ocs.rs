@@ -0,0 +1,85 @@
+//! Object Coordinate Systems (OCS). Planar entities such as arcs, circles, 2D
+//! polylines, hatches and inserts store their coordinates in the plane given by
+//! their extrusion direction; the DXF arbitrary axis algorithm derives the X and
+//! Y axes of that plane. Mirroring in AutoCAD flips the extrusion to (0, 0, -1)
+//! instead of changing the coordinates.
+
+use super::dxf_import::{POLYLINE_3D, POLYLINE_MESH};
+use super::dxf_transform::Transform;
+use super::shape::PointData;
+use dxf::entities::{EntityType, Insert};
+use dxf::Vector;
+
+/// Normals with X and Y both below this take the world Y axis as reference
+const ARBITRARY_AXIS_LIMIT: f64 = 1.0 / 64.0;
+
+/// Projection of OCS coordinates onto the world XY plane for the extrusion
+/// direction `normal`, where `elevation` is the OCS Z of the entity. `None` when
+/// the OCS is the world coordinate system.
+pub fn to_world(normal: &Vector, elevation: f64) -> Option<Transform> {
+    let length = (normal.x * normal.x + normal.y * normal.y + normal.z * normal.z).sqrt();
+    if length < 1e-12 {
+        return None;
+    }
+    let n = [normal.x / length, normal.y / length, normal.z / length];
+    if n[0].abs() < 1e-12 && n[1].abs() < 1e-12 && n[2] > 0.0 {
+        return None;
+    }
+
+    let x_axis = if n[0].abs() < ARBITRARY_AXIS_LIMIT && n[1].abs() < ARBITRARY_AXIS_LIMIT {
+        // World Y x N
+        normalize([n[2], 0.0, -n[0]])
+    } else {
+        // World Z x N
+        normalize([-n[1], n[0], 0.0])
+    };
+    let y_axis = normalize(cross(n, x_axis));
+    Some(Transform::from_axes(
+        PointData {
+            x: x_axis[0],
+            y: x_axis[1],
+        },
+        PointData {
+            x: y_axis[0],
+            y: y_axis[1],
+        },
+        PointData {
+            x: n[0] * elevation,
+            y: n[1] * elevation,
+        },
+    ))
+}
+
+/// OCS of a planar entity; `None` for entity types in world coordinates
+pub fn entity_ocs(specific: &EntityType) -> Option<Transform> {
+    match specific {
+        EntityType::Circle(circle) => to_world(&circle.normal, circle.center.z),
+        EntityType::Arc(arc) => to_world(&arc.normal, arc.center.z),
+        // The dxf crate does not read the elevation (group 38). It only moves
+        // polylines with a tilted extrusion, so they are taken as elevation 0.
+        EntityType::LwPolyline(polyline) => to_world(&polyline.extrusion_direction, 0.0),
+        // 3D polylines and meshes are in world coordinates
+        EntityType::Polyline(polyline) if polyline.flags & (POLYLINE_3D | POLYLINE_MESH) == 0 => {
+            to_world(&polyline.normal, polyline.location.z)
+        }
+        EntityType::Insert(insert) => insert_ocs(insert),
+        _ => None,
+    }
+}
+
+pub fn insert_ocs(insert: &Insert) -> Option<Transform> {
+    to_world(&insert.extrusion_direction, insert.location.z)
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(v: [f64; 3]) -> [f64; 3] {
+    let length = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    [v[0] / length, v[1] / length, v[2] / length]
+}
@@ -0,0 +1,131 @@
+//! Origin shift for drawings in large georeferenced coordinates, such as Dutch
+//! RD New (x ≈ 155000, y ≈ 463000 m). Geometry is kept near the origin in the
+//! project and the base point is added back on export.
+
+use super::dxf_export;
+use super::dxf_layouts::SheetData;
+use super::dxf_transform::{self, Transform};
+use super::shape::{PointData, ShapeData};
+
+/// Distance from the origin beyond which a drawing counts as georeferenced
+const LARGE_COORDINATE: f64 = 1.0e5;
+
+/// Drawings this many times their own size away from the origin are shifted
+const DISTANCE_TO_SIZE: f64 = 10.0;
+
+/// Detected base points are rounded down to this step so they stay readable
+const ROUNDING_STEP: f64 = 1000.0;
+
+/// Base point for a drawing whose geometry lies far from the origin compared to
+/// its size, or `None` when the coordinates can be used as they are
+pub fn detect(shapes: &[ShapeData]) -> Option<PointData> {
+    let (min, max) = dxf_export::extents(shapes)?;
+    detect_extents(min, max)
+}
+
+/// Base point for geometry within `min`..`max`, such as the `$EXTMIN`/`$EXTMAX`
+/// header extents when the shapes are not all known yet
+pub fn detect_extents(min: PointData, max: PointData) -> Option<PointData> {
+    if min.x > max.x || min.y > max.y {
+        return None;
+    }
+    let center = PointData {
+        x: (min.x + max.x) / 2.0,
+        y: (min.y + max.y) / 2.0,
+    };
+    let distance = center.x.hypot(center.y);
+    let size = (max.x - min.x).max(max.y - min.y);
+    if distance < LARGE_COORDINATE || distance < DISTANCE_TO_SIZE * size {
+        return None;
+    }
+    Some(PointData {
+        x: (min.x / ROUNDING_STEP).floor() * ROUNDING_STEP,
+        y: (min.y / ROUNDING_STEP).floor() * ROUNDING_STEP,
+    })
+}
+
+/// Move model space geometry and the viewport views onto it by `-origin`
+pub fn shift(shapes: &mut [ShapeData], sheets: &mut [SheetData], origin: PointData) {
+    transform_model(shapes, sheets, &Transform::translate(-origin.x, -origin.y));
+}
+
+/// Transform model space geometry and keep the viewports showing the same part
+/// of it. Block definitions are in block coordinates and are left alone.
+pub fn transform_model(shapes: &mut [ShapeData], sheets: &mut [SheetData], t: &Transform) {
+    for shape in shapes {
+        dxf_transform::transform_shape(shape, t);
+    }
+    let (scale_x, scale_y) = t.scale_factors();
+    let scale = (scale_x * scale_y).sqrt();
+    for viewport in sheets.iter_mut().flat_map(|sheet| &mut sheet.viewports) {
+        viewport.view_center = t.apply(viewport.view_center);
+        viewport.scale /= scale;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::dxf_layouts::ViewportData;
+    use super::*;
+
+    fn point(x: f64, y: f64) -> PointData {
+        PointData { x, y }
+    }
+
+    fn coordinates(p: Option<PointData>) -> Option<(f64, f64)> {
+        p.map(|p| (p.x, p.y))
+    }
+
+    #[test]
+    fn detects_georeferenced_extents() {
+        // A building in RD New, in millimetres
+        let origin = detect_extents(
+            point(155_123_400.0, 463_045_600.0),
+            point(155_163_400.0, 463_085_600.0),
+        );
+        assert_eq!(coordinates(origin), Some((155_123_000.0, 463_045_000.0)));
+    }
+
+    #[test]
+    fn keeps_drawings_near_the_origin() {
+        assert!(detect_extents(point(0.0, 0.0), point(50_000.0, 30_000.0)).is_none());
+        // Far away, but large compared to the distance
+        assert!(detect_extents(point(0.0, 0.0), point(2.0e6, 2.0e6)).is_none());
+    }
+
+    #[test]
+    fn ignores_inverted_extents() {
+        // Empty drawings keep the default $EXTMIN above $EXTMAX
+        assert!(detect_extents(point(1.0e20, 1.0e20), point(-1.0e20, -1.0e20)).is_none());
+    }
+
+    #[test]
+    fn shift_moves_viewport_views() {
+        let mut shapes = vec![ShapeData {
+            shape_type: "line".to_string(),
+            start: Some(point(155_000_500.0, 463_000_500.0)),
+            end: Some(point(155_001_500.0, 463_000_500.0)),
+            ..Default::default()
+        }];
+        let mut sheets = vec![SheetData {
+            viewports: vec![ViewportData {
+                view_center: point(155_001_000.0, 463_000_500.0),
+                scale: 0.01,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }];
+        shift(
+            &mut shapes,
+            &mut sheets,
+            point(155_000_000.0, 463_000_000.0),
+        );
+        assert_eq!(coordinates(shapes[0].start), Some((500.0, 500.0)));
+        let viewport = &sheets[0].viewports[0];
+        assert_eq!(
+            coordinates(Some(viewport.view_center)),
+            Some((1000.0, 500.0))
+        );
+        assert_eq!(viewport.scale, 0.01);
+    }
+}
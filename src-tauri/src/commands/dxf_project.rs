@@ -0,0 +1,187 @@
+//! Export of several drawings of a project at once, either as named blocks in one
+//! DXF file with an overview layout, or as one DXF file per drawing.
+
+use super::dxf_export::{self, DxfExportOptions};
+use super::dxf_file::{self, BlockContent, Content, ExportTables};
+use super::dxf_layouts::{SheetData, ViewportData};
+use super::dxf_report::ConversionReport;
+use super::dxf_text::sanitize_table_name;
+use super::shape::{PointData, ShapeData};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// Distance between the overview viewport and the paper edge, in mm
+const OVERVIEW_MARGIN: f64 = 10.0;
+
+/// Drawing as exchanged with the frontend
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DrawingExportData {
+    pub name: String,
+    pub shapes: Vec<ShapeData>,
+    /// Layouts written with the drawing when each drawing gets its own file
+    pub sheets: Vec<SheetData>,
+}
+
+/// Settings of the project export dialog
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DxfProjectExportOptions {
+    #[serde(flatten)]
+    pub dxf: DxfExportOptions,
+    /// "blocks": one file with a block per drawing; "files": one file per drawing
+    pub mode: String,
+    /// Space between drawings placed side by side, in project millimetres
+    pub gap: f64,
+    /// Paper size of the overview layout in "blocks" mode, as in `SheetData`
+    pub overview_paper_size: String,
+}
+
+impl Default for DxfProjectExportOptions {
+    fn default() -> Self {
+        Self {
+            dxf: DxfExportOptions::default(),
+            mode: "blocks".to_string(),
+            gap: 5000.0,
+            overview_paper_size: "A3".to_string(),
+        }
+    }
+}
+
+/// Write every drawing as a block into the file at `path`. The blocks are
+/// inserted side by side in model space and shown together on an "Overview"
+/// layout.
+pub fn export_blocks(
+    path: &str,
+    drawings: Vec<DrawingExportData>,
+    tables: &ExportTables,
+    options: &DxfProjectExportOptions,
+) -> Result<ConversionReport, String> {
+    let mut names = HashSet::new();
+    let mut blocks = Vec::new();
+    let mut cursor = 0.0;
+    let mut height: f64 = 0.0;
+    for drawing in drawings {
+        let name = unique_name(&mut names, sanitize_table_name(&drawing.name), "_");
+        // Bottoms are aligned; empty drawings take no space
+        let offset = match dxf_export::extents(&drawing.shapes) {
+            Some((min, max)) => {
+                let offset = PointData {
+                    x: cursor - min.x,
+                    y: -min.y,
+                };
+                cursor += max.x - min.x + options.gap;
+                height = height.max(max.y - min.y);
+                offset
+            }
+            None => PointData { x: cursor, y: 0.0 },
+        };
+        blocks.push(BlockContent {
+            name,
+            shapes: drawing.shapes,
+            offset,
+        });
+    }
+    let width = (cursor - options.gap).max(0.0);
+
+    let content = Content {
+        model: Vec::new(),
+        blocks,
+    };
+    let overview = overview_sheet(&options.overview_paper_size, width, height);
+    dxf_file::write_file(path, content, &[overview], tables, &options.dxf)
+}
+
+/// Write every drawing with its own sheets into a file named after it in the
+/// folder `dir`, which is created if needed. Returns the files written.
+pub fn export_files(
+    dir: &str,
+    drawings: Vec<DrawingExportData>,
+    tables: &ExportTables,
+    options: &DxfProjectExportOptions,
+) -> Result<(Vec<String>, ConversionReport), String> {
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create folder {}: {}", dir, e))?;
+    let mut names = HashSet::new();
+    let mut files = Vec::new();
+    let mut report = ConversionReport::default();
+    for drawing in drawings {
+        let name = unique_name(&mut names, sanitize_file_name(&drawing.name), "-");
+        let path = Path::new(dir).join(format!("{}.dxf", name));
+        let path = path.to_string_lossy().into_owned();
+        let content = Content {
+            model: drawing.shapes,
+            blocks: Vec::new(),
+        };
+        let written = dxf_file::write_file(&path, content, &drawing.sheets, tables, &options.dxf)
+            .map_err(|e| format!("{} ({})", e, drawing.name))?;
+        report.merge(written);
+        files.push(path);
+    }
+    Ok((files, report))
+}
+
+/// A3 landscape (or the chosen size) sheet with one viewport fitting the drawings
+fn overview_sheet(paper_size: &str, width: f64, height: f64) -> SheetData {
+    let mut sheet = SheetData {
+        name: "Overview".to_string(),
+        paper_size: paper_size.to_string(),
+        orientation: "landscape".to_string(),
+        ..Default::default()
+    };
+    let (paper_width, paper_height) = sheet.paper_dimensions();
+    let viewport_width = paper_width - 2.0 * OVERVIEW_MARGIN;
+    let viewport_height = paper_height - 2.0 * OVERVIEW_MARGIN;
+    let scale = if width > 0.0 && height > 0.0 {
+        (viewport_width / width).min(viewport_height / height)
+    } else {
+        1.0
+    };
+    sheet.viewports.push(ViewportData {
+        center: PointData {
+            x: paper_width / 2.0,
+            y: paper_height / 2.0,
+        },
+        width: viewport_width,
+        height: viewport_height,
+        view_center: PointData {
+            x: width / 2.0,
+            y: height / 2.0,
+        },
+        scale,
+        visible: Some(true),
+        ..Default::default()
+    });
+    sheet
+}
+
+/// Replace characters that are not allowed in file names
+fn sanitize_file_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | '/' | '\\' | '"' | ':' | '?' | '*' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let cleaned = cleaned.trim().trim_end_matches('.').to_string();
+    if cleaned.is_empty() {
+        "drawing".to_string()
+    } else {
+        cleaned
+    }
+}
+
+/// `name`, or `name{separator}2`, `name{separator}3`, ... if it is already taken.
+/// Names are compared case-insensitively, as DXF tables and most file systems do.
+fn unique_name(taken: &mut HashSet<String>, name: String, separator: &str) -> String {
+    let mut candidate = name.clone();
+    let mut counter = 2;
+    while !taken.insert(candidate.to_lowercase()) {
+        candidate = format!("{}{}{}", name, separator, counter);
+        counter += 1;
+    }
+    candidate
+}
@@ -0,0 +1,1149 @@
+//! Minimal ASCII DXF group code reader for data the `dxf` crate does not expose.
+
+use dxf::enums::AcadVersion;
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+
+/// Sentinel at the start of binary DXF files
+const BINARY_SENTINEL: &[u8] = b"AutoCAD Binary DXF";
+
+/// `$ACADVER` of R2007, from which text is stored as UTF-8
+const UTF8_VERSION: &str = "AC1021";
+
+/// Iterator over `(group code, value)` pairs of an ASCII DXF stream. Text is
+/// decoded like the `dxf` crate does: with the code page of `$DWGCODEPAGE` up to
+/// R2004, with `\U+XXXX` escapes for other characters, and as UTF-8 from R2007.
+pub struct CodePairReader<R: BufRead> {
+    reader: R,
+    code_line: Vec<u8>,
+    value_line: Vec<u8>,
+    encoding: &'static Encoding,
+    /// Header variable of the previous group 9
+    variable: String,
+    first_line: bool,
+    bytes_read: u64,
+}
+
+impl<R: BufRead> CodePairReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            code_line: Vec::new(),
+            value_line: Vec::new(),
+            encoding: WINDOWS_1252,
+            variable: String::new(),
+            first_line: true,
+            bytes_read: 0,
+        }
+    }
+
+    /// Bytes of the stream read so far
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// Encoding of the text read so far; final once the header has been read
+    pub fn encoding(&self) -> &'static Encoding {
+        self.encoding
+    }
+
+    fn decode<'a>(&self, line: &'a [u8]) -> Cow<'a, str> {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let (text, _) = self.encoding.decode_without_bom_handling(line);
+        if self.encoding != UTF_8 && text.contains("\\U+") {
+            Cow::Owned(unescape_unicode(&text))
+        } else {
+            text
+        }
+    }
+
+    /// Switch encodings on the header variables that select them
+    fn follow_header(&mut self, code: i32, value: &str) {
+        match (code, self.variable.as_str()) {
+            (9, _) => self.variable = value.trim().to_string(),
+            (1, "$ACADVER") if value.trim() >= UTF8_VERSION => self.encoding = UTF_8,
+            (3, "$DWGCODEPAGE") if self.encoding != UTF_8 => {
+                self.encoding = code_page_encoding(value.trim());
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for CodePairReader<R> {
+    type Item = io::Result<(i32, String)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.code_line.clear();
+        self.value_line.clear();
+        match self.reader.read_until(b'\n', &mut self.code_line) {
+            Ok(0) => return None,
+            Ok(n) => self.bytes_read += n as u64,
+            Err(e) => return Some(Err(e)),
+        }
+        match self.reader.read_until(b'\n', &mut self.value_line) {
+            Ok(n) => self.bytes_read += n as u64,
+            Err(e) => return Some(Err(e)),
+        }
+        // A byte order mark makes the whole file UTF-8
+        if std::mem::take(&mut self.first_line) {
+            if let Some(line) = self.code_line.strip_prefix(b"\xEF\xBB\xBF") {
+                self.code_line = line.to_vec();
+                self.encoding = UTF_8;
+            }
+        }
+        let code_line = String::from_utf8_lossy(&self.code_line);
+        let code = match code_line.trim().parse::<i32>() {
+            Ok(code) => code,
+            Err(_) => {
+                return Some(Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid group code '{}'", code_line.trim()),
+                )))
+            }
+        };
+        let value = self.decode(&self.value_line).into_owned();
+        if code == 9 || !self.variable.is_empty() {
+            self.follow_header(code, &value);
+        }
+        // Only the header has variables; its end stops following them
+        if code == 0 {
+            self.variable.clear();
+        }
+        Some(Ok((code, value)))
+    }
+}
+
+/// Encoding of a `$DWGCODEPAGE` value such as "ANSI_1252". Code pages without
+/// an encoding are read as Windows-1252, like the `dxf` crate does.
+pub fn code_page_encoding(code_page: &str) -> &'static Encoding {
+    let label = match code_page.to_ascii_uppercase().as_str() {
+        "ANSI_932" | "DOS932" => "shift_jis".to_string(),
+        "ANSI_936" => "gbk".to_string(),
+        "ANSI_949" => "euc-kr".to_string(),
+        "ANSI_950" => "big5".to_string(),
+        "DOS866" => "ibm866".to_string(),
+        page => match page.strip_prefix("ANSI_") {
+            Some(number) => format!("windows-{}", number),
+            None => String::new(),
+        },
+    };
+    Encoding::for_label(label.as_bytes()).unwrap_or(WINDOWS_1252)
+}
+
+/// Replace `\U+XXXX` escapes by the characters they stand for
+fn unescape_unicode(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("\\U+") {
+        result.push_str(&rest[..start]);
+        let escape = &rest[start..];
+        let character = escape
+            .get(3..7)
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .and_then(char::from_u32);
+        match character {
+            Some(character) => {
+                result.push(character);
+                rest = &escape[7..];
+            }
+            None => {
+                result.push_str("\\U+");
+                rest = &escape[3..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Code page the file's text is stored in before R2007, for loading it with the
+/// `dxf` crate. Binary and unreadable files get the crate's default.
+pub fn code_page(path: &str) -> &'static Encoding {
+    let Ok(Some(mut pairs)) = open(path) else {
+        return WINDOWS_1252;
+    };
+    // The header is the first section; its end is the first ENDSEC
+    for pair in pairs.by_ref() {
+        match pair {
+            Ok((0, value)) if value.trim() == "ENDSEC" => break,
+            Ok(_) => {}
+            Err(_) => return WINDOWS_1252,
+        }
+    }
+    // The dxf crate switches to UTF-8 for R2007 and later itself
+    if pairs.encoding() == UTF_8 {
+        WINDOWS_1252
+    } else {
+        pairs.encoding()
+    }
+}
+
+/// Open an ASCII DXF file for group code reading. Returns `Ok(None)` for binary DXF.
+pub fn open(path: &str) -> io::Result<Option<CodePairReader<BufReader<File>>>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    if reader.fill_buf()?.starts_with(BINARY_SENTINEL) {
+        return Ok(None);
+    }
+    Ok(Some(CodePairReader::new(reader)))
+}
+
+/// Bytes read between two calls of the `RawRecords::read` progress callback
+const PROGRESS_STEP: u64 = 1 << 20;
+
+/// Entities read from their group codes rather than through the `dxf` crate
+const RAW_ENTITY_TYPES: [&str; 3] = ["HATCH", "IMAGE", "VIEWPORT"];
+
+/// Records of the ENTITIES and BLOCKS sections the `dxf` crate reads; it skips
+/// any other entity type without a trace
+const CRATE_ENTITY_TYPES: [&str; 44] = [
+    "3DFACE",
+    "3DLINE",
+    "3DSOLID",
+    "ACAD_PROXY_ENTITY",
+    "ARC",
+    "ARCALIGNEDTEXT",
+    "ATTDEF",
+    "ATTRIB",
+    "BLOCK",
+    "BODY",
+    "CIRCLE",
+    "DGNUNDERLAY",
+    "DIMENSION",
+    "DWFUNDERLAY",
+    "ELLIPSE",
+    "ENDBLK",
+    "HELIX",
+    "IMAGE",
+    "INSERT",
+    "LEADER",
+    "LIGHT",
+    "LINE",
+    "LWPOLYLINE",
+    "MLINE",
+    "MTEXT",
+    "OLE2FRAME",
+    "OLEFRAME",
+    "PDFUNDERLAY",
+    "POINT",
+    "POLYLINE",
+    "RAY",
+    "REGION",
+    "RTEXT",
+    "SECTION",
+    "SEQEND",
+    "SHAPE",
+    "SOLID",
+    "SPLINE",
+    "TEXT",
+    "TOLERANCE",
+    "TRACE",
+    "VERTEX",
+    "WIPEOUT",
+    "XLINE",
+];
+
+/// A record of an ASCII DXF file: its type, the section it is in and its group
+/// codes after the leading `0` pair
+#[derive(Debug, Clone, Default)]
+pub struct RawRecord {
+    pub record_type: String,
+    pub section: String,
+    /// Name of the block definition a BLOCKS section record belongs to
+    pub block: String,
+    pub pairs: Vec<(i32, String)>,
+}
+
+/// The records of a DXF file with data the `dxf` crate does not expose, read in
+/// one pass: table entries, objects, HATCH, IMAGE and VIEWPORT entities, and
+/// entities with XDATA or an extension dictionary. Binary files yield none.
+#[derive(Debug, Default)]
+pub struct RawRecords {
+    records: Vec<RawRecord>,
+    /// Type and handle of every entity neither the `dxf` crate nor a raw
+    /// converter reads, e.g. MULTILEADER, TABLE or MESH
+    pub unread_entities: Vec<(String, Option<String>)>,
+    /// Binary DXF, which is not read group code by group code
+    pub binary: bool,
+}
+
+impl RawRecords {
+    /// Read the records of `path`. `progress` gets the bytes read every megabyte;
+    /// an error from it stops reading, e.g. when the import is cancelled.
+    pub fn read(path: &str, mut progress: impl FnMut(u64) -> io::Result<()>) -> io::Result<Self> {
+        let Some(mut pairs) = open(path)? else {
+            return Ok(Self {
+                binary: true,
+                ..Self::default()
+            });
+        };
+
+        let mut records = Vec::new();
+        let mut unread_entities = Vec::new();
+        let mut section = String::new();
+        let mut block = String::new();
+        let mut current = RawRecord::default();
+        let mut next_report = PROGRESS_STEP;
+        while let Some(pair) = pairs.next() {
+            let (code, value) = pair?;
+            if code != 0 {
+                if current.record_type == "SECTION" && code == 2 {
+                    section = value.trim().to_string();
+                } else if current.record_type == "BLOCK" && code == 2 {
+                    block = value.trim().to_string();
+                }
+                current.pairs.push((code, value));
+                continue;
+            }
+            if pairs.bytes_read() >= next_report {
+                next_report = pairs.bytes_read() + PROGRESS_STEP;
+                progress(pairs.bytes_read())?;
+            }
+            let record = std::mem::take(&mut current);
+            if is_unread_entity(&record) {
+                let handle = record.pairs.iter().find(|(code, _)| *code == 5);
+                let handle = handle.map(|(_, value)| value.trim().to_uppercase());
+                unread_entities.push((record.record_type.clone(), handle));
+            }
+            if is_raw_record(&record) {
+                records.push(record);
+            }
+            let value = value.trim();
+            if value == "EOF" {
+                break;
+            }
+            if value == "ENDSEC" {
+                section.clear();
+                block.clear();
+            }
+            current.record_type = value.to_string();
+            current.section = section.clone();
+            current.block = block.clone();
+        }
+        Ok(Self {
+            records,
+            unread_entities,
+            binary: false,
+        })
+    }
+
+    /// Every record kept, in file order
+    pub fn iter(&self) -> impl Iterator<Item = &RawRecord> {
+        self.records.iter()
+    }
+
+    /// Group codes of every `record_type` record in any section (table entries,
+    /// entities inside blocks, objects)
+    pub fn of_type<'a>(
+        &'a self,
+        record_type: &'a str,
+    ) -> impl Iterator<Item = &'a [(i32, String)]> + 'a {
+        self.records
+            .iter()
+            .filter(move |record| record.record_type == record_type)
+            .map(|record| record.pairs.as_slice())
+    }
+
+    /// Group codes of every `entity_type` entity in the ENTITIES section
+    pub fn entities<'a>(
+        &'a self,
+        entity_type: &'a str,
+    ) -> impl Iterator<Item = &'a [(i32, String)]> + 'a {
+        self.records
+            .iter()
+            .filter(move |record| record.section == "ENTITIES" && record.record_type == entity_type)
+            .map(|record| record.pairs.as_slice())
+    }
+
+    /// Block name and group codes of every `entity_type` entity inside a block
+    /// definition
+    pub fn block_entities<'a>(
+        &'a self,
+        entity_type: &'a str,
+    ) -> impl Iterator<Item = (&'a str, &'a [(i32, String)])> + 'a {
+        self.records
+            .iter()
+            .filter(move |record| record.section == "BLOCKS" && record.record_type == entity_type)
+            .map(|record| (record.block.as_str(), record.pairs.as_slice()))
+    }
+}
+
+fn is_unread_entity(record: &RawRecord) -> bool {
+    matches!(record.section.as_str(), "ENTITIES" | "BLOCKS")
+        && !CRATE_ENTITY_TYPES.contains(&record.record_type.as_str())
+        && !RAW_ENTITY_TYPES.contains(&record.record_type.as_str())
+}
+
+fn is_raw_record(record: &RawRecord) -> bool {
+    match record.section.as_str() {
+        "TABLES" | "OBJECTS" => true,
+        "ENTITIES" | "BLOCKS" => {
+            RAW_ENTITY_TYPES.contains(&record.record_type.as_str())
+                || record.pairs.iter().any(|(code, value)| {
+                    *code == 1001 || (*code == 102 && value.trim() == "{ACAD_XDICTIONARY")
+                })
+        }
+        _ => false,
+    }
+}
+
+/// LAYER table groups the `dxf` crate skips
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LayerGroups {
+    /// Group 70: 1 frozen, 4 locked
+    pub flags: i32,
+    /// Group 420: 24-bit true colour
+    pub true_color: Option<u32>,
+}
+
+/// The flags and true colour of each LAYER table entry, keyed by layer name
+pub fn layer_groups(raw: &RawRecords) -> HashMap<String, LayerGroups> {
+    let mut layers = HashMap::new();
+    for pairs in raw.of_type("LAYER") {
+        let mut name = None;
+        let mut groups = LayerGroups::default();
+        for (code, value) in pairs {
+            match code {
+                2 => name = Some(value.clone()),
+                70 => groups.flags = value.trim().parse().unwrap_or(0),
+                420 => {
+                    groups.true_color = value
+                        .trim()
+                        .parse::<i64>()
+                        .ok()
+                        .map(|c| (c & 0xFF_FFFF) as u32)
+                }
+                _ => {}
+            }
+        }
+        if let Some(name) = name.filter(|name| !name.is_empty()) {
+            layers.insert(name, groups);
+        }
+    }
+    layers
+}
+
+/// Group codes for entities the `dxf` crate cannot write, added to the
+/// ENTITIES section of a file it has already saved
+pub struct RawEntityWriter {
+    out: Vec<String>,
+    next_handle: Option<u64>,
+    owner: Option<String>,
+}
+
+impl RawEntityWriter {
+    /// Start a new entity: type, handle and owner (when the file uses handles).
+    /// Returns the handle.
+    pub fn start_entity(&mut self, entity_type: &str) -> Option<String> {
+        self.pair(0, entity_type);
+        let value = self.allocate_handle()?;
+        self.pair(5, &value);
+        if let Some(owner) = self.owner.clone() {
+            self.pair(330, owner);
+        }
+        Some(value)
+    }
+
+    pub fn pair(&mut self, code: i32, value: impl Display) {
+        push_pair(&mut self.out, code, value);
+    }
+
+    /// Handle for an object written separately, e.g. by `append_objects`
+    pub fn allocate_handle(&mut self) -> Option<String> {
+        let handle = self.next_handle.as_mut()?;
+        let value = format!("{:X}", handle);
+        *handle += 1;
+        Some(value)
+    }
+}
+
+/// Escape characters outside ASCII as `\U+XXXX` in lines added to a file of
+/// `version`, as the `dxf` crate does for text before R2007, whose files
+/// declare an ANSI code page. Later files are UTF-8.
+pub fn escape_unicode(lines: &mut [String], version: AcadVersion) {
+    if version >= AcadVersion::R2007 {
+        return;
+    }
+    for line in lines.iter_mut().filter(|line| !line.is_ascii()) {
+        let mut escaped = String::with_capacity(line.len() * 2);
+        for c in line.chars() {
+            if c.is_ascii() {
+                escaped.push(c);
+            } else {
+                escaped.push_str(&format!("\\U+{:04X}", u32::from(c)));
+            }
+        }
+        *line = escaped;
+    }
+}
+
+/// Append one group code pair in the layout the `dxf` crate writes
+pub fn push_pair(out: &mut Vec<String>, code: i32, value: impl Display) {
+    out.push(format!("{:>3}", code));
+    out.push(value.to_string());
+}
+
+/// Rewrite an ASCII DXF file with extra entities at the start of its ENTITIES
+/// section, or of the definition of `block`, so they draw behind the rest.
+/// The entities of `*Paper_Space`, the active layout, also go to the ENTITIES
+/// section. Handles continue from `$HANDSEED`, which is advanced past them.
+pub fn insert_entities(
+    path: &str,
+    block: Option<&str>,
+    version: AcadVersion,
+    write: impl FnOnce(&mut RawEntityWriter),
+) -> io::Result<()> {
+    let (mut lines, newline) = read_lines(path)?;
+
+    let owner_name = block.unwrap_or("*Model_Space");
+    let in_entities = owner_name.eq_ignore_ascii_case("*Model_Space")
+        || owner_name.eq_ignore_ascii_case("*Paper_Space");
+    let mut handle_seed = None;
+    let mut owner = None;
+    let mut insert_at = None;
+    let mut in_block = false;
+    let mut entry = "";
+    let mut entry_handle = None;
+    let mut pairs = lines.chunks_exact(2).enumerate();
+    while let Some((index, pair)) = pairs.next() {
+        let (code, value) = (pair[0].trim(), pair[1].trim());
+        match code {
+            // The entities of a block follow its BLOCK record
+            "0" if in_block => {
+                insert_at = Some(index * 2);
+                break;
+            }
+            "0" => {
+                entry = value;
+                entry_handle = None;
+            }
+            // Header and tables come first, so everything needed has been seen
+            "2" if in_entities && entry == "SECTION" && value == "ENTITIES" => {
+                insert_at = Some(index * 2 + 2);
+                break;
+            }
+            "2" if !in_entities && entry == "BLOCK" && value.eq_ignore_ascii_case(owner_name) => {
+                in_block = true;
+            }
+            "2" if entry == "BLOCK_RECORD" && value.eq_ignore_ascii_case(owner_name) => {
+                owner = entry_handle.clone();
+            }
+            "5" if entry == "BLOCK_RECORD" => entry_handle = Some(value.to_string()),
+            // The seed value is the next pair; remember its line index
+            "9" if value == "$HANDSEED" => {
+                if let Some((_, seed)) = pairs.next() {
+                    let seed = u64::from_str_radix(seed[1].trim(), 16).ok();
+                    handle_seed = seed.map(|seed| (index * 2 + 3, seed));
+                }
+            }
+            _ => {}
+        }
+    }
+    let insert_at = insert_at.ok_or_else(|| {
+        let message = if in_entities {
+            "DXF file has no ENTITIES section".to_string()
+        } else {
+            format!("DXF file has no block '{}'", owner_name)
+        };
+        io::Error::new(io::ErrorKind::InvalidData, message)
+    })?;
+
+    let mut writer = RawEntityWriter {
+        out: Vec::new(),
+        next_handle: handle_seed.map(|(_, seed)| seed),
+        owner,
+    };
+    write(&mut writer);
+    escape_unicode(&mut writer.out, version);
+
+    if let (Some((line, _)), Some(next)) = (handle_seed, writer.next_handle) {
+        lines[line] = format!("{:X}", next);
+    }
+    lines.splice(insert_at..insert_at, writer.out);
+    write_lines(path, &lines, newline)
+}
+
+/// Rewrite every `record_type` record of an ASCII DXF file of `version`.
+/// `edit` gets the group codes after the leading `0` pair.
+pub fn edit_records(
+    path: &str,
+    record_type: &str,
+    version: AcadVersion,
+    mut edit: impl FnMut(&mut Vec<(i32, String)>),
+) -> io::Result<()> {
+    let (lines, newline) = read_lines(path)?;
+
+    let mut out = Vec::with_capacity(lines.len());
+    let mut record: Option<Vec<(i32, String)>> = None;
+    for pair in lines.chunks_exact(2) {
+        let code = pair[0].trim();
+        if code == "0" {
+            if let Some(mut pairs) = record.take() {
+                edit(&mut pairs);
+                for (code, value) in &pairs {
+                    push_pair(&mut out, *code, value);
+                }
+            }
+            out.extend_from_slice(pair);
+            if pair[1].trim() == record_type {
+                record = Some(Vec::new());
+            }
+            continue;
+        }
+        match &mut record {
+            Some(pairs) => {
+                let code = code.parse().map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Invalid group code '{}'", code),
+                    )
+                })?;
+                pairs.push((code, pair[1].clone()));
+            }
+            None => out.extend_from_slice(pair),
+        }
+    }
+    escape_unicode(&mut out, version);
+    write_lines(path, &out, newline)
+}
+
+/// Replace the value of the first `code` group, or add the group at the end
+pub fn set_group(pairs: &mut Vec<(i32, String)>, code: i32, value: impl Display) {
+    match pairs.iter_mut().find(|(c, _)| *c == code) {
+        Some((_, existing)) => *existing = value.to_string(),
+        None => pairs.push((code, value.to_string())),
+    }
+}
+
+/// Custom class registered in the CLASSES section
+pub struct ClassDefinition {
+    /// DXF record name, e.g. "IMAGE"
+    pub name: &'static str,
+    pub class_name: &'static str,
+    pub application: &'static str,
+    pub proxy_flags: i64,
+    pub is_entity: bool,
+}
+
+/// Register custom classes the file does not define yet. The CLASSES section is
+/// created before TABLES when missing.
+pub fn add_classes(path: &str, classes: &[ClassDefinition]) -> io::Result<()> {
+    let (mut lines, newline) = read_lines(path)?;
+
+    let mut defined = Vec::new();
+    let mut section = "";
+    let mut entry = "";
+    let mut insert_at = None;
+    let mut tables_at = None;
+    for (index, pair) in lines.chunks_exact(2).enumerate() {
+        let (code, value) = (pair[0].trim(), pair[1].trim());
+        match code {
+            "0" => {
+                if value == "ENDSEC" && section == "CLASSES" {
+                    insert_at = Some(index * 2);
+                }
+                entry = value;
+            }
+            "1" if entry == "CLASS" => defined.push(value.to_string()),
+            "2" if entry == "SECTION" => {
+                section = value;
+                // Before the `0 SECTION` pair of TABLES
+                if value == "TABLES" && tables_at.is_none() {
+                    tables_at = Some(index * 2 - 2);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut out = Vec::new();
+    for class in classes
+        .iter()
+        .filter(|c| !defined.iter().any(|d| d == c.name))
+    {
+        push_pair(&mut out, 0, "CLASS");
+        push_pair(&mut out, 1, class.name);
+        push_pair(&mut out, 2, class.class_name);
+        push_pair(&mut out, 3, class.application);
+        push_pair(&mut out, 90, class.proxy_flags);
+        push_pair(&mut out, 280, 0);
+        push_pair(&mut out, 281, i32::from(class.is_entity));
+    }
+    if out.is_empty() {
+        return Ok(());
+    }
+    let at = match (insert_at, tables_at) {
+        (Some(at), _) => at,
+        (None, Some(at)) => {
+            let mut section = Vec::new();
+            push_pair(&mut section, 0, "SECTION");
+            push_pair(&mut section, 2, "CLASSES");
+            section.append(&mut out);
+            push_pair(&mut section, 0, "ENDSEC");
+            out = section;
+            at
+        }
+        (None, None) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "DXF file has no TABLES section",
+            ))
+        }
+    };
+    lines.splice(at..at, out);
+    write_lines(path, &lines, newline)
+}
+
+/// Append records at the end of the OBJECTS section and add `(name, handle)`
+/// entries to the root dictionary. `write` gets the handle of the root
+/// dictionary, which owns dictionaries added to it.
+pub fn append_objects(
+    path: &str,
+    entries: &[(&str, &str)],
+    version: AcadVersion,
+    write: impl FnOnce(&str, &mut Vec<String>),
+) -> io::Result<()> {
+    let (mut lines, newline) = read_lines(path)?;
+
+    let mut section = "";
+    let mut entry = "";
+    let mut root = None;
+    let mut in_root = false;
+    let mut entries_at = None;
+    let mut objects_end = None;
+    for (index, pair) in lines.chunks_exact(2).enumerate() {
+        let (code, value) = (pair[0].trim(), pair[1].trim());
+        match code {
+            "0" => {
+                // The root dictionary ends where the next object starts
+                if in_root {
+                    entries_at = Some(index * 2);
+                }
+                if value == "ENDSEC" && section == "OBJECTS" {
+                    objects_end = Some(index * 2);
+                    break;
+                }
+                // The first object is the root dictionary
+                in_root = section == "OBJECTS" && entries_at.is_none() && value == "DICTIONARY";
+                entry = value;
+            }
+            "2" if entry == "SECTION" => section = value,
+            "5" if in_root => root = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    let (Some(root), Some(entries_at), Some(objects_end)) = (root, entries_at, objects_end) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "DXF file has no OBJECTS section with a root dictionary",
+        ));
+    };
+
+    let mut objects = Vec::new();
+    write(&root, &mut objects);
+    escape_unicode(&mut objects, version);
+    lines.splice(objects_end..objects_end, objects);
+    let mut root_entries = Vec::new();
+    for (name, handle) in entries {
+        push_pair(&mut root_entries, 3, name);
+        push_pair(&mut root_entries, 350, handle);
+    }
+    escape_unicode(&mut root_entries, version);
+    lines.splice(entries_at..entries_at, root_entries);
+    write_lines(path, &lines, newline)
+}
+
+/// Freeze layers in VIEWPORT entities, keyed by viewport ID (group 69). The layer
+/// handles (group 331) are looked up in the LAYER table of the saved file.
+pub fn freeze_viewport_layers(path: &str, frozen: &HashMap<i32, Vec<String>>) -> io::Result<()> {
+    if frozen.is_empty() {
+        return Ok(());
+    }
+    let (lines, newline) = read_lines(path)?;
+
+    let mut layer_handles = HashMap::new();
+    let mut entry = "";
+    let mut handle = None;
+    for pair in lines.chunks_exact(2) {
+        let (code, value) = (pair[0].trim(), pair[1].trim());
+        match code {
+            "0" => {
+                entry = value;
+                handle = None;
+            }
+            "5" if entry == "LAYER" => handle = Some(value.to_string()),
+            "2" if entry == "LAYER" => {
+                if let Some(handle) = handle.clone() {
+                    layer_handles.insert(value.to_ascii_uppercase(), handle);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut out = Vec::with_capacity(lines.len());
+    let mut entry = String::new();
+    for pair in lines.chunks_exact(2) {
+        out.extend_from_slice(pair);
+        let (code, value) = (pair[0].trim(), pair[1].trim());
+        if code == "0" {
+            entry = value.to_string();
+        } else if code == "69" && entry == "VIEWPORT" {
+            let layers = value.parse().ok().and_then(|id: i32| frozen.get(&id));
+            for layer in layers.into_iter().flatten() {
+                if let Some(handle) = layer_handles.get(&layer.to_ascii_uppercase()) {
+                    out.push(format!("{:>3}", 331));
+                    out.push(handle.clone());
+                }
+            }
+        }
+    }
+    write_lines(path, &out, newline)
+}
+
+/// Round point coordinates (groups 10-39) to `decimals` places. Directions,
+/// spacings and the pixel vectors of images are not coordinates and are kept.
+pub fn round_coordinates(path: &str, decimals: u8) -> io::Result<()> {
+    let (mut lines, newline) = read_lines(path)?;
+    let mut record = String::new();
+    let mut variable = String::new();
+    for pair in lines.chunks_exact_mut(2) {
+        let code = pair[0].trim().parse::<i32>();
+        match code {
+            Ok(0) => record = pair[1].trim().to_string(),
+            Ok(9) => variable = pair[1].trim().to_string(),
+            _ => {}
+        }
+        let is_coordinate = match code {
+            Ok(code) => (10..=39).contains(&code) && !is_vector(&record, &variable, code),
+            Err(_) => false,
+        };
+        if let (true, Ok(value)) = (is_coordinate, pair[1].trim().parse::<f64>()) {
+            pair[1] = format!("{:.*}", usize::from(decimals), value);
+        }
+    }
+    write_lines(path, &lines, newline)
+}
+
+/// Groups 10-39 of `record` (or of header `variable`) that hold a direction,
+/// a spacing or a pixel vector rather than a point
+fn is_vector(record: &str, variable: &str, code: i32) -> bool {
+    let x_code = code % 10 + 10;
+    match record {
+        // Header variables such as $UCSXDIR
+        "SECTION" => variable.ends_with("DIR"),
+        "IMAGE" | "WIPEOUT" => matches!(x_code, 11 | 12),
+        "MTEXT" | "XLINE" | "RAY" => x_code == 11,
+        // Start and end tangents
+        "SPLINE" => matches!(x_code, 12 | 13),
+        // Segment and miter directions
+        "MLINE" => matches!(x_code, 12 | 13),
+        // View direction
+        "VIEW" => x_code == 11,
+        // X and Y axes
+        "UCS" => matches!(x_code, 11 | 12),
+        // Snap and grid spacing, view direction
+        "VPORT" | "VIEWPORT" => (14..=16).contains(&x_code),
+        _ => false,
+    }
+}
+
+/// Value encoding of a group code in binary DXF
+enum BinaryValue {
+    Text,
+    Double,
+    Short,
+    Int,
+    Long,
+    Bool,
+    Bytes,
+}
+
+fn binary_value(code: i32) -> BinaryValue {
+    use BinaryValue::*;
+    match code {
+        10..=59 | 110..=149 | 210..=239 | 460..=469 | 1010..=1059 => Double,
+        60..=79 | 170..=179 | 270..=289 | 370..=389 | 400..=409 | 1060..=1070 => Short,
+        90..=99 | 420..=429 | 440..=459 | 1071 => Int,
+        160..=169 => Long,
+        290..=299 => Bool,
+        310..=319 | 1004 => Bytes,
+        _ => Text,
+    }
+}
+
+/// Convert an ASCII DXF file to binary DXF in place. R12 and older use one byte
+/// group codes; later versions use two.
+pub fn convert_to_binary(path: &str, one_byte_codes: bool) -> io::Result<()> {
+    let (lines, _) = read_lines(path)?;
+    let invalid = |what: &str, value: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid {} '{}'", what, value),
+        )
+    };
+
+    let mut out = Vec::with_capacity(lines.len() * 8);
+    out.extend_from_slice(BINARY_SENTINEL);
+    out.extend_from_slice(b"\r\n\x1a\0");
+    for pair in lines.chunks_exact(2) {
+        let code: i32 = pair[0]
+            .trim()
+            .parse()
+            .map_err(|_| invalid("group code", pair[0].as_str()))?;
+        // Comments have no binary form
+        if code == 999 {
+            continue;
+        }
+        if !one_byte_codes {
+            out.extend_from_slice(&(code as i16).to_le_bytes());
+        } else if code < 255 {
+            out.push(code as u8);
+        } else {
+            out.push(255);
+            out.extend_from_slice(&(code as i16).to_le_bytes());
+        }
+
+        let value = pair[1].as_str();
+        let number = || {
+            value
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| invalid("value", value))
+        };
+        match binary_value(code) {
+            BinaryValue::Text => {
+                out.extend_from_slice(value.as_bytes());
+                out.push(0);
+            }
+            BinaryValue::Double => out.extend_from_slice(&number()?.to_le_bytes()),
+            BinaryValue::Short => out.extend_from_slice(&(number()? as i16).to_le_bytes()),
+            BinaryValue::Int => out.extend_from_slice(&(number()? as i32).to_le_bytes()),
+            BinaryValue::Long => out.extend_from_slice(&(number()? as i64).to_le_bytes()),
+            BinaryValue::Bool => out.push(u8::from(number()? != 0.0)),
+            BinaryValue::Bytes => {
+                let hex = value.trim();
+                let bytes = (0..hex.len() / 2)
+                    .map(|i| u8::from_str_radix(&hex[2 * i..2 * i + 2], 16))
+                    .collect::<Result<Vec<u8>, _>>()
+                    .map_err(|_| invalid("binary data", value))?;
+                out.push(bytes.len().min(255) as u8);
+                out.extend_from_slice(&bytes[..bytes.len().min(255)]);
+            }
+        }
+    }
+    fs::write(path, out)
+}
+
+/// Lines of an ASCII DXF file and the line ending it uses
+pub fn read_lines(path: &str) -> io::Result<(Vec<String>, &'static str)> {
+    let content = fs::read_to_string(path)?;
+    let newline = if content.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    Ok((content.lines().map(str::to_string).collect(), newline))
+}
+
+pub fn write_lines(path: &str, lines: &[String], newline: &str) -> io::Result<()> {
+    let mut output = lines.join(newline);
+    output.push_str(newline);
+    fs::write(path, output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(bytes: &[u8]) -> Vec<(i32, String)> {
+        CodePairReader::new(io::Cursor::new(bytes))
+            .collect::<io::Result<_>>()
+            .unwrap()
+    }
+
+    fn header(version: &str, code_page: &str) -> Vec<u8> {
+        format!(
+            "  0\r\nSECTION\r\n  2\r\nHEADER\r\n  9\r\n$ACADVER\r\n  1\r\n{}\r\n  9\r\n$DWGCODEPAGE\r\n  3\r\n{}\r\n  0\r\nENDSEC\r\n",
+            version, code_page
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn reads_windows_1252_text() {
+        let mut bytes = header("AC1015", "ANSI_1252");
+        bytes.extend_from_slice(b"  0\r\nLAYER\r\n  2\r\nM\xFCr \xB0\r\n");
+        let pairs = read(&bytes);
+        assert_eq!(pairs.last().unwrap(), &(2, "Mür °".to_string()));
+    }
+
+    #[test]
+    fn reads_the_header_code_page() {
+        let mut bytes = header("AC1015", "ANSI_1251");
+        bytes.extend_from_slice(b"  1\r\n\xCC\xE8\xF0\r\n");
+        assert_eq!(read(&bytes).last().unwrap().1, "Мир");
+    }
+
+    #[test]
+    fn unescapes_unicode_before_r2007() {
+        let mut bytes = header("AC1015", "ANSI_1252");
+        bytes.extend_from_slice(b"  1\r\n\\U+6C34 \\U+00E9\\U+zz\r\n");
+        assert_eq!(read(&bytes).last().unwrap().1, "水 é\\U+zz");
+    }
+
+    #[test]
+    fn reads_utf8_from_r2007() {
+        let mut bytes = header("AC1021", "ANSI_1252");
+        bytes.extend_from_slice("  1\r\nMür \\U+00E9\r\n".as_bytes());
+        // Escapes are left to the dxf crate once text is UTF-8
+        assert_eq!(read(&bytes).last().unwrap().1, "Mür \\U+00E9");
+    }
+
+    #[test]
+    fn byte_order_mark_selects_utf8() {
+        let pairs = read("\u{feff}  0\nSECTION\n  1\nMür\n".as_bytes());
+        assert_eq!(pairs, [(0, "SECTION".to_string()), (1, "Mür".to_string())]);
+    }
+
+    #[test]
+    fn rejects_invalid_group_codes() {
+        let mut reader = CodePairReader::new(io::Cursor::new(b"abc\nvalue\n"));
+        assert!(reader.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn keeps_only_raw_records() {
+        let path = std::env::temp_dir().join(format!("raw_records_{}.dxf", std::process::id()));
+        let content = [
+            "0", "SECTION", "2", "TABLES", "0", "LAYER", "2", "Walls", "70", "1", "420", "255",
+            "0", "ENDSEC", "0", "SECTION", "2", "ENTITIES", "0", "LINE", "5", "A", "0", "LINE",
+            "5", "B", "1001", "APP", "0", "HATCH", "5", "C", "0", "ENDSEC", "0", "EOF",
+        ];
+        fs::write(&path, content.join("\n")).unwrap();
+        let raw = RawRecords::read(path.to_str().unwrap(), |_| Ok(())).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let handles: Vec<&str> = raw
+            .iter()
+            .filter(|record| record.section == "ENTITIES")
+            .map(|record| record.pairs[0].1.as_str())
+            .collect();
+        assert_eq!(handles, ["B", "C"]);
+        assert_eq!(raw.entities("HATCH").count(), 1);
+        let layer = LayerGroups {
+            flags: 1,
+            true_color: Some(255),
+        };
+        assert_eq!(layer_groups(&raw).get("Walls"), Some(&layer));
+    }
+
+    #[test]
+    fn lists_entities_nothing_reads() {
+        let path = std::env::temp_dir().join(format!("raw_unread_{}.dxf", std::process::id()));
+        let content = [
+            "0",
+            "SECTION",
+            "2",
+            "BLOCKS",
+            "0",
+            "BLOCK",
+            "2",
+            "B",
+            "0",
+            "MESH",
+            "5",
+            "1A",
+            "0",
+            "ENDBLK",
+            "0",
+            "ENDSEC",
+            "0",
+            "SECTION",
+            "2",
+            "ENTITIES",
+            "0",
+            "LINE",
+            "5",
+            "2B",
+            "0",
+            "MULTILEADER",
+            "5",
+            "2c",
+            "0",
+            "HATCH",
+            "5",
+            "2D",
+            "0",
+            "ENDSEC",
+            "0",
+            "EOF",
+        ];
+        fs::write(&path, content.join("\n")).unwrap();
+        let raw = RawRecords::read(path.to_str().unwrap(), |_| Ok(())).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let unread = [
+            ("MESH".to_string(), Some("1A".to_string())),
+            ("MULTILEADER".to_string(), Some("2C".to_string())),
+        ];
+        assert_eq!(raw.unread_entities, unread);
+    }
+
+    #[test]
+    fn rounding_keeps_directions() {
+        let path = std::env::temp_dir().join(format!("raw_round_{}.dxf", std::process::id()));
+        let path = path.to_str().unwrap();
+        let content = [
+            "0", "SECTION", "2", "ENTITIES", "0", "MTEXT", "10", "1.23456", "20", "2.5", "11",
+            "0.281", "21", "0.960", "0", "SPLINE", "12", "0.6", "22", "0.8", "10", "3.14159", "0",
+            "ENDSEC", "0", "EOF",
+        ];
+        fs::write(path, content.join("\n")).unwrap();
+        round_coordinates(path, 2).unwrap();
+        let (lines, _) = read_lines(path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        let values: Vec<&str> = lines[7..23].iter().step_by(2).map(String::as_str).collect();
+        let expected = [
+            "1.23", "2.50", "0.281", "0.960", "SPLINE", "0.6", "0.8", "3.14",
+        ];
+        assert_eq!(values, expected);
+    }
+
+    #[test]
+    fn escapes_text_before_r2007() {
+        let path = std::env::temp_dir().join(format!("raw_escape_{}.dxf", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut drawing = dxf::Drawing::new();
+        drawing.header.version = AcadVersion::R2000;
+        drawing.save_file(path).unwrap();
+        insert_entities(path, None, AcadVersion::R2000, |w| {
+            w.start_entity("TEXT");
+            w.pair(1001, "APP");
+            w.pair(1000, "Café");
+        })
+        .unwrap();
+        let ascii = fs::read(path).unwrap().is_ascii();
+        let raw = RawRecords::read(path, |_| Ok(())).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert!(ascii);
+        let text = raw.iter().find(|record| record.record_type == "TEXT");
+        let xdata = text.unwrap().pairs.iter().find(|(code, _)| *code == 1000);
+        assert_eq!(xdata.unwrap().1, "Café");
+    }
+
+    #[test]
+    fn maps_code_pages() {
+        assert_eq!(code_page_encoding("ANSI_1250").name(), "windows-1250");
+        assert_eq!(code_page_encoding("ansi_932").name(), "Shift_JIS");
+        assert_eq!(code_page_encoding("DOS866").name(), "IBM866");
+        assert_eq!(code_page_encoding("unknown"), WINDOWS_1252);
+    }
+}
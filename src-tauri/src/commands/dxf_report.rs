@@ -0,0 +1,108 @@
+//! Per entity type summary of a DXF import or export, listing everything that was
+//! approximated or skipped.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ConversionReport {
+    /// Counts keyed by DXF entity type on import and by shape type on export
+    pub counts: BTreeMap<String, EntityCounts>,
+    /// Every entity that was approximated or skipped
+    pub issues: Vec<ConversionIssue>,
+}
+
+/// Each entity is counted exactly once
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct EntityCounts {
+    pub converted: usize,
+    pub approximated: usize,
+    pub skipped: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueKind {
+    Approximated,
+    Skipped,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConversionIssue {
+    pub entity_type: String,
+    /// DXF handle on import, shape id on export
+    pub handle: Option<String>,
+    pub kind: IssueKind,
+    pub reason: String,
+}
+
+impl ConversionReport {
+    pub fn converted(&mut self, entity_type: &str) {
+        self.entry(entity_type).converted += 1;
+    }
+
+    pub fn approximated(
+        &mut self,
+        entity_type: &str,
+        handle: Option<String>,
+        reason: impl Into<String>,
+    ) {
+        self.entry(entity_type).approximated += 1;
+        self.issue(entity_type, handle, IssueKind::Approximated, reason.into());
+    }
+
+    pub fn skipped(
+        &mut self,
+        entity_type: &str,
+        handle: Option<String>,
+        reason: impl Into<String>,
+    ) {
+        self.entry(entity_type).skipped += 1;
+        self.issue(entity_type, handle, IssueKind::Skipped, reason.into());
+    }
+
+    pub fn merge(&mut self, other: ConversionReport) {
+        for (entity_type, counts) in other.counts {
+            let entry = self.entry(&entity_type);
+            entry.converted += counts.converted;
+            entry.approximated += counts.approximated;
+            entry.skipped += counts.skipped;
+        }
+        self.issues.extend(other.issues);
+    }
+
+    /// "120 converted, 3 approximated, 1 skipped"
+    pub fn summary(&self) -> String {
+        let (converted, approximated, skipped) =
+            self.counts.values().fold((0, 0, 0), |(c, a, s), counts| {
+                (
+                    c + counts.converted,
+                    a + counts.approximated,
+                    s + counts.skipped,
+                )
+            });
+        format!(
+            "{} converted, {} approximated, {} skipped",
+            converted, approximated, skipped
+        )
+    }
+
+    fn entry(&mut self, entity_type: &str) -> &mut EntityCounts {
+        self.counts.entry(entity_type.to_string()).or_default()
+    }
+
+    fn issue(
+        &mut self,
+        entity_type: &str,
+        handle: Option<String>,
+        kind: IssueKind,
+        reason: String,
+    ) {
+        self.issues.push(ConversionIssue {
+            entity_type: entity_type.to_string(),
+            handle,
+            kind,
+            reason,
+        });
+    }
+}
@@ -0,0 +1,339 @@
+//! Quick look inside a DXF file before importing it: version, units, extents,
+//! layers with entity counts, blocks and layouts.
+//!
+//! ASCII files are scanned group code by group code without building a drawing;
+//! binary files fall back to loading them with the `dxf` crate.
+
+use super::dxf_blocks;
+use super::dxf_import;
+use super::dxf_raw;
+use super::shape::PointData;
+use dxf::enums::Units;
+use dxf::objects::ObjectType;
+use dxf::Drawing;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+
+#[derive(Debug, Default, Serialize)]
+pub struct DxfSummary {
+    /// "R12" ... "R2018", or the raw `$ACADVER` value when unknown
+    pub version: String,
+    /// "mm" | "cm" | "m" | "in" | "ft" as in the export options, else "unitless"
+    /// or the `$INSUNITS` code
+    pub units: String,
+    /// `$EXTMIN` and `$EXTMAX`, when set
+    pub extents: Option<[PointData; 2]>,
+    pub binary: bool,
+    /// Layers in table order, then layers used without a definition
+    pub layers: Vec<LayerSummary>,
+    /// User blocks; layout and anonymous blocks are left out
+    pub blocks: Vec<BlockSummary>,
+    /// Paper space layout names in tab order
+    pub layouts: Vec<String>,
+    /// Model and paper space entities by DXF type
+    pub entity_counts: BTreeMap<String, usize>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct LayerSummary {
+    pub name: String,
+    /// Model and paper space entities on the layer (block contents not included)
+    pub entity_count: usize,
+    pub visible: bool,
+    pub frozen: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct BlockSummary {
+    pub name: String,
+    pub entity_count: usize,
+    /// INSERTs of this block in model and paper space
+    pub insert_count: usize,
+}
+
+pub fn scan(path: &str) -> Result<DxfSummary, String> {
+    match dxf_raw::open(path).map_err(|e| e.to_string())? {
+        Some(pairs) => scan_pairs(pairs).map_err(|e| e.to_string()),
+        None => {
+            let drawing = Drawing::load_file(path).map_err(|e| e.to_string())?;
+            Ok(summarize(&drawing))
+        }
+    }
+}
+
+/// Summary being collected, with lookups by upper case name
+#[derive(Default)]
+struct Collector {
+    summary: DxfSummary,
+    layer_index: HashMap<String, usize>,
+    block_index: HashMap<String, usize>,
+    layouts: Vec<(i64, String)>,
+}
+
+impl Collector {
+    fn layer(&mut self, name: &str) -> &mut LayerSummary {
+        let layers = &mut self.summary.layers;
+        let index = *self
+            .layer_index
+            .entry(name.to_ascii_uppercase())
+            .or_insert_with(|| {
+                layers.push(LayerSummary {
+                    name: name.to_string(),
+                    visible: true,
+                    ..Default::default()
+                });
+                layers.len() - 1
+            });
+        &mut layers[index]
+    }
+
+    fn block(&mut self, name: &str) -> Option<&mut BlockSummary> {
+        if dxf_blocks::is_layout_block(name) || name.starts_with('*') {
+            return None;
+        }
+        let blocks = &mut self.summary.blocks;
+        let index = *self
+            .block_index
+            .entry(name.to_ascii_uppercase())
+            .or_insert_with(|| {
+                blocks.push(BlockSummary {
+                    name: name.to_string(),
+                    ..Default::default()
+                });
+                blocks.len() - 1
+            });
+        Some(&mut blocks[index])
+    }
+
+    /// One model or paper space entity
+    fn entity(&mut self, entity_type: &str, layer: &str, block: Option<&str>) {
+        *self
+            .summary
+            .entity_counts
+            .entry(entity_type.to_string())
+            .or_default() += 1;
+        self.layer(layer).entity_count += 1;
+        if let Some(block) = block {
+            if let Some(block) = self.block(block) {
+                block.insert_count += 1;
+            }
+        }
+    }
+
+    fn finish(mut self) -> DxfSummary {
+        self.layouts.sort_by_key(|(tab_order, _)| *tab_order);
+        self.summary.layouts = self.layouts.into_iter().map(|(_, name)| name).collect();
+        self.summary
+    }
+}
+
+/// The group codes of the record being read that the summary needs
+#[derive(Default)]
+struct Record {
+    record_type: String,
+    name: Option<String>,
+    layer: Option<String>,
+    flags: i64,
+    color: i64,
+    tab_order: i64,
+    /// LAYOUT groups after the AcDbLayout marker; earlier ones are plot settings
+    in_layout: bool,
+}
+
+fn scan_pairs(pairs: impl Iterator<Item = io::Result<(i32, String)>>) -> io::Result<DxfSummary> {
+    let mut collector = Collector::default();
+    let mut section = String::new();
+    let mut in_section_header = false;
+    let mut variable = String::new();
+    let mut extents = [None, None];
+    let mut current_block: Option<String> = None;
+    let mut record = Record::default();
+
+    for pair in pairs {
+        let (code, value) = pair?;
+        let value = value.trim();
+        if code == 0 {
+            finish_record(&mut collector, &section, &mut current_block, &record);
+            if value == "EOF" {
+                break;
+            }
+            in_section_header = value == "SECTION";
+            record = Record {
+                record_type: value.to_string(),
+                ..Default::default()
+            };
+            continue;
+        }
+        if in_section_header && code == 2 {
+            section = value.to_string();
+            in_section_header = false;
+            continue;
+        }
+
+        match section.as_str() {
+            "HEADER" => match (code, variable.as_str()) {
+                (9, _) => variable = value.to_string(),
+                (1, "$ACADVER") => collector.summary.version = version_name(value),
+                (70, "$INSUNITS") => {
+                    collector.summary.units = unit_name(value.parse().unwrap_or(0));
+                }
+                (10 | 20, "$EXTMIN" | "$EXTMAX") => {
+                    let corner = usize::from(variable == "$EXTMAX");
+                    let point = extents[corner].get_or_insert(PointData { x: 0.0, y: 0.0 });
+                    let coordinate = value.parse().unwrap_or(0.0);
+                    if code == 10 {
+                        point.x = coordinate;
+                    } else {
+                        point.y = coordinate;
+                    }
+                }
+                _ => {}
+            },
+            "OBJECTS" if record.record_type == "LAYOUT" => match code {
+                100 => record.in_layout = value == "AcDbLayout",
+                1 if record.in_layout => record.name = Some(value.to_string()),
+                71 if record.in_layout => record.tab_order = value.parse().unwrap_or(0),
+                _ => {}
+            },
+            _ => match code {
+                2 => record.name = Some(value.to_string()),
+                8 => record.layer = Some(value.to_string()),
+                62 => record.color = value.parse().unwrap_or(0),
+                70 => record.flags = value.parse().unwrap_or(0),
+                _ => {}
+            },
+        }
+    }
+
+    if let [Some(min), Some(max)] = extents {
+        // Empty drawings keep the inverted default extents
+        if min.x <= max.x && min.y <= max.y {
+            collector.summary.extents = Some([min, max]);
+        }
+    }
+    Ok(collector.finish())
+}
+
+fn finish_record(
+    collector: &mut Collector,
+    section: &str,
+    current_block: &mut Option<String>,
+    record: &Record,
+) {
+    let name = record.name.as_deref().unwrap_or_default();
+    let layer = record.layer.as_deref().unwrap_or("0");
+    match (section, record.record_type.as_str()) {
+        (_, "SECTION" | "ENDSEC") => {}
+        ("TABLES", "LAYER") if !name.is_empty() => {
+            let summary = collector.layer(name);
+            // Turned-off layers store a negative colour index; bit 1 marks frozen
+            summary.visible = record.color >= 0;
+            summary.frozen = record.flags & 1 != 0;
+        }
+        ("BLOCKS", "BLOCK") => {
+            collector.block(name);
+            *current_block = Some(name.to_string());
+        }
+        ("BLOCKS", "ENDBLK") => *current_block = None,
+        // Vertices and attributes are part of their POLYLINE or INSERT
+        ("BLOCKS" | "ENTITIES", "VERTEX" | "SEQEND" | "ATTRIB") => {}
+        ("BLOCKS", _) => {
+            if let Some(block) = current_block.clone() {
+                if let Some(block) = collector.block(&block) {
+                    block.entity_count += 1;
+                }
+            }
+        }
+        ("ENTITIES", "INSERT") => collector.entity("INSERT", layer, Some(name)),
+        ("ENTITIES", entity_type) => collector.entity(entity_type, layer, None),
+        ("OBJECTS", "LAYOUT") if !name.eq_ignore_ascii_case("Model") => {
+            collector.layouts.push((record.tab_order, name.to_string()));
+        }
+        _ => {}
+    }
+}
+
+/// Summary of a drawing loaded by the `dxf` crate (binary files). The crate
+/// does not expose the layer flags, which `dxf_raw` only reads from ASCII files,
+/// so layers are reported as not frozen.
+fn summarize(drawing: &Drawing) -> DxfSummary {
+    let mut collector = Collector::default();
+    for layer in drawing.layers() {
+        let summary = collector.layer(&layer.name);
+        summary.visible = layer.is_layer_on;
+    }
+    for block in drawing.blocks() {
+        if let Some(summary) = collector.block(&block.name) {
+            summary.entity_count = block.entities.len();
+        }
+    }
+    for entity in drawing.entities() {
+        let entity_type = dxf_import::entity_type_name(&entity.specific);
+        let block = match &entity.specific {
+            dxf::entities::EntityType::Insert(insert) => Some(insert.name.as_str()),
+            _ => None,
+        };
+        collector.entity(&entity_type, &entity.common.layer, block);
+    }
+    for object in drawing.objects() {
+        if let ObjectType::Layout(layout) = &object.specific {
+            if !layout.layout_name.eq_ignore_ascii_case("Model") {
+                collector
+                    .layouts
+                    .push((i64::from(layout.tab_order), layout.layout_name.clone()));
+            }
+        }
+    }
+
+    let header = &drawing.header;
+    collector.summary.version = format!("{:?}", header.version);
+    collector.summary.units = match header.default_drawing_units {
+        Units::Millimeters => "mm",
+        Units::Centimeters => "cm",
+        Units::Meters => "m",
+        Units::Inches => "in",
+        Units::Feet => "ft",
+        _ => "unitless",
+    }
+    .to_string();
+    let (min, max) = (
+        dxf_import::to_point_data(&header.minimum_drawing_extents),
+        dxf_import::to_point_data(&header.maximum_drawing_extents),
+    );
+    if min.x <= max.x && min.y <= max.y {
+        collector.summary.extents = Some([min, max]);
+    }
+    collector.summary.binary = true;
+    collector.finish()
+}
+
+/// `$ACADVER` code to the release names used by the export options
+fn version_name(acadver: &str) -> String {
+    match acadver {
+        "AC1009" => "R12",
+        "AC1012" => "R13",
+        "AC1014" => "R14",
+        "AC1015" => "R2000",
+        "AC1018" => "R2004",
+        "AC1021" => "R2007",
+        "AC1024" => "R2010",
+        "AC1027" => "R2013",
+        "AC1032" => "R2018",
+        other => return other.to_string(),
+    }
+    .to_string()
+}
+
+/// `$INSUNITS` code to the unit names used by the export options
+fn unit_name(insunits: i64) -> String {
+    match insunits {
+        0 => "unitless".to_string(),
+        1 => "in".to_string(),
+        2 => "ft".to_string(),
+        4 => "mm".to_string(),
+        5 => "cm".to_string(),
+        6 => "m".to_string(),
+        other => other.to_string(),
+    }
+}
@@ -0,0 +1,272 @@
+//! Background DXF import for large files. The drawing is loaded and converted on
+//! a worker thread that reports progress to the webview, sends model space shapes
+//! in chunks and can be cancelled from the import dialog.
+//!
+//! Events, all carrying the `job_id` returned when the import is started:
+//! - `dxf-import-progress`: bytes read while loading and while reading the group
+//!   codes the `dxf` crate skips, then entities converted
+//! - `dxf-import-chunk`: the next shapes in drawing order, images and hatches
+//!   first, sent as they are converted
+//! - `dxf-import-finished`: the document without its shapes (layers, blocks,
+//!   sheets, report, origin, ...), or the error or cancellation
+
+use super::dxf_import::{self, DxfImportOptions, ImportHooks, ImportStop};
+use super::dxf_raw;
+use super::shape::{DxfDocument, ShapeData};
+use dxf::Drawing;
+use serde::Serialize;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter};
+
+/// Most model space shapes sent per chunk
+const CHUNK_SIZE: usize = 2000;
+
+/// Bytes read between two loading progress events
+const PROGRESS_STEP: u64 = 1 << 20;
+
+/// Imports running in the background, with their cancellation flags
+#[derive(Default)]
+pub struct DxfImportJobs {
+    jobs: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+impl DxfImportJobs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a new import and return its ID
+    fn register(&self) -> (String, Arc<AtomicBool>) {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let id = format!("dxf_import_{:x}", nanos);
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.jobs
+            .lock()
+            .unwrap()
+            .insert(id.clone(), cancelled.clone());
+        (id, cancelled)
+    }
+
+    /// Ask a running import to stop; `false` when it is unknown or already done
+    pub fn cancel(&self, id: &str) -> bool {
+        match self.jobs.lock().unwrap().get(id) {
+            Some(cancelled) => {
+                cancelled.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn finish(&self, id: &str) {
+        self.jobs.lock().unwrap().remove(id);
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ProgressEvent<'a> {
+    job_id: &'a str,
+    /// "loading" | "reading" | "hatches" | "entities" | "layouts"
+    stage: &'static str,
+    bytes_read: u64,
+    total_bytes: u64,
+    entities_processed: usize,
+    total_entities: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ChunkEvent<'a> {
+    job_id: &'a str,
+    index: usize,
+    shapes: Vec<ShapeData>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct FinishedEvent<'a> {
+    job_id: &'a str,
+    success: bool,
+    cancelled: bool,
+    /// `DxfDocument` JSON with empty `shapes`; they arrived as chunks
+    data: Option<String>,
+    message: String,
+}
+
+/// Open the file and start importing it on a worker thread. Returns the job ID
+/// the events refer to.
+pub fn start(
+    app: AppHandle,
+    jobs: Arc<DxfImportJobs>,
+    path: String,
+    options: DxfImportOptions,
+) -> io::Result<String> {
+    let file = File::open(&path)?;
+    let total_bytes = file.metadata()?.len();
+    let (job_id, cancelled) = jobs.register();
+    let id = job_id.clone();
+    std::thread::spawn(move || {
+        let job = Job {
+            app: &app,
+            id: &id,
+            cancelled: &cancelled,
+            total_bytes,
+            chunks_sent: Cell::new(0),
+        };
+        let reader = CountingReader {
+            inner: BufReader::new(file),
+            bytes_read: 0,
+            next_report: PROGRESS_STEP,
+            job: &job,
+        };
+        let result = job.run(reader, &path, &options);
+        job.finished(result);
+        jobs.finish(&id);
+    });
+    Ok(job_id)
+}
+
+struct Job<'a> {
+    app: &'a AppHandle,
+    id: &'a str,
+    cancelled: &'a AtomicBool,
+    total_bytes: u64,
+    chunks_sent: Cell<usize>,
+}
+
+impl Job<'_> {
+    fn progress(&self, stage: &'static str, bytes_read: u64, processed: usize, total: usize) {
+        let _ = self.app.emit(
+            "dxf-import-progress",
+            ProgressEvent {
+                job_id: self.id,
+                stage,
+                bytes_read,
+                total_bytes: self.total_bytes,
+                entities_processed: processed,
+                total_entities: total,
+            },
+        );
+    }
+
+    fn chunk(&self, shapes: Vec<ShapeData>) {
+        let index = self.chunks_sent.get();
+        self.chunks_sent.set(index + 1);
+        let _ = self.app.emit(
+            "dxf-import-chunk",
+            ChunkEvent {
+                job_id: self.id,
+                index,
+                shapes,
+            },
+        );
+    }
+
+    fn finished(&self, result: Result<DxfDocument, ImportStop>) {
+        let event = match result {
+            Ok(document) => match serde_json::to_string(&document) {
+                Ok(json) => FinishedEvent {
+                    job_id: self.id,
+                    success: true,
+                    cancelled: false,
+                    data: Some(json),
+                    message: format!("DXF imported: {}", document.report.summary()),
+                },
+                Err(e) => self.failed(format!("Failed to serialize shapes: {}", e)),
+            },
+            Err(ImportStop::Cancelled) => FinishedEvent {
+                job_id: self.id,
+                success: false,
+                cancelled: true,
+                data: None,
+                message: "DXF import cancelled".to_string(),
+            },
+            Err(ImportStop::Failed(message)) => self.failed(message),
+        };
+        let _ = self.app.emit("dxf-import-finished", event);
+    }
+
+    fn failed(&self, message: String) -> FinishedEvent<'_> {
+        FinishedEvent {
+            job_id: self.id,
+            success: false,
+            cancelled: false,
+            data: None,
+            message,
+        }
+    }
+
+    /// Load the drawing and convert it like `import_dxf`; the model space shapes
+    /// are sent in chunks while it is converted
+    fn run(
+        &self,
+        mut reader: CountingReader<'_, BufReader<File>>,
+        path: &str,
+        options: &DxfImportOptions,
+    ) -> Result<DxfDocument, ImportStop> {
+        let drawing = match Drawing::load_with_encoding(&mut reader, dxf_raw::code_page(path)) {
+            Ok(d) => d,
+            // The reader fails with an I/O error once the import is cancelled
+            Err(_) if self.is_cancelled() => return Err(ImportStop::Cancelled),
+            Err(e) => return Err(ImportStop::Failed(format!("Failed to load DXF: {}", e))),
+        };
+        self.progress("loading", self.total_bytes, 0, 0);
+        dxf_import::import_drawing(path, &drawing, options, self)
+    }
+}
+
+impl ImportHooks for Job<'_> {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    fn reading(&self, bytes_read: u64) {
+        self.progress("reading", bytes_read, 0, 0);
+    }
+
+    fn converting(&self, stage: &'static str, processed: usize, total: usize) {
+        self.progress(stage, self.total_bytes, processed, total);
+    }
+
+    fn streams_shapes(&self) -> bool {
+        true
+    }
+
+    fn shapes(&self, mut shapes: Vec<ShapeData>) {
+        while shapes.len() > CHUNK_SIZE {
+            let rest = shapes.split_off(CHUNK_SIZE);
+            self.chunk(std::mem::replace(&mut shapes, rest));
+        }
+        self.chunk(shapes);
+    }
+}
+
+/// Reader that reports loading progress and stops once the import is cancelled
+struct CountingReader<'a, R> {
+    inner: R,
+    bytes_read: u64,
+    next_report: u64,
+    job: &'a Job<'a>,
+}
+
+impl<R: Read> Read for CountingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.job.is_cancelled() {
+            // Not `Interrupted`, which readers retry
+            return Err(io::Error::other("import cancelled"));
+        }
+        let n = self.inner.read(buf)?;
+        self.bytes_read += n as u64;
+        if self.bytes_read >= self.next_report {
+            self.next_report = self.bytes_read + PROGRESS_STEP;
+            self.job.progress("loading", self.bytes_read, 0, 0);
+        }
+        Ok(n)
+    }
+}
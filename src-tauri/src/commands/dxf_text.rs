@@ -0,0 +1,518 @@
+//! TEXT / MTEXT conversion, including MTEXT inline formatting codes.
+
+use super::shape::{PointData, ShapeData};
+use dxf::entities::{MText, Text};
+use dxf::enums::{AttachmentPoint, HorizontalTextJustification, VerticalTextJustification};
+use dxf::tables::Style;
+use dxf::{Drawing, Point, Vector};
+
+/// Maximum length of a single MTEXT string group; longer text continues in group 3
+const MTEXT_CHUNK_LEN: usize = 250;
+
+/// Plain text and whole-shape style extracted from an MTEXT/TEXT string.
+/// Inline overrides apply to the whole shape; the first override of each kind wins.
+#[derive(Debug, Default)]
+pub struct FormattedText {
+    pub text: String,
+    pub font_family: Option<String>,
+    pub height: Option<f64>,
+    pub width_factor: Option<f64>,
+    pub oblique_angle: Option<f64>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub strikethrough: bool,
+}
+
+/// Parse MTEXT content: `\P`, `\f`, `\H`, `\W`, `\Q`, `\L`, `\K`, `\S`, `\U+`,
+/// grouping braces and `%%` control codes. `base_height` resolves relative `\H..x` values.
+pub fn parse_mtext(raw: &str, base_height: f64) -> FormattedText {
+    let mut out = FormattedText::default();
+    let chars: Vec<char> = raw.chars().collect();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if i + 1 < chars.len() => {
+                let code = chars[i + 1];
+                i += 2;
+                match code {
+                    'P' | 'N' => out.text.push('\n'),
+                    '~' => out.text.push('\u{a0}'),
+                    '\\' | '{' | '}' => out.text.push(code),
+                    'L' => out.underline = true,
+                    'K' => out.strikethrough = true,
+                    'l' | 'k' | 'O' | 'o' => {}
+                    'f' | 'F' => {
+                        let arg = read_argument(&chars, &mut i);
+                        apply_font(&mut out, &arg);
+                    }
+                    'H' => {
+                        let arg = read_argument(&chars, &mut i);
+                        if out.height.is_none() {
+                            out.height = parse_scaled(&arg, base_height);
+                        }
+                    }
+                    'W' => {
+                        let arg = read_argument(&chars, &mut i);
+                        if out.width_factor.is_none() {
+                            out.width_factor = parse_scaled(&arg, 1.0);
+                        }
+                    }
+                    'Q' => {
+                        let arg = read_argument(&chars, &mut i);
+                        if out.oblique_angle.is_none() {
+                            out.oblique_angle = arg.trim().parse().ok();
+                        }
+                    }
+                    'S' => {
+                        // Stacked text "num^den", "num/den" or "num#den" becomes "num/den"
+                        let arg = read_argument(&chars, &mut i);
+                        let stacked: String = arg
+                            .chars()
+                            .map(|c| if c == '^' || c == '#' { '/' } else { c })
+                            .collect();
+                        out.text.push_str(stacked.trim());
+                    }
+                    'U' if chars.get(i) == Some(&'+') => {
+                        let hex: String = chars[i + 1..].iter().take(4).collect();
+                        if let Some(c) = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32)
+                        {
+                            out.text.push(c);
+                            i += 5;
+                        }
+                    }
+                    // Codes with an argument that has no plain-text meaning
+                    'A' | 'C' | 'c' | 'T' | 'p' => {
+                        read_argument(&chars, &mut i);
+                    }
+                    other => out.text.push(other),
+                }
+            }
+            '{' | '}' => i += 1,
+            '%' if chars.get(i + 1) == Some(&'%') => {
+                i += 2;
+                if let Some(c) = read_percent_code(&chars, &mut i, &mut out) {
+                    out.text.push(c);
+                }
+            }
+            c => {
+                out.text.push(c);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// Decode the `%%` control codes used in single-line TEXT values
+pub fn parse_text(raw: &str) -> FormattedText {
+    let mut out = FormattedText::default();
+    let chars: Vec<char> = raw.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == '%' && chars.get(i + 1) == Some(&'%') {
+            i += 2;
+            if let Some(c) = read_percent_code(&chars, &mut i, &mut out) {
+                out.text.push(c);
+            }
+        } else {
+            out.text.push(chars[i]);
+            i += 1;
+        }
+    }
+    out
+}
+
+/// Handle the character(s) after `%%`. Returns the character to emit, if any.
+fn read_percent_code(chars: &[char], i: &mut usize, out: &mut FormattedText) -> Option<char> {
+    let code = *chars.get(*i)?;
+    *i += 1;
+    match code.to_ascii_lowercase() {
+        'c' => Some('Ø'),
+        'd' => Some('°'),
+        'p' => Some('±'),
+        '%' => Some('%'),
+        'u' => {
+            out.underline = true;
+            None
+        }
+        'k' => {
+            out.strikethrough = true;
+            None
+        }
+        'o' => None,
+        d if d.is_ascii_digit() => {
+            // %%nnn: character by decimal code
+            let mut digits = String::from(d);
+            while digits.len() < 3 && chars.get(*i).is_some_and(|c| c.is_ascii_digit()) {
+                digits.push(chars[*i]);
+                *i += 1;
+            }
+            digits.parse::<u32>().ok().and_then(char::from_u32)
+        }
+        other => Some(other),
+    }
+}
+
+/// Read a formatting code argument up to (and consuming) the terminating `;`
+fn read_argument(chars: &[char], i: &mut usize) -> String {
+    let mut arg = String::new();
+    while *i < chars.len() && chars[*i] != ';' {
+        arg.push(chars[*i]);
+        *i += 1;
+    }
+    *i += 1;
+    arg
+}
+
+/// `\fArial|b1|i0|c0|p34;` or `\Ftxt.shx;`
+fn apply_font(out: &mut FormattedText, arg: &str) {
+    let mut parts = arg.split('|');
+    let family = parts.next().unwrap_or("").trim();
+    if out.font_family.is_none() && !family.is_empty() {
+        out.font_family = Some(font_family_from_file(family));
+    }
+    for part in parts {
+        match part {
+            "b1" => out.bold = true,
+            "i1" => out.italic = true,
+            _ => {}
+        }
+    }
+}
+
+/// Absolute value, or a multiple of `base` when suffixed with `x`
+fn parse_scaled(arg: &str, base: f64) -> Option<f64> {
+    let arg = arg.trim();
+    match arg.strip_suffix(['x', 'X']) {
+        Some(factor) => factor.parse::<f64>().ok().map(|f| f * base),
+        None => arg.parse().ok(),
+    }
+}
+
+/// Strip the extension from a font file name ("arial.ttf" -> "arial")
+fn font_family_from_file(file: &str) -> String {
+    match file.rsplit_once('.') {
+        Some((stem, ext)) if matches!(ext.to_ascii_lowercase().as_str(), "ttf" | "otf" | "shx") => {
+            stem.to_string()
+        }
+        _ => file.to_string(),
+    }
+}
+
+/// Encode plain text as MTEXT content, with whole-shape formatting as inline codes
+pub fn encode_mtext(shape: &ShapeData) -> String {
+    let mut body = String::new();
+    for c in shape.text.as_deref().unwrap_or("").chars() {
+        match c {
+            '\\' => body.push_str("\\\\"),
+            '{' => body.push_str("\\{"),
+            '}' => body.push_str("\\}"),
+            '\n' => body.push_str("\\P"),
+            '\r' => {}
+            c => body.push(c),
+        }
+    }
+
+    let mut codes = String::new();
+    let bold = shape.bold.unwrap_or(false);
+    let italic = shape.italic.unwrap_or(false);
+    if bold || italic {
+        let family = shape.font_family.as_deref().unwrap_or("Arial");
+        codes.push_str(&format!(
+            "\\f{}|b{}|i{};",
+            family,
+            u8::from(bold),
+            u8::from(italic)
+        ));
+    }
+    if shape.underline.unwrap_or(false) {
+        codes.push_str("\\L");
+    }
+    if shape.strikethrough.unwrap_or(false) {
+        codes.push_str("\\K");
+    }
+
+    if codes.is_empty() {
+        body
+    } else {
+        format!("{{{}{}}}", codes, body)
+    }
+}
+
+/// Convert a single-line TEXT entity into a text shape
+pub fn text_to_shape(text: &Text, drawing: &Drawing) -> Option<ShapeData> {
+    let formatted = parse_text(&text.value);
+    if formatted.text.trim().is_empty() {
+        return None;
+    }
+
+    use HorizontalTextJustification as H;
+    use VerticalTextJustification as V;
+    // Any justification other than left/baseline anchors at the second alignment point
+    let anchored_left = matches!(
+        text.horizontal_text_justification,
+        H::Left | H::Aligned | H::Fit
+    ) && text.vertical_text_justification == V::Baseline;
+    let position = if anchored_left {
+        &text.location
+    } else {
+        &text.second_alignment_point
+    };
+    let alignment = match text.horizontal_text_justification {
+        H::Center | H::Middle => "center",
+        H::Right => "right",
+        _ => "left",
+    };
+    let vertical_alignment = match (
+        text.horizontal_text_justification,
+        text.vertical_text_justification,
+    ) {
+        (H::Middle, _) | (_, V::Middle) => "middle",
+        (_, V::Top) => "top",
+        _ => "bottom",
+    };
+
+    let mut shape = text_shape(formatted, text.text_height, &text.text_style_name, drawing);
+    shape.position = Some(PointData {
+        x: position.x,
+        y: position.y,
+    });
+    shape.rotation = Some(text.rotation.to_radians());
+    shape.alignment = Some(alignment.to_string());
+    shape.vertical_alignment = Some(vertical_alignment.to_string());
+    if text.relative_x_scale_factor != 1.0 {
+        shape.width_factor = Some(text.relative_x_scale_factor);
+    }
+    if text.oblique_angle != 0.0 {
+        shape.oblique_angle = Some(text.oblique_angle);
+    }
+    Some(shape)
+}
+
+/// Convert an MTEXT entity into a text shape
+pub fn mtext_to_shape(mtext: &MText, drawing: &Drawing) -> Option<ShapeData> {
+    let raw = format!("{}{}", mtext.extended_text.concat(), mtext.text);
+    let formatted = parse_mtext(&raw, mtext.initial_text_height);
+    if formatted.text.trim().is_empty() {
+        return None;
+    }
+
+    use AttachmentPoint as A;
+    let (vertical_alignment, alignment) = match mtext.attachment_point {
+        A::TopLeft => ("top", "left"),
+        A::TopCenter => ("top", "center"),
+        A::TopRight => ("top", "right"),
+        A::MiddleLeft => ("middle", "left"),
+        A::MiddleCenter => ("middle", "center"),
+        A::MiddleRight => ("middle", "right"),
+        A::BottomLeft => ("bottom", "left"),
+        A::BottomCenter => ("bottom", "center"),
+        A::BottomRight => ("bottom", "right"),
+    };
+    // An explicit X axis direction takes precedence over the rotation angle
+    let axis = &mtext.x_axis_direction;
+    let rotation = if axis.x != 0.0 || axis.y != 0.0 {
+        axis.y.atan2(axis.x)
+    } else {
+        mtext.rotation_angle.to_radians()
+    };
+
+    let height = formatted.height.unwrap_or(mtext.initial_text_height);
+    let mut shape = text_shape(formatted, height, &mtext.text_style_name, drawing);
+    shape.position = Some(PointData {
+        x: mtext.insertion_point.x,
+        y: mtext.insertion_point.y,
+    });
+    shape.rotation = Some(rotation);
+    shape.alignment = Some(alignment.to_string());
+    shape.vertical_alignment = Some(vertical_alignment.to_string());
+    if mtext.reference_rectangle_width > 0.0 {
+        shape.fixed_width = Some(mtext.reference_rectangle_width);
+    }
+    Some(shape)
+}
+
+fn text_shape(
+    formatted: FormattedText,
+    height: f64,
+    style_name: &str,
+    drawing: &Drawing,
+) -> ShapeData {
+    let style = drawing
+        .styles()
+        .find(|s| s.name.eq_ignore_ascii_case(style_name));
+    let font_family = formatted.font_family.or_else(|| {
+        style
+            .map(|s| font_family_from_file(&s.primary_font_file_name))
+            .filter(|f| !f.is_empty())
+    });
+    let width_factor = formatted.width_factor.or_else(|| {
+        style
+            .map(|s| s.width_factor)
+            .filter(|w| *w != 1.0 && *w > 0.0)
+    });
+    let oblique_angle = formatted
+        .oblique_angle
+        .or_else(|| style.map(|s| s.oblique_angle).filter(|a| *a != 0.0));
+
+    ShapeData {
+        shape_type: "text".to_string(),
+        text: Some(formatted.text),
+        font_size: Some(height),
+        font_family,
+        bold: Some(formatted.bold),
+        italic: Some(formatted.italic),
+        underline: Some(formatted.underline),
+        strikethrough: Some(formatted.strikethrough),
+        width_factor,
+        oblique_angle,
+        text_style_name: (!style_name.is_empty()).then(|| style_name.to_string()),
+        ..Default::default()
+    }
+}
+
+/// Convert a text shape into an MTEXT entity, registering its STYLE table entry
+pub fn shape_to_mtext(shape: &ShapeData, drawing: &mut Drawing) -> Option<MText> {
+    let position = shape.position?;
+    let content = encode_mtext(shape);
+    if content.is_empty() {
+        return None;
+    }
+    let style_name = ensure_style(drawing, shape);
+
+    use AttachmentPoint as A;
+    let attachment_point = match (
+        shape.vertical_alignment.as_deref().unwrap_or("top"),
+        shape.alignment.as_deref().unwrap_or("left"),
+    ) {
+        ("middle", "center") => A::MiddleCenter,
+        ("middle", "right") => A::MiddleRight,
+        ("middle", _) => A::MiddleLeft,
+        ("bottom", "center") => A::BottomCenter,
+        ("bottom", "right") => A::BottomRight,
+        ("bottom", _) => A::BottomLeft,
+        (_, "center") => A::TopCenter,
+        (_, "right") => A::TopRight,
+        _ => A::TopLeft,
+    };
+
+    // Long content is split into 250 character chunks; the last one goes in group 1
+    let chars: Vec<char> = content.chars().collect();
+    let mut chunks: Vec<String> = chars
+        .chunks(MTEXT_CHUNK_LEN)
+        .map(|c| c.iter().collect())
+        .collect();
+    let text = chunks.pop().unwrap_or_default();
+    // Readers take the X axis over the rotation angle, so both carry the rotation
+    let rotation = shape.rotation.unwrap_or(0.0);
+
+    Some(MText {
+        insertion_point: Point::new(position.x, position.y, 0.0),
+        initial_text_height: shape.font_size.unwrap_or(2.5),
+        reference_rectangle_width: shape.fixed_width.unwrap_or(0.0),
+        attachment_point,
+        x_axis_direction: Vector::new(rotation.cos(), rotation.sin(), 0.0),
+        rotation_angle: rotation.to_degrees(),
+        text_style_name: style_name,
+        extended_text: chunks,
+        text,
+        ..Default::default()
+    })
+}
+
+/// Make sure a STYLE table entry exists for the shape's text style and return its name.
+/// The project text style name is used when present, otherwise the font family.
+fn ensure_style(drawing: &mut Drawing, shape: &ShapeData) -> String {
+    let font_family = shape.font_family.as_deref().unwrap_or("Arial");
+    let name = shape
+        .text_style_name
+        .clone()
+        .unwrap_or_else(|| font_family.to_string());
+    let name = sanitize_table_name(&name);
+
+    if !drawing.styles().any(|s| s.name.eq_ignore_ascii_case(&name)) {
+        drawing.add_style(Style {
+            name: name.clone(),
+            primary_font_file_name: format!("{}.ttf", font_family.to_lowercase()),
+            width_factor: shape.width_factor.unwrap_or(1.0),
+            oblique_angle: shape.oblique_angle.unwrap_or(0.0),
+            ..Default::default()
+        });
+    }
+    name
+}
+
+/// Replace characters that are not allowed in DXF symbol table names
+pub fn sanitize_table_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | '/' | '\\' | '"' | ':' | ';' | '?' | '*' | '|' | ',' | '=' | '`' => '_',
+            c => c,
+        })
+        .collect();
+    let cleaned = cleaned.trim().to_string();
+    if cleaned.is_empty() {
+        "Standard".to_string()
+    } else {
+        cleaned
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(content: &str) -> ShapeData {
+        ShapeData {
+            shape_type: "text".to_string(),
+            text: Some(content.to_string()),
+            position: Some(PointData { x: 1.0, y: 2.0 }),
+            font_size: Some(2.5),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parse_mtext_reads_formatting_codes() {
+        let parsed = parse_mtext("{\\fArial|b1|i0;\\H2x;\\LLine 1\\PLine 2 \\U+00B1%%d}", 2.5);
+        assert_eq!(parsed.text, "Line 1\nLine 2 ±°");
+        assert_eq!(parsed.font_family.as_deref(), Some("Arial"));
+        assert_eq!(parsed.height, Some(5.0));
+        assert!(parsed.bold && !parsed.italic && parsed.underline);
+    }
+
+    #[test]
+    fn parse_mtext_joins_stacked_text() {
+        assert_eq!(parse_mtext("1\\S1^2;\"", 1.0).text, "11/2\"");
+    }
+
+    #[test]
+    fn encode_mtext_escapes_and_round_trips() {
+        let shape = ShapeData {
+            bold: Some(true),
+            underline: Some(true),
+            font_family: Some("Arial".to_string()),
+            ..text("a\\b {c}\nd")
+        };
+        let encoded = encode_mtext(&shape);
+        assert_eq!(encoded, "{\\fArial|b1|i0;\\La\\\\b \\{c\\}\\Pd}");
+        let parsed = parse_mtext(&encoded, 2.5);
+        assert_eq!(parsed.text, "a\\b {c}\nd");
+        assert!(parsed.bold && parsed.underline);
+    }
+
+    #[test]
+    fn mtext_keeps_rotation() {
+        let mut drawing = Drawing::new();
+        let shape = ShapeData {
+            rotation: Some(0.5),
+            ..text("rotated")
+        };
+        let mtext = shape_to_mtext(&shape, &mut drawing).unwrap();
+        let read = mtext_to_shape(&mtext, &drawing).unwrap();
+        assert!((read.rotation.unwrap() - 0.5).abs() < 1e-9);
+    }
+}
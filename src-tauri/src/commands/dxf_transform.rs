@@ -0,0 +1,542 @@
+//! 2D affine transforms for placing block contents (INSERT) in world coordinates.
+
+use super::shape::{PointData, ShapeData};
+use std::f64::consts::{FRAC_PI_2, PI};
+
+/// Relative tolerance for a transformed axis to count as horizontal or vertical
+const AXIS_TOLERANCE: f64 = 1e-9;
+
+/// Affine transform `x' = a*x + c*y + e`, `y' = b*x + d*y + f`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    a: f64,
+    b: f64,
+    c: f64,
+    d: f64,
+    e: f64,
+    f: f64,
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Self::translate(0.0, 0.0)
+    }
+
+    pub fn translate(x: f64, y: f64) -> Self {
+        Self {
+            a: 1.0,
+            b: 0.0,
+            c: 0.0,
+            d: 1.0,
+            e: x,
+            f: y,
+        }
+    }
+
+    /// Transform taking the unit X and Y vectors to `x_axis` and `y_axis` and
+    /// the origin to `origin`
+    pub fn from_axes(x_axis: PointData, y_axis: PointData, origin: PointData) -> Self {
+        Self {
+            a: x_axis.x,
+            b: x_axis.y,
+            c: y_axis.x,
+            d: y_axis.y,
+            e: origin.x,
+            f: origin.y,
+        }
+    }
+
+    /// Counter-clockwise rotation in radians
+    pub fn rotate(angle: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self {
+            a: cos,
+            b: sin,
+            c: -sin,
+            d: cos,
+            e: 0.0,
+            f: 0.0,
+        }
+    }
+
+    pub fn scale(x: f64, y: f64) -> Self {
+        Self {
+            a: x,
+            b: 0.0,
+            c: 0.0,
+            d: y,
+            e: 0.0,
+            f: 0.0,
+        }
+    }
+
+    /// Composition that applies `inner` first, then `self`
+    pub fn then_after(&self, inner: &Transform) -> Transform {
+        Transform {
+            a: self.a * inner.a + self.c * inner.b,
+            b: self.b * inner.a + self.d * inner.b,
+            c: self.a * inner.c + self.c * inner.d,
+            d: self.b * inner.c + self.d * inner.d,
+            e: self.a * inner.e + self.c * inner.f + self.e,
+            f: self.b * inner.e + self.d * inner.f + self.f,
+        }
+    }
+
+    pub fn apply(&self, p: PointData) -> PointData {
+        PointData {
+            x: self.a * p.x + self.c * p.y + self.e,
+            y: self.b * p.x + self.d * p.y + self.f,
+        }
+    }
+
+    /// Apply without translation
+    pub fn apply_vector(&self, v: PointData) -> PointData {
+        PointData {
+            x: self.a * v.x + self.c * v.y,
+            y: self.b * v.x + self.d * v.y,
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Self::identity()
+    }
+
+    /// A negative determinant reverses orientation (mirror)
+    pub fn is_mirrored(&self) -> bool {
+        self.a * self.d - self.b * self.c < 0.0
+    }
+
+    /// Rotation of the transformed X axis
+    pub fn rotation(&self) -> f64 {
+        self.b.atan2(self.a)
+    }
+
+    /// Length of the transformed X and Y unit vectors
+    pub fn scale_factors(&self) -> (f64, f64) {
+        (self.a.hypot(self.b), self.c.hypot(self.d))
+    }
+}
+
+/// Transform a shape in place. Circles and arcs become ellipses under non-uniform
+/// scaling, mirrored arcs and bulges reverse direction, and text keeps reading upright.
+pub fn transform_shape(shape: &mut ShapeData, t: &Transform) {
+    for point in [&mut shape.start, &mut shape.end, &mut shape.position]
+        .into_iter()
+        .flatten()
+    {
+        *point = t.apply(*point);
+    }
+    for point in shape
+        .points
+        .iter_mut()
+        .chain(shape.inner_loops.iter_mut().flatten())
+        .chain(shape.clip_points.iter_mut())
+        .flatten()
+    {
+        *point = t.apply(*point);
+    }
+
+    match shape.shape_type.as_str() {
+        "circle" | "arc" | "ellipse" => transform_conic(shape, t),
+        "polyline" if t.is_mirrored() => {
+            if let Some(bulges) = &mut shape.bulge {
+                bulges.iter_mut().for_each(|b| *b = -*b);
+            }
+        }
+        "hatch" => {
+            if t.is_mirrored() {
+                if let Some(bulges) = &mut shape.bulge {
+                    bulges.iter_mut().for_each(|b| *b = -*b);
+                }
+            }
+            let scale = mean_scale(t);
+            for pattern_scale in [&mut shape.pattern_scale, &mut shape.bg_pattern_scale] {
+                *pattern_scale = pattern_scale.map(|s| s * scale);
+            }
+            for angle in [&mut shape.pattern_angle, &mut shape.bg_pattern_angle] {
+                *angle = angle.map(|a| a + t.rotation().to_degrees());
+            }
+        }
+        "text" => {
+            let rotation = shape.rotation.unwrap_or(0.0);
+            let (sin, cos) = rotation.sin_cos();
+            // Mirrored text is kept readable by following the transformed up direction
+            shape.rotation = Some(if t.is_mirrored() {
+                let up = t.apply_vector(PointData { x: -sin, y: cos });
+                up.y.atan2(up.x) - FRAC_PI_2
+            } else {
+                let dir = t.apply_vector(PointData { x: cos, y: sin });
+                dir.y.atan2(dir.x)
+            });
+            let (scale_x, scale_y) = t.scale_factors();
+            shape.font_size = shape.font_size.map(|h| h * scale_y);
+            shape.fixed_width = shape.fixed_width.map(|w| w * scale_x);
+        }
+        "dimension" => {
+            // Points are already placed. The offset follows the transformed
+            // offset direction; linear dimensions stay linear while they still
+            // measure along an axis and become aligned otherwise. Style sizes
+            // follow the average scale.
+            let scale = mean_scale(t);
+            let offset = shape.dimension_line_offset.unwrap_or(0.0);
+            let direction = match shape.dimension_type.as_deref() {
+                Some("linear") => shape.linear_direction.as_deref(),
+                _ => None,
+            };
+            let axes = match direction {
+                Some("horizontal") => {
+                    Some((PointData { x: 1.0, y: 0.0 }, PointData { x: 0.0, y: 1.0 }))
+                }
+                Some("vertical") => {
+                    Some((PointData { x: 0.0, y: 1.0 }, PointData { x: 1.0, y: 0.0 }))
+                }
+                _ => None,
+            };
+            shape.dimension_line_offset = Some(match axes {
+                Some((measured, normal)) => {
+                    let measured = t.apply_vector(measured);
+                    let normal = t.apply_vector(normal);
+                    if measured.y.abs() <= AXIS_TOLERANCE * measured.x.abs() {
+                        shape.linear_direction = Some("horizontal".to_string());
+                        offset * normal.y
+                    } else if measured.x.abs() <= AXIS_TOLERANCE * measured.y.abs() {
+                        shape.linear_direction = Some("vertical".to_string());
+                        offset * normal.x
+                    } else {
+                        shape.dimension_type = Some("aligned".to_string());
+                        shape.linear_direction = None;
+                        left_offset(shape.points.as_deref(), normal) * offset
+                    }
+                }
+                // The offset of aligned dimensions is to the left of the
+                // measured points, which a mirror swaps; angular offsets are radii
+                None if shape.dimension_type.as_deref() == Some("aligned") && t.is_mirrored() => {
+                    -offset * scale
+                }
+                None => offset * scale,
+            });
+            shape.text_offset = shape.text_offset.map(|o| t.apply_vector(o));
+            if let Some(style) = &mut shape.dimension_style {
+                style.arrow_size *= scale;
+                style.extension_line_gap *= scale;
+                style.extension_line_overshoot *= scale;
+                style.text_height *= scale;
+            }
+        }
+        "image" => {
+            // Images stay unmirrored; the top-left corner moves to where the
+            // transformed top edge starts
+            let (scale_x, scale_y) = t.scale_factors();
+            let rotation = shape.rotation.unwrap_or(0.0);
+            let width = shape.width.unwrap_or(0.0);
+            if t.is_mirrored() {
+                if let Some(position) = &mut shape.position {
+                    let (sin, cos) = rotation.sin_cos();
+                    let edge = t.apply_vector(PointData {
+                        x: width * cos,
+                        y: width * sin,
+                    });
+                    position.x += edge.x;
+                    position.y += edge.y;
+                }
+            }
+            let x_axis = t.apply_vector(PointData {
+                x: rotation.cos(),
+                y: rotation.sin(),
+            });
+            let mirror = if t.is_mirrored() { PI } else { 0.0 };
+            shape.rotation = Some(x_axis.y.atan2(x_axis.x) + mirror);
+            shape.width = Some(width * scale_x);
+            shape.height = shape.height.map(|h| h * scale_y);
+        }
+        "block" => {
+            // The block's X axis keeps its direction; a mirror flips its Y axis
+            let (scale_x, scale_y) = t.scale_factors();
+            let rotation = shape.rotation.unwrap_or(0.0);
+            let x_axis = t.apply_vector(PointData {
+                x: rotation.cos(),
+                y: rotation.sin(),
+            });
+            shape.rotation = Some(x_axis.y.atan2(x_axis.x));
+            shape.scale_x = Some(shape.scale_x.unwrap_or(1.0) * scale_x);
+            let mirror = if t.is_mirrored() { -1.0 } else { 1.0 };
+            shape.scale_y = Some(shape.scale_y.unwrap_or(1.0) * scale_y * mirror);
+        }
+        _ => {}
+    }
+}
+
+/// Component of `v` to the left of the first two points, the side positive
+/// offsets of aligned dimensions are on
+fn left_offset(points: Option<&[PointData]>, v: PointData) -> f64 {
+    let Some([p1, p2, ..]) = points else {
+        return 0.0;
+    };
+    let (dx, dy) = (p2.x - p1.x, p2.y - p1.y);
+    let length = dx.hypot(dy);
+    if length == 0.0 {
+        return 0.0;
+    }
+    (dx * v.y - dy * v.x) / length
+}
+
+/// Scale of lengths that have no direction, such as offsets and pattern spacing
+fn mean_scale(t: &Transform) -> f64 {
+    let (scale_x, scale_y) = t.scale_factors();
+    (scale_x * scale_y).sqrt()
+}
+
+/// Circles, arcs and ellipses are transformed through their conjugate diameters
+/// and re-expressed along the principal axes of the resulting ellipse
+fn transform_conic(shape: &mut ShapeData, t: &Transform) {
+    let Some(center) = shape.center else {
+        return;
+    };
+    let (u, v, params) = match shape.shape_type.as_str() {
+        "ellipse" => {
+            let rx = shape.radius_x.unwrap_or(0.0);
+            let ry = shape.radius_y.unwrap_or(rx);
+            let (sin, cos) = shape.rotation.unwrap_or(0.0).sin_cos();
+            (
+                PointData {
+                    x: rx * cos,
+                    y: rx * sin,
+                },
+                PointData {
+                    x: -ry * sin,
+                    y: ry * cos,
+                },
+                shape.start_angle.zip(shape.end_angle),
+            )
+        }
+        shape_type => {
+            let r = shape.radius.unwrap_or(0.0);
+            let params = if shape_type == "arc" {
+                shape.start_angle.zip(shape.end_angle)
+            } else {
+                None
+            };
+            (
+                PointData { x: r, y: 0.0 },
+                PointData { x: 0.0, y: r },
+                params,
+            )
+        }
+    };
+
+    let (u, v) = (t.apply_vector(u), t.apply_vector(v));
+    let dot = |p: PointData, q: PointData| p.x * q.x + p.y * q.y;
+    // Parameter of the major vertex: tan(2*t0) = 2 u.v / (|u|^2 - |v|^2)
+    let t0 = 0.5 * (2.0 * dot(u, v)).atan2(dot(u, u) - dot(v, v));
+    let (sin0, cos0) = t0.sin_cos();
+    let a = PointData {
+        x: u.x * cos0 + v.x * sin0,
+        y: u.y * cos0 + v.y * sin0,
+    };
+    let b = PointData {
+        x: -u.x * sin0 + v.x * cos0,
+        y: -u.y * sin0 + v.y * cos0,
+    };
+    let mut params = params.map(|(start, end)| (start - t0, end - t0));
+    if a.x * b.y - a.y * b.x < 0.0 {
+        // The minor axis lies clockwise of the major axis: run the parameter backwards
+        params = params.map(|(start, end)| (-end, -start));
+    }
+
+    let (radius_x, radius_y) = (a.x.hypot(a.y), b.x.hypot(b.y));
+    let rotation = a.y.atan2(a.x);
+    let center = t.apply(center);
+    let circular = (radius_x - radius_y).abs() <= 1e-9 * radius_x.max(radius_y);
+
+    shape.center = Some(center);
+    shape.radius = None;
+    shape.radius_x = None;
+    shape.radius_y = None;
+    shape.rotation = None;
+    shape.start_angle = None;
+    shape.end_angle = None;
+    match (circular, params) {
+        (true, Some((start, end))) => {
+            shape.shape_type = "arc".to_string();
+            shape.radius = Some(radius_x);
+            shape.start_angle = Some(rotation + start);
+            shape.end_angle = Some(rotation + end);
+        }
+        (true, None) => {
+            shape.shape_type = "circle".to_string();
+            shape.radius = Some(radius_x);
+        }
+        (false, params) => {
+            shape.shape_type = "ellipse".to_string();
+            shape.radius_x = Some(radius_x);
+            shape.radius_y = Some(radius_y);
+            shape.rotation = Some(rotation);
+            shape.start_angle = params.map(|(start, _)| start);
+            shape.end_angle = params.map(|(_, end)| end);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::dxf_ocs;
+    use super::*;
+    use dxf::Vector;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    fn arc(start: f64, end: f64) -> ShapeData {
+        ShapeData {
+            shape_type: "arc".to_string(),
+            center: Some(PointData { x: 1.0, y: 0.0 }),
+            radius: Some(1.0),
+            start_angle: Some(start),
+            end_angle: Some(end),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn then_after_applies_inner_first() {
+        let t = Transform::translate(10.0, 0.0).then_after(&Transform::rotate(FRAC_PI_2));
+        let p = t.apply(PointData { x: 1.0, y: 0.0 });
+        assert!(close(p.x, 10.0) && close(p.y, 1.0));
+        assert!(close(t.rotation(), FRAC_PI_2));
+        assert!(!t.is_mirrored());
+    }
+
+    #[test]
+    fn mirrored_arc_runs_the_other_way() {
+        let mut shape = arc(0.0, FRAC_PI_2);
+        transform_shape(&mut shape, &Transform::scale(-1.0, 1.0));
+        assert_eq!(shape.shape_type, "arc");
+        let center = shape.center.unwrap();
+        assert!(close(center.x, -1.0) && close(center.y, 0.0));
+        assert!(close(shape.start_angle.unwrap(), FRAC_PI_2));
+        assert!(close(shape.end_angle.unwrap(), PI));
+    }
+
+    #[test]
+    fn non_uniform_scale_turns_circles_into_ellipses() {
+        let mut shape = ShapeData {
+            shape_type: "circle".to_string(),
+            center: Some(PointData { x: 0.0, y: 0.0 }),
+            radius: Some(2.0),
+            ..Default::default()
+        };
+        transform_shape(&mut shape, &Transform::scale(1.0, 3.0));
+        assert_eq!(shape.shape_type, "ellipse");
+        assert!(close(shape.radius_x.unwrap(), 6.0));
+        assert!(close(shape.radius_y.unwrap(), 2.0));
+        assert!(close(shape.rotation.unwrap().abs(), FRAC_PI_2));
+    }
+
+    #[test]
+    fn mirrored_polyline_bulges_change_sign() {
+        let mut shape = ShapeData {
+            shape_type: "polyline".to_string(),
+            points: Some(vec![
+                PointData { x: 0.0, y: 0.0 },
+                PointData { x: 1.0, y: 0.0 },
+            ]),
+            bulge: Some(vec![0.5, 0.0]),
+            ..Default::default()
+        };
+        transform_shape(&mut shape, &Transform::scale(1.0, -1.0));
+        assert_eq!(shape.bulge, Some(vec![-0.5, 0.0]));
+    }
+
+    #[test]
+    fn mirrored_text_stays_upright() {
+        let mut shape = ShapeData {
+            shape_type: "text".to_string(),
+            position: Some(PointData { x: 2.0, y: 0.0 }),
+            rotation: Some(0.0),
+            font_size: Some(2.5),
+            ..Default::default()
+        };
+        transform_shape(&mut shape, &Transform::scale(-2.0, 2.0));
+        assert!(close(shape.rotation.unwrap(), 0.0));
+        assert!(close(shape.font_size.unwrap(), 5.0));
+        assert!(close(shape.position.unwrap().x, -4.0));
+    }
+
+    /// Linear part of a block reference: rotation after scale
+    fn block_matrix(shape: &ShapeData) -> [f64; 4] {
+        let (sin, cos) = shape.rotation.unwrap().sin_cos();
+        let (x, y) = (shape.scale_x.unwrap(), shape.scale_y.unwrap());
+        [cos * x, sin * x, -sin * y, cos * y]
+    }
+
+    #[test]
+    fn mirrored_block_keeps_its_orientation() {
+        let ocs = dxf_ocs::to_world(&Vector::new(0.0, 0.0, -1.0), 0.0).unwrap();
+        let p = ocs.apply(PointData { x: 1.0, y: 2.0 });
+        assert!(close(p.x, -1.0) && close(p.y, 2.0));
+
+        let mut shape = ShapeData {
+            shape_type: "block".to_string(),
+            position: Some(PointData { x: 0.0, y: 0.0 }),
+            rotation: Some(FRAC_PI_2),
+            scale_x: Some(1.0),
+            scale_y: Some(1.0),
+            ..Default::default()
+        };
+        transform_shape(&mut shape, &ocs);
+        let expected = [0.0, 1.0, 1.0, 0.0];
+        let matrix = block_matrix(&shape);
+        assert!(matrix.iter().zip(expected).all(|(a, b)| close(*a, b)));
+    }
+
+    fn horizontal_dimension() -> ShapeData {
+        ShapeData {
+            shape_type: "dimension".to_string(),
+            dimension_type: Some("linear".to_string()),
+            linear_direction: Some("horizontal".to_string()),
+            points: Some(vec![
+                PointData { x: 0.0, y: 0.0 },
+                PointData { x: 10.0, y: 0.0 },
+            ]),
+            dimension_line_offset: Some(5.0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn mirrored_linear_dimension_keeps_its_side() {
+        let mut shape = horizontal_dimension();
+        transform_shape(&mut shape, &Transform::scale(-1.0, 1.0));
+        assert_eq!(shape.linear_direction.as_deref(), Some("horizontal"));
+        assert!(close(shape.dimension_line_offset.unwrap(), 5.0));
+
+        let mut shape = horizontal_dimension();
+        transform_shape(&mut shape, &Transform::scale(1.0, -2.0));
+        assert!(close(shape.dimension_line_offset.unwrap(), -10.0));
+    }
+
+    #[test]
+    fn rotated_linear_dimension_changes_direction() {
+        let mut shape = horizontal_dimension();
+        transform_shape(&mut shape, &Transform::rotate(FRAC_PI_2));
+        assert_eq!(shape.linear_direction.as_deref(), Some("vertical"));
+        // The dimension line above the points is now to their left
+        assert!(close(shape.dimension_line_offset.unwrap(), -5.0));
+
+        let mut shape = horizontal_dimension();
+        transform_shape(&mut shape, &Transform::rotate(PI / 4.0));
+        assert_eq!(shape.dimension_type.as_deref(), Some("aligned"));
+        assert_eq!(shape.linear_direction, None);
+        assert!(close(shape.dimension_line_offset.unwrap(), 5.0));
+    }
+
+    #[test]
+    fn mirrored_aligned_dimension_changes_side() {
+        let mut shape = horizontal_dimension();
+        shape.dimension_type = Some("aligned".to_string());
+        shape.linear_direction = None;
+        transform_shape(&mut shape, &Transform::scale(-2.0, 2.0));
+        assert!(close(shape.dimension_line_offset.unwrap(), -10.0));
+    }
+}
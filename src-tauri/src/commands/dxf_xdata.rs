@@ -0,0 +1,437 @@
+//! Application data of DXF entities: XDATA and extension dictionaries. The project
+//! does not interpret them; they are kept on the imported shapes as group codes and
+//! written back on export so data of other tools survives a round trip.
+//!
+//! Both are read from and written to the ASCII group codes, like hatches.
+
+use super::dxf_raw::{self, RawRecord, RawRecords};
+use super::shape::ShapeData;
+use dxf::enums::AcadVersion;
+use dxf::tables::AppId;
+use dxf::Drawing;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io;
+
+/// Nesting depth of extension dictionaries that is followed
+const MAX_DICTIONARY_DEPTH: usize = 8;
+
+/// Data of the source entity the project does not interpret
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DxfEntityData {
+    /// Handle in the source file. Exported entities get new handles, so this is
+    /// for matching entities against the source file only.
+    pub handle: Option<String>,
+    /// XDATA groups (1001 application name, then its 1000-1071 groups)
+    pub xdata: Vec<(i32, String)>,
+    /// Entries of the extension dictionary (`ACAD_XDICTIONARY`)
+    pub dictionary: Vec<DictionaryEntryData>,
+}
+
+/// Object stored under a name in an extension dictionary, usually an XRECORD
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DictionaryEntryData {
+    pub name: String,
+    /// "XRECORD", "DICTIONARY", ...
+    pub object_type: String,
+    /// Group codes from the first subclass marker on; handle and owner are
+    /// assigned on export
+    pub pairs: Vec<(i32, String)>,
+    /// Entries of a nested dictionary
+    pub entries: Vec<DictionaryEntryData>,
+}
+
+/// The XDATA and extension dictionaries of all entities in model space, paper
+/// space and blocks, keyed by upper case handle. Entities without either are
+/// left out.
+pub fn read_entity_data(raw: &RawRecords) -> HashMap<String, DxfEntityData> {
+    let objects: HashMap<String, &RawRecord> = raw
+        .iter()
+        .filter(|record| record.section == "OBJECTS")
+        .filter_map(|record| Some((find(&record.pairs, 5)?, record)))
+        .collect();
+
+    let mut data = HashMap::new();
+    let entities = raw
+        .iter()
+        .filter(|record| matches!(record.section.as_str(), "ENTITIES" | "BLOCKS"))
+        .filter_map(|record| entity_groups(&record.pairs));
+    for EntityGroups {
+        handle,
+        xdata,
+        dictionary,
+    } in entities
+    {
+        let dictionary = dictionary
+            .map(|dictionary| dictionary_entries(&dictionary, &objects, 0))
+            .unwrap_or_default();
+        if xdata.is_empty() && dictionary.is_empty() {
+            continue;
+        }
+        data.insert(
+            handle.clone(),
+            DxfEntityData {
+                handle: Some(handle),
+                xdata,
+                dictionary,
+            },
+        );
+    }
+    data
+}
+
+/// The groups of an entity record kept with its shape
+struct EntityGroups {
+    handle: String,
+    xdata: Vec<(i32, String)>,
+    /// Extension dictionary handle
+    dictionary: Option<String>,
+}
+
+fn entity_groups(pairs: &[(i32, String)]) -> Option<EntityGroups> {
+    let handle = find(pairs, 5)?;
+    let dictionary = pairs
+        .iter()
+        .position(|(code, value)| *code == 102 && value.trim() == "{ACAD_XDICTIONARY")
+        .and_then(|start| find(&pairs[start..], 360));
+    let xdata = match pairs.iter().position(|(code, _)| *code == 1001) {
+        Some(start) => pairs[start..].to_vec(),
+        None => Vec::new(),
+    };
+    Some(EntityGroups {
+        handle,
+        xdata,
+        dictionary,
+    })
+}
+
+fn find(pairs: &[(i32, String)], code: i32) -> Option<String> {
+    pairs
+        .iter()
+        .find(|(c, _)| *c == code)
+        .map(|(_, value)| value.trim().to_ascii_uppercase())
+}
+
+/// Entries of the DICTIONARY object `handle`: names (group 3) each followed by
+/// the handle of the object (group 350 or 360)
+fn dictionary_entries(
+    handle: &str,
+    objects: &HashMap<String, &RawRecord>,
+    depth: usize,
+) -> Vec<DictionaryEntryData> {
+    let Some(dictionary) = objects.get(handle) else {
+        return Vec::new();
+    };
+    if depth >= MAX_DICTIONARY_DEPTH {
+        return Vec::new();
+    }
+    let mut entries = Vec::new();
+    let mut name = None;
+    for (code, value) in &dictionary.pairs {
+        match code {
+            3 => name = Some(value.clone()),
+            350 | 360 => {
+                let handle = value.trim().to_ascii_uppercase();
+                let (Some(name), Some(object)) = (name.take(), objects.get(&handle)) else {
+                    continue;
+                };
+                let nested = object.record_type == "DICTIONARY";
+                entries.push(DictionaryEntryData {
+                    name,
+                    object_type: object.record_type.clone(),
+                    pairs: object_body(object),
+                    entries: if nested {
+                        dictionary_entries(&handle, objects, depth + 1)
+                    } else {
+                        Vec::new()
+                    },
+                });
+            }
+            _ => {}
+        }
+    }
+    entries
+}
+
+/// Group codes of an object after its handle, reactors and owner. The entries of
+/// a dictionary are left out; they are kept as nested entries.
+fn object_body(object: &RawRecord) -> Vec<(i32, String)> {
+    let start = object
+        .pairs
+        .iter()
+        .position(|(code, _)| *code == 100)
+        .unwrap_or(object.pairs.len());
+    object.pairs[start..]
+        .iter()
+        .filter(|(code, _)| object.record_type != "DICTIONARY" || !matches!(code, 3 | 350 | 360))
+        .cloned()
+        .collect()
+}
+
+/// Why the data of a shape can't be written completely to a file of `version`
+pub fn approximation(shape: &ShapeData, version: AcadVersion) -> Option<&'static str> {
+    let data = shape.dxf_data.as_ref()?;
+    (!data.dictionary.is_empty() && version < AcadVersion::R13)
+        .then_some("extension dictionary needs R13 or later and was not written")
+}
+
+/// Add the applications named in XDATA to the APPID table; XDATA of an
+/// unregistered application makes the file invalid
+pub fn register_applications(drawing: &mut Drawing, shapes: &[ShapeData]) {
+    let mut known: HashSet<String> = drawing
+        .app_ids()
+        .map(|app_id| app_id.name.to_ascii_uppercase())
+        .collect();
+    let names = shapes
+        .iter()
+        .filter_map(|shape| shape.dxf_data.as_ref())
+        .flat_map(|data| &data.xdata)
+        .filter(|(code, _)| *code == 1001)
+        .map(|(_, name)| name.trim());
+    for name in names {
+        if known.insert(name.to_ascii_uppercase()) {
+            drawing.add_app_id(AppId {
+                name: name.to_string(),
+                ..Default::default()
+            });
+        }
+    }
+}
+
+/// Write the data of exported shapes into an already saved ASCII DXF file.
+/// `entities` pairs the handle of the entity written for a shape with the shape.
+/// Extension dictionaries are added to the OBJECTS section with new handles;
+/// files without one (R12) only get the XDATA, as reported by `approximation`.
+pub fn write_entity_data(
+    path: &str,
+    entities: &[(String, &ShapeData)],
+    version: AcadVersion,
+) -> io::Result<()> {
+    let data: HashMap<&str, &DxfEntityData> = entities
+        .iter()
+        .filter_map(|(handle, shape)| Some((handle.as_str(), shape.dxf_data.as_ref()?)))
+        .collect();
+    if data.is_empty() {
+        return Ok(());
+    }
+    let (lines, newline) = dxf_raw::read_lines(path)?;
+
+    let mut seed = None;
+    let mut has_objects = false;
+    let mut entry = "";
+    for (index, pair) in lines.chunks_exact(2).enumerate() {
+        let (code, value) = (pair[0].trim(), pair[1].trim());
+        match code {
+            "0" => entry = value,
+            "2" if entry == "SECTION" && value == "OBJECTS" => has_objects = true,
+            "9" if value == "$HANDSEED" => {
+                let line = index * 2 + 3;
+                let handle = lines.get(line).map(|seed| seed.trim());
+                seed = handle
+                    .and_then(|seed| u64::from_str_radix(seed, 16).ok())
+                    .map(|handle| (line, handle));
+            }
+            _ => {}
+        }
+    }
+
+    // Dictionaries need handles; without a handle seed only XDATA is written
+    let mut objects = Vec::new();
+    let mut after_handle: HashMap<&str, Vec<String>> = HashMap::new();
+    let mut next_handle = seed.map(|(_, handle)| handle);
+    for (&handle, entity_data) in &data {
+        let Some(next) = next_handle.as_mut().filter(|_| has_objects) else {
+            break;
+        };
+        if entity_data.dictionary.is_empty() {
+            continue;
+        }
+        let dictionary = write_dictionary(&mut objects, next, handle, &entity_data.dictionary);
+        let mut groups = Vec::new();
+        dxf_raw::push_pair(&mut groups, 102, "{ACAD_XDICTIONARY");
+        dxf_raw::push_pair(&mut groups, 360, dictionary);
+        dxf_raw::push_pair(&mut groups, 102, "}");
+        after_handle.insert(handle, groups);
+    }
+
+    let mut out = Vec::with_capacity(lines.len());
+    let mut section = String::new();
+    let mut entry = String::new();
+    let mut pending_xdata: Option<&DxfEntityData> = None;
+    for pair in lines.chunks_exact(2) {
+        let (code, value) = (pair[0].trim(), pair[1].trim());
+        if code == "0" {
+            // XDATA ends the record it belongs to
+            for (code, value) in pending_xdata.take().into_iter().flat_map(|d| &d.xdata) {
+                dxf_raw::push_pair(&mut out, *code, value);
+            }
+            if value == "ENDSEC" && section == "OBJECTS" {
+                out.append(&mut objects);
+            }
+            entry = value.to_string();
+        } else if code == "2" && entry == "SECTION" {
+            section = value.to_string();
+        }
+        out.extend_from_slice(pair);
+        if code == "5" && (section == "ENTITIES" || section == "BLOCKS") {
+            let handle = value.to_ascii_uppercase();
+            if let Some(groups) = after_handle.remove(handle.as_str()) {
+                out.extend(groups);
+            }
+            pending_xdata = data.get(handle.as_str()).copied();
+        }
+    }
+
+    if let (Some((line, _)), Some(next)) = (seed, next_handle) {
+        out[line] = format!("{:X}", next);
+    }
+    dxf_raw::escape_unicode(&mut out, version);
+    dxf_raw::write_lines(path, &out, newline)
+}
+
+/// Append a DICTIONARY owned by `owner` and the objects of its entries to `out`,
+/// returning the dictionary's handle
+fn write_dictionary(
+    out: &mut Vec<String>,
+    next_handle: &mut u64,
+    owner: &str,
+    entries: &[DictionaryEntryData],
+) -> String {
+    let handle = allocate(next_handle);
+    let handles: Vec<String> = entries.iter().map(|_| allocate(next_handle)).collect();
+    dxf_raw::push_pair(out, 0, "DICTIONARY");
+    dxf_raw::push_pair(out, 5, &handle);
+    dxf_raw::push_pair(out, 102, "{ACAD_REACTORS");
+    dxf_raw::push_pair(out, 330, owner);
+    dxf_raw::push_pair(out, 102, "}");
+    dxf_raw::push_pair(out, 330, owner);
+    dxf_raw::push_pair(out, 100, "AcDbDictionary");
+    dxf_raw::push_pair(out, 280, 1);
+    for (entry, entry_handle) in entries.iter().zip(&handles) {
+        dxf_raw::push_pair(out, 3, &entry.name);
+        dxf_raw::push_pair(out, 360, entry_handle);
+    }
+    for (entry, entry_handle) in entries.iter().zip(&handles) {
+        write_object(out, next_handle, entry, entry_handle, &handle);
+    }
+    handle
+}
+
+fn write_object(
+    out: &mut Vec<String>,
+    next_handle: &mut u64,
+    entry: &DictionaryEntryData,
+    handle: &str,
+    owner: &str,
+) {
+    let nested: Vec<String> = entry
+        .entries
+        .iter()
+        .map(|_| allocate(next_handle))
+        .collect();
+    dxf_raw::push_pair(out, 0, &entry.object_type);
+    dxf_raw::push_pair(out, 5, handle);
+    dxf_raw::push_pair(out, 102, "{ACAD_REACTORS");
+    dxf_raw::push_pair(out, 330, owner);
+    dxf_raw::push_pair(out, 102, "}");
+    dxf_raw::push_pair(out, 330, owner);
+    for (code, value) in &entry.pairs {
+        dxf_raw::push_pair(out, *code, value);
+    }
+    for (nested_entry, nested_handle) in entry.entries.iter().zip(&nested) {
+        dxf_raw::push_pair(out, 3, &nested_entry.name);
+        dxf_raw::push_pair(out, 360, nested_handle);
+    }
+    for (nested_entry, nested_handle) in entry.entries.iter().zip(&nested) {
+        write_object(out, next_handle, nested_entry, nested_handle, handle);
+    }
+}
+
+fn allocate(next_handle: &mut u64) -> String {
+    let handle = format!("{:X}", next_handle);
+    *next_handle += 1;
+    handle
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::dxf_import;
+    use super::*;
+    use dxf::entities::{Entity, EntityType, Line};
+    use dxf::Point;
+    use std::fs;
+
+    fn pairs(pairs: &[(i32, &str)]) -> Vec<(i32, String)> {
+        pairs
+            .iter()
+            .map(|&(code, value)| (code, value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn xdata_and_dictionaries_round_trip() {
+        let data = DxfEntityData {
+            handle: None,
+            xdata: pairs(&[(1001, "SURVEY"), (1000, "Wall A"), (1040, "2.5")]),
+            dictionary: vec![
+                DictionaryEntryData {
+                    name: "NOTE".to_string(),
+                    object_type: "XRECORD".to_string(),
+                    pairs: pairs(&[(100, "AcDbXrecord"), (280, "1"), (1, "checked")]),
+                    entries: Vec::new(),
+                },
+                DictionaryEntryData {
+                    name: "NESTED".to_string(),
+                    object_type: "DICTIONARY".to_string(),
+                    pairs: pairs(&[(100, "AcDbDictionary"), (280, "1")]),
+                    entries: vec![DictionaryEntryData {
+                        name: "INNER".to_string(),
+                        object_type: "XRECORD".to_string(),
+                        pairs: pairs(&[(100, "AcDbXrecord"), (70, "3")]),
+                        entries: Vec::new(),
+                    }],
+                },
+            ],
+        };
+        let shape = ShapeData {
+            shape_type: "line".to_string(),
+            dxf_data: Some(data.clone()),
+            ..Default::default()
+        };
+
+        let mut drawing = Drawing::new();
+        drawing.header.version = AcadVersion::R2018;
+        register_applications(&mut drawing, std::slice::from_ref(&shape));
+        let line = Line::new(Point::new(0.0, 0.0, 0.0), Point::new(1.0, 0.0, 0.0));
+        let entity = drawing.add_entity(Entity::new(EntityType::Line(line)));
+        let handle = dxf_import::handle(entity).unwrap();
+        let path = std::env::temp_dir().join(format!("xdata_{}.dxf", std::process::id()));
+        let path = path.to_str().unwrap();
+        drawing.save_file(path).unwrap();
+        write_entity_data(path, &[(handle.clone(), &shape)], AcadVersion::R2018).unwrap();
+        let registered = Drawing::load_file(path)
+            .unwrap()
+            .app_ids()
+            .any(|app_id| app_id.name == "SURVEY");
+        let raw = RawRecords::read(path, |_| Ok(())).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert!(registered);
+        let read = read_entity_data(&raw);
+        let read = &read[&handle];
+        assert_eq!(read.handle.as_deref(), Some(handle.as_str()));
+        assert_eq!(read.xdata, data.xdata);
+        assert_eq!(read.dictionary.len(), 2);
+        for (read, written) in read.dictionary.iter().zip(&data.dictionary) {
+            assert_eq!(read.name, written.name);
+            assert_eq!(read.object_type, written.object_type);
+            assert_eq!(read.pairs, written.pairs);
+        }
+        let inner = &read.dictionary[1].entries;
+        assert_eq!(inner.len(), 1);
+        assert_eq!(inner[0].name, "INNER");
+        assert_eq!(inner[0].pairs, data.dictionary[1].entries[0].pairs);
+    }
+}
@@ -0,0 +1,199 @@
+//! Crash-safe saving of project files. The data is written to a temporary file in
+//! the same directory, flushed to disk and renamed over the target, so the target
+//! always holds either the previous or the new version, never a partial one.
+//!
+//! Previous versions are kept as `<name>.bak`, `<name>.bak2`, ... with `.bak`
+//! the most recent.
+
+use serde::Deserialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Save settings from the preferences
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SaveOptions {
+    /// Backup generations to keep; 0 disables backups
+    pub backups: usize,
+    /// "json" for plain project JSON, "zip" for the container in `project_container`
+    pub format: String,
+    /// Save even if the file changed on disk since it was loaded
+    pub overwrite: bool,
+    /// Path of the project before Save As: the `o2d-asset:` references of the
+    /// data point into it, and its lock is released once saved
+    pub previous_path: Option<String>,
+}
+
+impl Default for SaveOptions {
+    fn default() -> Self {
+        Self {
+            backups: 1,
+            format: "json".to_string(),
+            overwrite: false,
+            previous_path: None,
+        }
+    }
+}
+
+/// Write `data` to `path` atomically. Returns the backup made of the previous
+/// version, if there was one.
+pub fn save_atomic(path: &Path, data: &[u8], options: &SaveOptions) -> io::Result<Option<PathBuf>> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+
+    // Same directory, so the rename stays on one file system
+    let temp = dir.join(format!(
+        ".{}.{}.tmp",
+        name.to_string_lossy(),
+        std::process::id()
+    ));
+    if let Err(e) = write_synced(&temp, data, path) {
+        let _ = fs::remove_file(&temp);
+        return Err(e);
+    }
+
+    let mut backup = None;
+    if options.backups > 0 && path.exists() {
+        match rotate_backups(path, options.backups) {
+            Ok(path) => backup = Some(path),
+            Err(e) => {
+                let _ = fs::remove_file(&temp);
+                return Err(e);
+            }
+        }
+    }
+    if let Err(e) = fs::rename(&temp, path) {
+        let _ = fs::remove_file(&temp);
+        return Err(e);
+    }
+    sync_dir(dir);
+    Ok(backup)
+}
+
+/// Write and flush the temporary file, with the permissions of the file it replaces
+fn write_synced(temp: &Path, data: &[u8], original: &Path) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(temp)?;
+    file.write_all(data)?;
+    if let Ok(metadata) = fs::metadata(original) {
+        file.set_permissions(metadata.permissions())?;
+    }
+    file.sync_all()
+}
+
+/// Shift the existing backups one generation down, dropping the oldest, and make
+/// the current file the newest backup. The current file stays in place until the
+/// new version is renamed over it.
+fn rotate_backups(path: &Path, generations: usize) -> io::Result<PathBuf> {
+    let oldest = backup_path(path, generations);
+    if oldest.exists() {
+        fs::remove_file(&oldest)?;
+    }
+    for generation in (1..generations).rev() {
+        let from = backup_path(path, generation);
+        if from.exists() {
+            fs::rename(&from, backup_path(path, generation + 1))?;
+        }
+    }
+    let newest = backup_path(path, 1);
+    // A hard link is instant and atomic; copy where links are not supported
+    if fs::hard_link(path, &newest).is_err() {
+        fs::copy(path, &newest)?;
+        File::open(&newest)?.sync_all()?;
+    }
+    Ok(newest)
+}
+
+/// `<name>.bak` for the first generation, `<name>.bak<n>` for the others
+fn backup_path(path: &Path, generation: usize) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    match generation {
+        1 => name.push(".bak"),
+        n => name.push(format!(".bak{}", n)),
+    }
+    path.with_file_name(name)
+}
+
+/// Persist the rename itself; directories can't be opened for syncing on Windows
+fn sync_dir(dir: &Path) {
+    #[cfg(unix)]
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    #[cfg(not(unix))]
+    let _ = dir;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::temp_dir;
+    use super::*;
+
+    fn read(path: &Path) -> Option<String> {
+        fs::read_to_string(path).ok()
+    }
+
+    #[test]
+    fn rotate_backups_keeps_the_newest_generations() {
+        let dir = temp_dir("rotate_backups");
+        let path = dir.join("plan.o2d");
+        let options = SaveOptions {
+            backups: 2,
+            ..Default::default()
+        };
+        let mut backups = Vec::new();
+        for version in ["v1", "v2", "v3", "v4"] {
+            backups.push(save_atomic(&path, version.as_bytes(), &options).unwrap());
+        }
+        let files = [
+            read(&path),
+            read(&backup_path(&path, 1)),
+            read(&backup_path(&path, 2)),
+            read(&backup_path(&path, 3)),
+        ];
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(backups[0], None);
+        assert_eq!(backups[3], Some(dir.join("plan.o2d.bak")));
+        let files: Vec<_> = files.iter().map(Option::as_deref).collect();
+        assert_eq!(files, [Some("v4"), Some("v3"), Some("v2"), None]);
+    }
+
+    #[test]
+    fn save_without_backups_leaves_only_the_file() {
+        let dir = temp_dir("save_without_backups");
+        let path = dir.join("plan.o2d");
+        let options = SaveOptions {
+            backups: 0,
+            ..Default::default()
+        };
+        save_atomic(&path, b"v1", &options).unwrap();
+        let backup = save_atomic(&path, b"v2", &options).unwrap();
+        let names: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        let content = read(&path);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(backup, None);
+        assert_eq!(names, ["plan.o2d"]);
+        assert_eq!(content.as_deref(), Some("v2"));
+    }
+
+    #[test]
+    fn backup_names_count_generations() {
+        let path = Path::new("dir/plan.o2d");
+        assert_eq!(backup_path(path, 1), Path::new("dir/plan.o2d.bak"));
+        assert_eq!(backup_path(path, 3), Path::new("dir/plan.o2d.bak3"));
+    }
+}
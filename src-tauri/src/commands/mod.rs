@@ -1,6 +1,54 @@
+mod dxf_blocks;
+mod dxf_color;
+mod dxf_dimensions;
+mod dxf_elements;
+mod dxf_export;
+mod dxf_file;
+mod dxf_hatch;
+mod dxf_image;
+mod dxf_import;
+mod dxf_layouts;
+mod dxf_layers;
+mod dxf_linetypes;
+mod dxf_ocs;
+mod dxf_origin;
+mod dxf_project;
+mod dxf_raw;
+mod dxf_report;
+mod dxf_scan;
+mod dxf_stream;
+mod dxf_text;
+mod dxf_transform;
+mod dxf_xdata;
+mod file_save;
+mod project_container;
+mod project_lock;
+mod shape;
+#[cfg(test)]
+mod test_support;
+
+use dxf_export::DxfExportOptions;
+use dxf_file::{Content, ExportTables};
+use dxf_hatch::HatchPatternData;
+use dxf_import::DxfImportOptions;
+use dxf_layers::LayerData;
+use dxf_layouts::SheetData;
+use dxf_linetypes::LineTypeData;
+use dxf_project::{DrawingExportData, DxfProjectExportOptions};
+use dxf_report::ConversionReport;
+use file_save::SaveOptions;
+pub use dxf_stream::DxfImportJobs;
+pub use project_container::read_project;
+use project_container::AssetEntry;
+pub use project_lock::ProjectLocks;
+use project_lock::{LoadOptions, LockError, LockInfo};
 use serde::{Deserialize, Serialize};
-use std::fs;
+use crate::file_watcher::FileWatcher;
+use crate::project_schema::{self, ProjectError, ProjectIssue, ValidationReport};
+use shape::ShapeData;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::Arc;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SaveResult {
@@ -8,6 +56,27 @@ pub struct SaveResult {
     message: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileSaveResult {
+    success: bool,
+    message: String,
+    /// Backup made of the previous version of the file
+    backup: Option<String>,
+    /// Not saved because someone else changed the file since it was loaded
+    changed_on_disk: bool,
+}
+
+impl FileSaveResult {
+    fn failed(message: String) -> Self {
+        Self {
+            success: false,
+            message,
+            backup: None,
+            changed_on_disk: false,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LoadResult {
     success: bool,
@@ -15,101 +84,438 @@ pub struct LoadResult {
     message: String,
 }
 
-/// Save drawing to native JSON format
+#[derive(Debug, Serialize)]
+pub struct ProjectLoadResult {
+    success: bool,
+    data: Option<String>,
+    message: String,
+    /// File format version the project was written in
+    version: Option<u64>,
+    /// Broken references the project was loaded with
+    issues: Vec<ProjectIssue>,
+    /// Where the file is broken, if it could not be loaded
+    error: Option<ProjectError>,
+    /// Opened without the lock; saving is refused
+    read_only: bool,
+    /// Lock owner when the project is in use elsewhere
+    locked_by: Option<LockInfo>,
+    /// Files embedded in a project container
+    assets: Vec<AssetEntry>,
+}
+
+impl ProjectLoadResult {
+    fn failed(message: String) -> Self {
+        Self {
+            success: false,
+            data: None,
+            message,
+            version: None,
+            issues: Vec::new(),
+            error: None,
+            read_only: false,
+            locked_by: None,
+            assets: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DxfExportResult {
+    success: bool,
+    message: String,
+    /// Per shape type counts and the shapes that were approximated or skipped
+    report: Option<ConversionReport>,
+}
+
+/// Save drawing to native JSON format, plain or packed in a project container
+/// (`"format": "zip"`). The file is replaced atomically and the previous version
+/// is kept as a backup (see `file_save`). Saving is refused for projects opened
+/// read-only or changed on disk since they were loaded, unless `"overwrite": true`
+/// (see `project_lock`).
+///
+/// Asset references from `load_file` are resolved against the container being
+/// replaced. On Save As, `"previous_path"` names the file the project had: the
+/// references point into it and its lock is released once saved.
+#[tauri::command]
+pub fn save_file(
+    locks: tauri::State<'_, Arc<ProjectLocks>>,
+    watcher: tauri::State<'_, Arc<FileWatcher>>,
+    path: String,
+    data: String,
+    options_json: Option<String>,
+) -> FileSaveResult {
+    let options: SaveOptions = match options_json.as_deref().map(serde_json::from_str) {
+        Some(Ok(o)) => o,
+        Some(Err(e)) => return FileSaveResult::failed(format!("Failed to parse save options: {}", e)),
+        None => SaveOptions::default(),
+    };
+
+    let locked_here = match locks.check_save(Path::new(&path), options.overwrite) {
+        Ok(locked_here) => locked_here,
+        Err(e) => {
+            return FileSaveResult {
+                changed_on_disk: matches!(e, LockError::Changed),
+                ..FileSaveResult::failed(format!("Failed to save file: {}", e))
+            }
+        }
+    };
+    // A lock taken for Save As is released again if nothing gets saved
+    let release = || {
+        if locked_here {
+            locks.close(Path::new(&path));
+        }
+    };
+
+    let source = Path::new(options.previous_path.as_deref().unwrap_or(&path));
+    let bytes = match options.format.as_str() {
+        "json" => project_container::inline_assets(&data, Some(source))
+            .map(String::into_bytes)
+            .map_err(|e| format!("Failed to read project files: {}", e)),
+        "zip" => project_container::write_container(&data, Path::new(&path).parent(), Some(source))
+            .map_err(|e| format!("Failed to pack project: {}", e)),
+        other => Err(format!("Unknown project file format: {}", other)),
+    };
+    let bytes = match bytes {
+        Ok(b) => b,
+        Err(message) => {
+            release();
+            return FileSaveResult::failed(message);
+        }
+    };
+
+    match file_save::save_atomic(Path::new(&path), &bytes, &options) {
+        Ok(backup) => {
+            locks.saved(Path::new(&path));
+            if let Some(previous) = &options.previous_path {
+                locks.moved(Path::new(previous), Path::new(&path));
+            }
+            watcher.ignore_own_write(Path::new(&path));
+            FileSaveResult {
+                success: true,
+                message: match &backup {
+                    Some(backup) => format!("File saved to {} (backup: {})", path, backup.display()),
+                    None => format!("File saved to {}", path),
+                },
+                backup: backup.map(|b| b.to_string_lossy().into_owned()),
+                changed_on_disk: false,
+            }
+        }
+        Err(e) => {
+            release();
+            FileSaveResult::failed(format!("Failed to save file: {}", e))
+        }
+    }
+}
+
+/// Load drawing from native JSON format, either plain or in a project container.
+/// The project is checked and migrated to the current file format version (see
+/// `project_schema`). Embedded files stay `o2d-asset:<path>` references in the
+/// data; `assets` lists them and `load_project_asset` reads their content.
+///
+/// The project is locked while open. If someone else holds the lock, nothing is
+/// loaded and `locked_by` tells who; `"read_only": true` opens it anyway, without
+/// the lock and without saving, and `"break_lock": true` takes the lock over.
+#[tauri::command]
+pub fn load_file(
+    locks: tauri::State<'_, Arc<ProjectLocks>>,
+    path: String,
+    options_json: Option<String>,
+) -> ProjectLoadResult {
+    let options: LoadOptions = match options_json.as_deref().map(serde_json::from_str) {
+        Some(Ok(o)) => o,
+        Some(Err(e)) => return ProjectLoadResult::failed(format!("Failed to parse load options: {}", e)),
+        None => LoadOptions::default(),
+    };
+
+    let opened = match locks.open(Path::new(&path), &options) {
+        Ok(opened) => opened,
+        Err(LockError::Locked(lock)) => {
+            return ProjectLoadResult {
+                locked_by: Some(lock.clone()),
+                ..ProjectLoadResult::failed(LockError::Locked(lock).to_string())
+            }
+        }
+        Err(e) => return ProjectLoadResult::failed(format!("Failed to lock file: {}", e)),
+    };
+
+    let (loaded, assets) = match project_container::open_project(Path::new(&path)) {
+        Ok(file) => (project_schema::load(&file.json), file.assets),
+        Err(e) => {
+            // A project that failed to reload stays open as it was
+            if !opened.reopened {
+                locks.close(Path::new(&path));
+            }
+            return ProjectLoadResult::failed(format!("Failed to load file: {}", e));
+        }
+    };
+    let project = match loaded {
+        Ok(project) => project,
+        Err(error) => {
+            if !opened.reopened {
+                locks.close(Path::new(&path));
+            }
+            return ProjectLoadResult {
+                message: format!("Invalid project file: {}", error),
+                error: Some(error),
+                ..ProjectLoadResult::failed(String::new())
+            };
+        }
+    };
+
+    let mut message = "File loaded successfully".to_string();
+    if project.migrated() {
+        message = format!("File loaded and migrated from version {}", project.version);
+    }
+    if opened.read_only {
+        message = format!("{} (read-only)", message);
+    }
+    if opened.took_over && options.break_lock {
+        message = format!("{}; the lock was broken", message);
+    } else if opened.took_over {
+        message = format!("{}; a stale lock was removed", message);
+    }
+    if !project.issues.is_empty() {
+        message = format!("{} ({} problems found)", message, project.issues.len());
+    }
+    ProjectLoadResult {
+        success: true,
+        message,
+        version: Some(project.version),
+        data: Some(project.json),
+        issues: project.issues,
+        read_only: opened.read_only,
+        assets,
+        ..ProjectLoadResult::failed(String::new())
+    }
+}
+
+/// Content of a file embedded in a project container, as binary rather than
+/// base64 in JSON. `asset` is the path of an `o2d-asset:<path>` reference.
+#[tauri::command]
+pub fn load_project_asset(path: String, asset: String) -> Result<tauri::ipc::Response, String> {
+    project_container::read_asset(Path::new(&path), &asset)
+        .map(tauri::ipc::Response::new)
+        .map_err(|e| format!("Failed to load {}: {}", asset, e))
+}
+
+/// Release the lock of a project that is closed
 #[tauri::command]
-pub fn save_file(path: String, data: String) -> SaveResult {
-    match fs::write(&path, &data) {
-        Ok(_) => SaveResult {
+pub fn close_project(locks: tauri::State<'_, Arc<ProjectLocks>>, path: String) -> SaveResult {
+    if locks.close(Path::new(&path)) {
+        SaveResult {
             success: true,
-            message: format!("File saved to {}", path),
-        },
-        Err(e) => SaveResult {
+            message: format!("Closed {}", path),
+        }
+    } else {
+        SaveResult {
             success: false,
-            message: format!("Failed to save file: {}", e),
-        },
+            message: format!("{} is not open", path),
+        }
     }
 }
 
-/// Load drawing from native JSON format
+/// Watch the open project and the files it references (`sourcePath` in the
+/// project, plus `references_json`, a list of paths) for changes by others;
+/// see `file_watcher` for the events sent
 #[tauri::command]
-pub fn load_file(path: String) -> LoadResult {
-    match fs::read_to_string(&path) {
-        Ok(content) => LoadResult {
+pub fn watch_project(
+    app: tauri::AppHandle,
+    watcher: tauri::State<'_, Arc<FileWatcher>>,
+    path: String,
+    references_json: Option<String>,
+) -> SaveResult {
+    let extra: Vec<PathBuf> = match parse_optional_json(references_json.as_deref(), "references") {
+        Ok(e) => e,
+        Err(message) => return SaveResult { success: false, message },
+    };
+    // A project that can't be read is still watched, e.g. to notice it being restored
+    let json = project_container::read_project(Path::new(&path)).unwrap_or_default();
+    match watcher.watch_project(&app, Path::new(&path), &json, &extra) {
+        Ok(count) => SaveResult {
             success: true,
-            data: Some(content),
-            message: "File loaded successfully".to_string(),
+            message: format!("Watching {} and {} referenced files", path, count),
         },
-        Err(e) => LoadResult {
+        Err(e) => SaveResult {
             success: false,
-            data: None,
-            message: format!("Failed to load file: {}", e),
+            message: format!("Failed to watch {}: {}", path, e),
         },
     }
 }
 
+/// Stop watching the project, when it is closed
+#[tauri::command]
+pub fn unwatch_project(watcher: tauri::State<'_, Arc<FileWatcher>>) -> SaveResult {
+    watcher.unwatch();
+    SaveResult {
+        success: true,
+        message: "Stopped watching files".to_string(),
+    }
+}
+
+/// Check a project file without opening it
+#[tauri::command]
+pub fn validate_project(path: String) -> ValidationReport {
+    match project_container::read_project(Path::new(&path)) {
+        Ok(json) => project_schema::validate(&json),
+        Err(e) => ValidationReport::unreadable(format!("Failed to load file: {}", e)),
+    }
+}
+
 /// Export drawing to DXF format
 #[tauri::command]
-pub fn export_dxf(path: String, shapes_json: String) -> SaveResult {
+pub fn export_dxf(
+    path: String,
+    shapes_json: String,
+    layers_json: Option<String>,
+    line_types_json: Option<String>,
+    patterns_json: Option<String>,
+    sheets_json: Option<String>,
+    options_json: Option<String>,
+) -> DxfExportResult {
     // Parse shapes from JSON
     let shapes: Vec<ShapeData> = match serde_json::from_str(&shapes_json) {
         Ok(s) => s,
         Err(e) => {
-            return SaveResult {
+            return DxfExportResult {
                 success: false,
                 message: format!("Failed to parse shapes: {}", e),
+                report: None,
             }
         }
     };
 
-    // Create DXF drawing
-    let mut drawing = dxf::Drawing::new();
-
-    for shape in shapes {
-        match shape.shape_type.as_str() {
-            "line" => {
-                if let (Some(start), Some(end)) = (shape.start, shape.end) {
-                    let line = dxf::entities::Line::new(
-                        dxf::Point::new(start.x, start.y, 0.0),
-                        dxf::Point::new(end.x, end.y, 0.0),
-                    );
-                    drawing.add_entity(dxf::entities::Entity::new(
-                        dxf::entities::EntityType::Line(line),
-                    ));
-                }
+    let layers: Vec<LayerData> = match parse_optional_json(layers_json.as_deref(), "layers") {
+        Ok(l) => l,
+        Err(message) => return DxfExportResult { success: false, message, report: None },
+    };
+    let line_types: Vec<LineTypeData> =
+        match parse_optional_json(line_types_json.as_deref(), "line types") {
+            Ok(l) => l,
+            Err(message) => return DxfExportResult { success: false, message, report: None },
+        };
+    let patterns: Vec<HatchPatternData> =
+        match parse_optional_json(patterns_json.as_deref(), "hatch patterns") {
+            Ok(p) => p,
+            Err(message) => return DxfExportResult { success: false, message, report: None },
+        };
+    let sheets: Vec<SheetData> = match parse_optional_json(sheets_json.as_deref(), "sheets") {
+        Ok(s) => s,
+        Err(message) => return DxfExportResult { success: false, message, report: None },
+    };
+
+    let options: DxfExportOptions = match options_json.as_deref().map(serde_json::from_str) {
+        Some(Ok(o)) => o,
+        Some(Err(e)) => {
+            return DxfExportResult {
+                success: false,
+                message: format!("Failed to parse export options: {}", e),
+                report: None,
             }
-            "circle" => {
-                if let (Some(center), Some(radius)) = (shape.center, shape.radius) {
-                    let circle = dxf::entities::Circle::new(
-                        dxf::Point::new(center.x, center.y, 0.0),
-                        radius,
-                    );
-                    drawing.add_entity(dxf::entities::Entity::new(
-                        dxf::entities::EntityType::Circle(circle),
-                    ));
-                }
+        }
+        None => DxfExportOptions::default(),
+    };
+    let tables = ExportTables { layers, line_types, patterns };
+    let content = Content { model: shapes, blocks: Vec::new() };
+    match dxf_file::write_file(&path, content, &sheets, &tables, &options) {
+        Ok(report) => DxfExportResult {
+            success: true,
+            message: format!("DXF exported to {} ({})", path, report.summary()),
+            report: Some(report),
+        },
+        Err(message) => DxfExportResult { success: false, message, report: None },
+    }
+}
+
+/// Export several drawings at once: as named blocks in the DXF file `path` with
+/// an overview layout, or with `"mode": "files"` as one DXF file per drawing in
+/// the folder `path`
+#[tauri::command]
+pub fn export_dxf_project(
+    path: String,
+    drawings_json: String,
+    layers_json: Option<String>,
+    line_types_json: Option<String>,
+    patterns_json: Option<String>,
+    options_json: Option<String>,
+) -> DxfExportResult {
+    let drawings: Vec<DrawingExportData> = match serde_json::from_str(&drawings_json) {
+        Ok(d) => d,
+        Err(e) => {
+            return DxfExportResult {
+                success: false,
+                message: format!("Failed to parse drawings: {}", e),
+                report: None,
+            }
+        }
+    };
+    let tables = match (
+        parse_optional_json(layers_json.as_deref(), "layers"),
+        parse_optional_json(line_types_json.as_deref(), "line types"),
+        parse_optional_json(patterns_json.as_deref(), "hatch patterns"),
+    ) {
+        (Ok(layers), Ok(line_types), Ok(patterns)) => ExportTables { layers, line_types, patterns },
+        (Err(message), _, _) | (_, Err(message), _) | (_, _, Err(message)) => {
+            return DxfExportResult { success: false, message, report: None }
+        }
+    };
+    let options: DxfProjectExportOptions = match options_json.as_deref().map(serde_json::from_str) {
+        Some(Ok(o)) => o,
+        Some(Err(e)) => {
+            return DxfExportResult {
+                success: false,
+                message: format!("Failed to parse export options: {}", e),
+                report: None,
             }
-            // Add more shape types as needed
-            _ => {}
         }
+        None => DxfProjectExportOptions::default(),
+    };
+
+    let count = drawings.len();
+    let exported = match options.mode.as_str() {
+        "blocks" => dxf_project::export_blocks(&path, drawings, &tables, &options).map(|report| {
+            let message = format!("{} drawings exported to {} ({})", count, path, report.summary());
+            (message, report)
+        }),
+        "files" => dxf_project::export_files(&path, drawings, &tables, &options).map(|(files, report)| {
+            let message = format!("{} DXF files exported to {} ({})", files.len(), path, report.summary());
+            (message, report)
+        }),
+        other => Err(format!("Unknown project export mode: {}", other)),
+    };
+    match exported {
+        Ok((message, report)) => DxfExportResult { success: true, message, report: Some(report) },
+        Err(message) => DxfExportResult { success: false, message, report: None },
     }
+}
 
-    // Save DXF file
-    match drawing.save_file(&path) {
-        Ok(_) => SaveResult {
-            success: true,
-            message: format!("DXF exported to {}", path),
-        },
-        Err(e) => SaveResult {
-            success: false,
-            message: format!("Failed to export DXF: {}", e),
-        },
+/// Parse an optional JSON list argument; a missing argument is an empty list
+fn parse_optional_json<T: serde::de::DeserializeOwned>(
+    json: Option<&str>,
+    what: &str,
+) -> Result<Vec<T>, String> {
+    match json {
+        Some(json) => serde_json::from_str(json)
+            .map_err(|e| format!("Failed to parse {}: {}", what, e)),
+        None => Ok(Vec::new()),
     }
 }
 
 /// Import drawing from DXF format
 #[tauri::command]
-pub fn import_dxf(path: String) -> LoadResult {
-    let drawing = match dxf::Drawing::load_file(&path) {
+pub fn import_dxf(path: String, options_json: Option<String>) -> LoadResult {
+    let options: DxfImportOptions = match options_json.as_deref().map(serde_json::from_str) {
+        Some(Ok(o)) => o,
+        Some(Err(e)) => {
+            return LoadResult {
+                success: false,
+                data: None,
+                message: format!("Failed to parse import options: {}", e),
+            }
+        }
+        None => DxfImportOptions::default(),
+    };
+
+    let drawing = match dxf::Drawing::load_file_with_encoding(&path, dxf_raw::code_page(&path)) {
         Ok(d) => d,
         Err(e) => {
             return LoadResult {
@@ -119,50 +525,23 @@ pub fn import_dxf(path: String) -> LoadResult {
             }
         }
     };
-
-    let mut shapes: Vec<ShapeData> = Vec::new();
-
-    for entity in drawing.entities() {
-        match &entity.specific {
-            dxf::entities::EntityType::Line(line) => {
-                shapes.push(ShapeData {
-                    shape_type: "line".to_string(),
-                    start: Some(PointData {
-                        x: line.p1.x,
-                        y: line.p1.y,
-                    }),
-                    end: Some(PointData {
-                        x: line.p2.x,
-                        y: line.p2.y,
-                    }),
-                    center: None,
-                    radius: None,
-                    points: None,
-                });
-            }
-            dxf::entities::EntityType::Circle(circle) => {
-                shapes.push(ShapeData {
-                    shape_type: "circle".to_string(),
-                    start: None,
-                    end: None,
-                    center: Some(PointData {
-                        x: circle.center.x,
-                        y: circle.center.y,
-                    }),
-                    radius: Some(circle.radius),
-                    points: None,
-                });
+    let document = match dxf_import::import_drawing(&path, &drawing, &options, &()) {
+        Ok(document) => document,
+        Err(stop) => {
+            return LoadResult {
+                success: false,
+                data: None,
+                message: stop.to_string(),
             }
-            // Add more entity types as needed
-            _ => {}
         }
-    }
+    };
+    let message = format!("DXF imported: {}", document.report.summary());
 
-    match serde_json::to_string(&shapes) {
+    match serde_json::to_string(&document) {
         Ok(json) => LoadResult {
             success: true,
             data: Some(json),
-            message: "DXF imported successfully".to_string(),
+            message,
         },
         Err(e) => LoadResult {
             success: false,
@@ -172,20 +551,88 @@ pub fn import_dxf(path: String) -> LoadResult {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct ShapeData {
-    shape_type: String,
-    start: Option<PointData>,
-    end: Option<PointData>,
-    center: Option<PointData>,
-    radius: Option<f64>,
-    points: Option<Vec<PointData>>,
+/// Import a DXF file in the background. Progress, model space shapes in chunks and
+/// the rest of the document arrive as `dxf-import-*` events; `data` is the job ID
+#[tauri::command]
+pub fn start_dxf_import(
+    app: tauri::AppHandle,
+    jobs: tauri::State<'_, Arc<DxfImportJobs>>,
+    path: String,
+    options_json: Option<String>,
+) -> LoadResult {
+    let options: DxfImportOptions = match options_json.as_deref().map(serde_json::from_str) {
+        Some(Ok(o)) => o,
+        Some(Err(e)) => {
+            return LoadResult {
+                success: false,
+                data: None,
+                message: format!("Failed to parse import options: {}", e),
+            }
+        }
+        None => DxfImportOptions::default(),
+    };
+
+    match dxf_stream::start(app, jobs.inner().clone(), path.clone(), options) {
+        Ok(job_id) => LoadResult {
+            success: true,
+            data: Some(job_id),
+            message: format!("Importing {}", path),
+        },
+        Err(e) => LoadResult {
+            success: false,
+            data: None,
+            message: format!("Failed to open DXF: {}", e),
+        },
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct PointData {
-    x: f64,
-    y: f64,
+/// Stop a background DXF import; it finishes with a cancelled `dxf-import-finished` event
+#[tauri::command]
+pub fn cancel_dxf_import(jobs: tauri::State<'_, Arc<DxfImportJobs>>, job_id: String) -> SaveResult {
+    if jobs.cancel(&job_id) {
+        SaveResult {
+            success: true,
+            message: "Cancelling DXF import".to_string(),
+        }
+    } else {
+        SaveResult {
+            success: false,
+            message: format!("No DXF import running with ID {}", job_id),
+        }
+    }
+}
+
+/// Summarise a DXF file (version, units, extents, layers, blocks, layouts) without
+/// importing it, so the import dialog can offer layer and layout filters
+#[tauri::command]
+pub fn inspect_dxf(path: String) -> LoadResult {
+    let summary = match dxf_scan::scan(&path) {
+        Ok(s) => s,
+        Err(e) => {
+            return LoadResult {
+                success: false,
+                data: None,
+                message: format!("Failed to read DXF: {}", e),
+            }
+        }
+    };
+    match serde_json::to_string(&summary) {
+        Ok(json) => LoadResult {
+            success: true,
+            data: Some(json),
+            message: format!(
+                "{} layers, {} blocks, {} layouts",
+                summary.layers.len(),
+                summary.blocks.len(),
+                summary.layouts.len()
+            ),
+        },
+        Err(e) => LoadResult {
+            success: false,
+            data: None,
+            message: format!("Failed to serialize DXF summary: {}", e),
+        },
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -0,0 +1,536 @@
+//! Zip container for project files. Besides the project JSON it holds the embedded
+//! images, PDFs and SVG hatch pattern tiles as separate entries, so they are not
+//! inflated by base64 and survive moving the project:
+//!
+//! - `manifest.json`: container and project versions and the list of assets
+//! - `project.json`: the project with every asset replaced by `o2d-asset:<entry>`
+//! - `images/`, `pdfs/`, `patterns/`, `files/`: the assets
+//!
+//! Reading accepts both the container and the legacy plain JSON `.o2d`. The project
+//! JSON keeps its asset references, so the assets don't cross IPC as base64: they
+//! are read one by one with `read_asset`, and the references passed back on saving
+//! are resolved against the container they came from.
+
+use super::dxf_image;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Cursor, Read, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// Written to `manifest.json` so other zip files are not mistaken for projects
+const FORMAT: &str = "open-nd-studio-project";
+
+/// Layout of the container; the project JSON has its own `version`
+const CONTAINER_VERSION: u32 = 1;
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const PROJECT_ENTRY: &str = "project.json";
+
+/// Prefix of the strings in `project.json` that refer to an asset entry
+const ASSET_PREFIX: &str = "o2d-asset:";
+
+/// Smaller data URLs and pattern tiles stay in the project JSON
+const MIN_ASSET_SIZE: usize = 4096;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Manifest {
+    format: String,
+    container_version: u32,
+    /// `version` of the project JSON
+    project_version: Option<u64>,
+    /// Version of the application that wrote the file
+    application_version: String,
+    project: String,
+    #[serde(default)]
+    assets: Vec<AssetEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssetEntry {
+    /// Entry name in the container
+    path: String,
+    /// "image" | "pdf" | "pattern" | "file"
+    kind: String,
+    media_type: String,
+    size: u64,
+    /// Inlined as text (pattern tiles) instead of a base64 data URL
+    #[serde(default)]
+    text: bool,
+    /// File the asset was embedded from, for linked images
+    #[serde(default)]
+    source_path: Option<String>,
+}
+
+/// Whether the file content is a zip container rather than plain JSON
+pub fn is_container(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"PK\x03\x04")
+}
+
+/// Project JSON read from a project file, with the assets its `o2d-asset:<path>`
+/// references point to
+pub struct ProjectFile {
+    pub json: String,
+    /// Empty for plain JSON files
+    pub assets: Vec<AssetEntry>,
+}
+
+/// Read a project file of either format
+pub fn open_project(path: &Path) -> io::Result<ProjectFile> {
+    let bytes = fs::read(path)?;
+    if !is_container(&bytes) {
+        let json = String::from_utf8(bytes).map_err(|e| invalid(e.to_string()))?;
+        return Ok(ProjectFile {
+            json,
+            assets: Vec::new(),
+        });
+    }
+    let mut zip = ZipArchive::new(Cursor::new(bytes)).map_err(to_io)?;
+    let manifest = read_manifest(&mut zip)?;
+    let json = String::from_utf8(read_entry(&mut zip, &manifest.project)?)
+        .map_err(|e| invalid(format!("invalid project: {}", e)))?;
+    Ok(ProjectFile {
+        json,
+        assets: manifest.assets,
+    })
+}
+
+/// Read a project file of either format and return its project JSON
+pub fn read_project(path: &Path) -> io::Result<String> {
+    open_project(path).map(|project| project.json)
+}
+
+/// Content of an asset listed in the manifest of a container
+pub fn read_asset(path: &Path, asset: &str) -> io::Result<Vec<u8>> {
+    let mut zip = ZipArchive::new(Cursor::new(fs::read(path)?)).map_err(to_io)?;
+    let manifest = read_manifest(&mut zip)?;
+    if !manifest.assets.iter().any(|entry| entry.path == asset) {
+        return Err(invalid(format!("no asset {}", asset)));
+    }
+    read_entry(&mut zip, asset)
+}
+
+/// Pack project JSON into a container. Asset references are resolved against the
+/// container `source`, usually the file being replaced. Images only linked through
+/// their `sourcePath` are embedded; relative paths are resolved against `base_dir`.
+pub fn write_container(
+    project_json: &str,
+    base_dir: Option<&Path>,
+    source: Option<&Path>,
+) -> io::Result<Vec<u8>> {
+    let mut project: Value =
+        serde_json::from_str(project_json).map_err(|e| invalid(e.to_string()))?;
+    let mut assets = Assets {
+        source: source_assets(source)?,
+        ..Default::default()
+    };
+    extract_assets(&mut project, None, &mut assets, base_dir);
+
+    let manifest = Manifest {
+        format: FORMAT.to_string(),
+        container_version: CONTAINER_VERSION,
+        project_version: project.get("version").and_then(Value::as_u64),
+        application_version: env!("CARGO_PKG_VERSION").to_string(),
+        project: PROJECT_ENTRY.to_string(),
+        assets: assets
+            .entries
+            .iter()
+            .map(|(entry, _)| entry.clone())
+            .collect(),
+    };
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    // Raster images and PDFs are compressed already
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    zip.start_file(MANIFEST_ENTRY, deflated).map_err(to_io)?;
+    zip.write_all(&serde_json::to_vec_pretty(&manifest).map_err(to_io)?)?;
+    zip.start_file(PROJECT_ENTRY, deflated).map_err(to_io)?;
+    zip.write_all(&serde_json::to_vec(&project).map_err(to_io)?)?;
+    for (entry, data) in &assets.entries {
+        let options = if entry.text || entry.media_type == "image/bmp" {
+            deflated
+        } else {
+            stored
+        };
+        zip.start_file(entry.path.as_str(), options)
+            .map_err(to_io)?;
+        zip.write_all(data)?;
+    }
+    Ok(zip.finish().map_err(to_io)?.into_inner())
+}
+
+/// Replace the asset references of project JSON by the assets of the container
+/// `source`, for saving as plain JSON
+pub fn inline_assets(project_json: &str, source: Option<&Path>) -> io::Result<String> {
+    let source = source_assets(source)?;
+    if source.is_empty() {
+        return Ok(project_json.to_string());
+    }
+    let mut project: Value =
+        serde_json::from_str(project_json).map_err(|e| invalid(e.to_string()))?;
+    inline_references(&mut project, &source);
+    serde_json::to_string(&project).map_err(to_io)
+}
+
+fn read_manifest(zip: &mut ZipArchive<Cursor<Vec<u8>>>) -> io::Result<Manifest> {
+    let manifest: Manifest = serde_json::from_slice(&read_entry(zip, MANIFEST_ENTRY)?)
+        .map_err(|e| invalid(format!("invalid manifest: {}", e)))?;
+    if manifest.format != FORMAT {
+        return Err(invalid(format!("not a project file: {}", manifest.format)));
+    }
+    if manifest.container_version > CONTAINER_VERSION {
+        return Err(invalid(format!(
+            "project container version {} needs a newer version of the application",
+            manifest.container_version
+        )));
+    }
+    Ok(manifest)
+}
+
+/// The assets of a container by entry name; none when `path` is missing, does
+/// not exist yet or is plain JSON
+fn source_assets(path: Option<&Path>) -> io::Result<HashMap<String, (AssetEntry, Vec<u8>)>> {
+    let bytes = match path.map(fs::read) {
+        Some(Ok(bytes)) if is_container(&bytes) => bytes,
+        Some(Err(e)) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => return Ok(HashMap::new()),
+    };
+    let mut zip = ZipArchive::new(Cursor::new(bytes)).map_err(to_io)?;
+    let manifest = read_manifest(&mut zip)?;
+    manifest
+        .assets
+        .into_iter()
+        .map(|entry| {
+            let data = read_entry(&mut zip, &entry.path)?;
+            Ok((entry.path.clone(), (entry, data)))
+        })
+        .collect()
+}
+
+fn read_entry(zip: &mut ZipArchive<Cursor<Vec<u8>>>, name: &str) -> io::Result<Vec<u8>> {
+    let mut file = zip
+        .by_name(name)
+        .map_err(|e| invalid(format!("{}: {}", name, e)))?;
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// Assets collected while packing, each stored once however often it is used
+#[derive(Default)]
+struct Assets {
+    entries: Vec<(AssetEntry, Vec<u8>)>,
+    by_hash: HashMap<u64, Vec<usize>>,
+    /// Assets of the container the project was loaded from, by entry name
+    source: HashMap<String, (AssetEntry, Vec<u8>)>,
+}
+
+impl Assets {
+    /// Add an asset and return the reference that replaces it in the project
+    fn add(
+        &mut self,
+        data: Vec<u8>,
+        media_type: &str,
+        text: bool,
+        source_path: Option<String>,
+    ) -> String {
+        let mut hasher = DefaultHasher::new();
+        data.hash(&mut hasher);
+        text.hash(&mut hasher);
+        let hash = hasher.finish();
+        let same = self
+            .by_hash
+            .get(&hash)
+            .into_iter()
+            .flatten()
+            .map(|&index| &self.entries[index])
+            .find(|(entry, existing)| entry.text == text && *existing == data);
+        if let Some((entry, _)) = same {
+            return format!("{}{}", ASSET_PREFIX, entry.path);
+        }
+
+        let (kind, folder) = match media_type {
+            _ if text => ("pattern", "patterns"),
+            "application/pdf" => ("pdf", "pdfs"),
+            m if m.starts_with("image/") => ("image", "images"),
+            _ => ("file", "files"),
+        };
+        let path = format!(
+            "{}/{}.{}",
+            folder,
+            self.entries.len() + 1,
+            extension(media_type)
+        );
+        let entry = AssetEntry {
+            path: path.clone(),
+            kind: kind.to_string(),
+            media_type: media_type.to_string(),
+            size: data.len() as u64,
+            text,
+            source_path,
+        };
+        self.by_hash
+            .entry(hash)
+            .or_default()
+            .push(self.entries.len());
+        self.entries.push((entry, data));
+        format!("{}{}", ASSET_PREFIX, path)
+    }
+}
+
+/// Replace data URLs and SVG pattern tiles by asset references, carry over the
+/// references to the source container, and embed images that only have a
+/// `sourcePath`
+fn extract_assets(
+    value: &mut Value,
+    key: Option<&str>,
+    assets: &mut Assets,
+    base_dir: Option<&Path>,
+) {
+    match value {
+        Value::Object(object) => {
+            link_source(object, assets, base_dir);
+            for (key, value) in object.iter_mut() {
+                extract_assets(value, Some(key), assets, base_dir);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                extract_assets(item, None, assets, base_dir);
+            }
+        }
+        // Only references to assets of the source container are carried over;
+        // other text stays as it is
+        Value::String(text) if text.starts_with(ASSET_PREFIX) => {
+            let path = &text[ASSET_PREFIX.len()..];
+            if let Some((entry, data)) = assets.source.get(path).cloned() {
+                *text = assets.add(data, &entry.media_type, entry.text, entry.source_path);
+            }
+        }
+        Value::String(text) if text.len() >= MIN_ASSET_SIZE => {
+            if key == Some("svgTile") {
+                let data = std::mem::take(text).into_bytes();
+                *text = assets.add(data, "image/svg+xml", true, None);
+            } else if let Some((media_type, data)) = parse_data_url(text) {
+                *text = assets.add(data, &media_type, false, None);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Embed the file of an image that is linked but has no image data of its own
+fn link_source(
+    object: &mut serde_json::Map<String, Value>,
+    assets: &mut Assets,
+    base_dir: Option<&Path>,
+) {
+    let has_data = object
+        .get("imageData")
+        .and_then(Value::as_str)
+        .is_some_and(|data| !data.is_empty());
+    let Some(source) = object.get("sourcePath").and_then(Value::as_str) else {
+        return;
+    };
+    if has_data || object.get("type").and_then(Value::as_str) != Some("image") {
+        return;
+    }
+    let source_path = Path::new(source);
+    let file = match base_dir {
+        Some(dir) if source_path.is_relative() => dir.join(source_path),
+        _ => source_path.to_path_buf(),
+    };
+    let Some((media_type, data)) = dxf_image::data_url(&file)
+        .ok()
+        .and_then(|url| parse_data_url(&url))
+    else {
+        return;
+    };
+    let reference = assets.add(data, &media_type, false, Some(source.to_string()));
+    object.insert("imageData".to_string(), Value::String(reference));
+}
+
+/// Put the asset contents back in place of the references to `source` assets;
+/// other strings stay as they are, even if they look like references
+fn inline_references(value: &mut Value, source: &HashMap<String, (AssetEntry, Vec<u8>)>) {
+    match value {
+        Value::Object(object) => {
+            for value in object.values_mut() {
+                inline_references(value, source);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                inline_references(item, source);
+            }
+        }
+        Value::String(text) => {
+            let reference = text.strip_prefix(ASSET_PREFIX);
+            if let Some((entry, data)) = reference.and_then(|path| source.get(path)) {
+                *text = if entry.text {
+                    String::from_utf8_lossy(data).into_owned()
+                } else {
+                    format!("data:{};base64,{}", entry.media_type, STANDARD.encode(data))
+                };
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Media type and content of a base64 data URL
+fn parse_data_url(url: &str) -> Option<(String, Vec<u8>)> {
+    let (header, data) = url.strip_prefix("data:")?.split_once(',')?;
+    let media_type = header.strip_suffix(";base64")?;
+    let data = STANDARD.decode(data.trim()).ok()?;
+    let media_type = match media_type.split(';').next() {
+        Some(m) if !m.is_empty() => m,
+        _ => "application/octet-stream",
+    };
+    Some((media_type.to_string(), data))
+}
+
+fn extension(media_type: &str) -> &str {
+    match media_type {
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/bmp" => "bmp",
+        "image/tiff" => "tif",
+        "image/webp" => "webp",
+        "image/svg+xml" => "svg",
+        "application/pdf" => "pdf",
+        _ => "bin",
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn to_io(e: impl std::error::Error + Send + Sync + 'static) -> io::Error {
+    io::Error::other(e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::temp_dir;
+    use super::*;
+    use serde_json::json;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("{}_{}.o2d", name, std::process::id()))
+    }
+
+    fn project() -> (Value, Vec<u8>, String) {
+        let png: Vec<u8> = (0..MIN_ASSET_SIZE as u32)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let image = format!("data:image/png;base64,{}", STANDARD.encode(&png));
+        let tile = format!("<svg>{}</svg>", "<path/>".repeat(MIN_ASSET_SIZE));
+        let project = json!({
+            "version": 3,
+            "shapes": [
+                { "type": "image", "imageData": image },
+                { "type": "image", "imageData": image },
+                { "type": "text", "text": "o2d-asset:notes.txt" },
+            ],
+            "patterns": [{ "svgTile": tile }],
+        });
+        (project, png, tile)
+    }
+
+    #[test]
+    fn container_round_trip_keeps_references() {
+        let (project, png, tile) = project();
+        let path = temp_path("container_round_trip");
+        let bytes = write_container(&project.to_string(), None, None).unwrap();
+        fs::write(&path, bytes).unwrap();
+
+        let opened = open_project(&path).unwrap();
+        let json: Value = serde_json::from_str(&opened.json).unwrap();
+        let paths: Vec<&str> = opened.assets.iter().map(|a| a.path.as_str()).collect();
+        // Object keys are visited in order, so "patterns" comes before "shapes"
+        assert_eq!(paths, ["patterns/1.svg", "images/2.png"]);
+        assert_eq!(json["shapes"][0]["imageData"], "o2d-asset:images/2.png");
+        assert_eq!(json["shapes"][1]["imageData"], "o2d-asset:images/2.png");
+        // Text that only looks like a reference is left alone
+        assert_eq!(json["shapes"][2]["text"], "o2d-asset:notes.txt");
+        assert_eq!(read_asset(&path, "images/2.png").unwrap(), png);
+        assert!(read_asset(&path, "project.json").is_err());
+
+        // Saving the loaded JSON again takes the assets from the old container
+        let resaved = write_container(&opened.json, None, Some(&path)).unwrap();
+        let copy = temp_path("container_round_trip_copy");
+        fs::write(&copy, resaved).unwrap();
+        assert_eq!(read_asset(&copy, "images/2.png").unwrap(), png);
+        assert_eq!(
+            read_asset(&copy, "patterns/1.svg").unwrap(),
+            tile.as_bytes()
+        );
+
+        // Saving as plain JSON inlines them again
+        let inlined: Value =
+            serde_json::from_str(&inline_assets(&opened.json, Some(&copy)).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&copy).unwrap();
+        assert_eq!(inlined, project);
+    }
+
+    #[test]
+    fn plain_json_is_read_as_it_is() {
+        let path = temp_path("plain_project");
+        fs::write(&path, r#"{"version":3,"text":"o2d-asset:images/2.png"}"#).unwrap();
+        let opened = open_project(&path).unwrap();
+        let inlined = inline_assets(&opened.json, Some(&path)).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(opened.assets.is_empty());
+        assert_eq!(inlined, opened.json);
+    }
+
+    #[test]
+    fn linked_images_and_pdfs_are_embedded() {
+        let dir = temp_dir("container_linked");
+        let photo: Vec<u8> = (0..100u8).collect();
+        fs::write(dir.join("photo.png"), &photo).unwrap();
+        let pdf = b"%PDF-1.7".repeat(MIN_ASSET_SIZE);
+        let pdf_url = format!("data:application/pdf;base64,{}", STANDARD.encode(&pdf));
+        let project = json!({
+            "version": 3,
+            "shapes": [
+                { "type": "image", "sourcePath": "photo.png" },
+                { "type": "pdf", "pdfData": pdf_url },
+            ],
+        });
+        let path = dir.join("linked.o2d");
+        let bytes = write_container(&project.to_string(), Some(&dir), None).unwrap();
+        fs::write(&path, bytes).unwrap();
+        let opened = open_project(&path).unwrap();
+        let json: Value = serde_json::from_str(&opened.json).unwrap();
+        let image = read_asset(&path, "images/1.png").unwrap();
+        let document = read_asset(&path, "pdfs/2.pdf").unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let kinds: Vec<(&str, &str)> = opened
+            .assets
+            .iter()
+            .map(|a| (a.kind.as_str(), a.media_type.as_str()))
+            .collect();
+        assert_eq!(kinds, [("image", "image/png"), ("pdf", "application/pdf")]);
+        assert_eq!(opened.assets[0].source_path.as_deref(), Some("photo.png"));
+        assert_eq!(json["shapes"][0]["imageData"], "o2d-asset:images/1.png");
+        assert_eq!(json["shapes"][0]["sourcePath"], "photo.png");
+        assert_eq!(json["shapes"][1]["pdfData"], "o2d-asset:pdfs/2.pdf");
+        assert_eq!(image, photo);
+        assert_eq!(document, pdf);
+    }
+}
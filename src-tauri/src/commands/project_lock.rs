@@ -0,0 +1,417 @@
+//! Locking of open project files, for projects shared on network drives.
+//!
+//! Opening a project creates `<name>.lock` next to it with the user, host, process
+//! and time. Others opening the project while the lock exists get the lock owner
+//! instead, and may open it read-only. A lock left behind by a process of this
+//! host that is no longer running is stale and taken over. Processes of other
+//! hosts can't be checked: their locks are only broken when asked for, e.g. after
+//! the other computer crashed.
+//!
+//! Saving is refused for projects opened read-only and for projects that changed
+//! on disk since they were loaded or last saved, unless overwriting is asked for.
+
+use crate::file_watcher;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Who holds a lock, as written to the lock file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockInfo {
+    pub user: String,
+    pub host: String,
+    pub pid: u32,
+    /// Seconds since the Unix epoch
+    pub since: u64,
+}
+
+impl LockInfo {
+    fn current() -> Self {
+        Self {
+            user: whoami::username(),
+            host: whoami::fallible::hostname().unwrap_or_else(|_| "unknown".to_string()),
+            pid: std::process::id(),
+            since: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        }
+    }
+
+    fn is_same_host(&self, other: &LockInfo) -> bool {
+        self.host.eq_ignore_ascii_case(&other.host)
+    }
+
+    /// Held by this process
+    fn is_own(&self, current: &LockInfo) -> bool {
+        self.is_same_host(current) && self.pid == current.pid
+    }
+
+    /// Left behind by a process of this host that has ended. Processes on other
+    /// hosts can't be checked, so their locks are never stale.
+    fn is_stale(&self, current: &LockInfo) -> bool {
+        self.is_same_host(current) && self.pid != current.pid && !process_running(self.pid)
+    }
+}
+
+/// Why a project can't be opened for editing or saved
+#[derive(Debug)]
+pub enum LockError {
+    /// Locked by someone else
+    Locked(LockInfo),
+    /// Opened read-only
+    ReadOnly,
+    /// Changed on disk since it was loaded or saved
+    Changed,
+    Io(io::Error),
+}
+
+impl std::fmt::Display for LockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LockError::Locked(lock) => write!(
+                f,
+                "File is in use by {} on {} (process {})",
+                lock.user, lock.host, lock.pid
+            ),
+            LockError::ReadOnly => write!(f, "File was opened read-only"),
+            LockError::Changed => write!(f, "File was changed on disk since it was loaded"),
+            LockError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<io::Error> for LockError {
+    fn from(e: io::Error) -> Self {
+        LockError::Io(e)
+    }
+}
+
+/// Open settings of `load_file`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct LoadOptions {
+    /// Open without taking the lock, e.g. when someone else has the project open
+    pub read_only: bool,
+    /// Take over the lock of someone else, when the lock owner is known to be gone
+    pub break_lock: bool,
+}
+
+/// How a project was opened
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Opened {
+    pub read_only: bool,
+    /// A stale lock, or one broken with `break_lock`, was replaced
+    pub took_over: bool,
+    /// The project was already open, and is being reloaded
+    pub reopened: bool,
+}
+
+/// Open projects and the file state they were loaded with
+#[derive(Default)]
+pub struct ProjectLocks {
+    open: Mutex<HashMap<PathBuf, OpenProject>>,
+}
+
+struct OpenProject {
+    read_only: bool,
+    /// Modification time and size when loaded or last saved
+    stamp: Option<(SystemTime, u64)>,
+}
+
+impl ProjectLocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a project being loaded. Unless `read_only`, its lock is taken;
+    /// a lock held by someone else is returned as `LockError::Locked` unless
+    /// `break_lock`.
+    pub fn open(&self, path: &Path, options: &LoadOptions) -> Result<Opened, LockError> {
+        let key = key(path);
+        let read_only = options.read_only;
+        let mut opened = Opened {
+            read_only,
+            ..Opened::default()
+        };
+        if !read_only {
+            opened.took_over = acquire(path, options.break_lock)?;
+        }
+        let project = OpenProject {
+            read_only,
+            stamp: stamp(path),
+        };
+        if let Some(previous) = self.open.lock().unwrap().insert(key, project) {
+            opened.reopened = true;
+            // Reopened read-only after having it open for editing
+            if read_only && !previous.read_only {
+                release(path);
+            }
+        }
+        Ok(opened)
+    }
+
+    /// Check that `path` may be written. Projects saved under a new name are
+    /// locked first, and `true` is returned: if the save fails, `close` releases
+    /// that lock again. With `overwrite`, changes on disk are overwritten.
+    pub fn check_save(&self, path: &Path, overwrite: bool) -> Result<bool, LockError> {
+        let key = key(path);
+        let mut open = self.open.lock().unwrap();
+        match open.get(&key) {
+            Some(project) if project.read_only => Err(LockError::ReadOnly),
+            Some(project) if !overwrite && stamp(path) != project.stamp => Err(LockError::Changed),
+            Some(_) => Ok(false),
+            None => {
+                acquire(path, false)?;
+                open.insert(
+                    key,
+                    OpenProject {
+                        read_only: false,
+                        stamp: None,
+                    },
+                );
+                Ok(true)
+            }
+        }
+    }
+
+    /// Remember the file state after a save
+    pub fn saved(&self, path: &Path) {
+        if let Some(project) = self.open.lock().unwrap().get_mut(&key(path)) {
+            project.stamp = stamp(path);
+        }
+    }
+
+    /// Release the lock of a project saved under a new name, `from` being its
+    /// previous path
+    pub fn moved(&self, from: &Path, to: &Path) {
+        if key(from) != key(to) {
+            self.close(from);
+        }
+    }
+
+    /// Forget a closed project and release its lock
+    pub fn close(&self, path: &Path) -> bool {
+        match self.open.lock().unwrap().remove(&key(path)) {
+            Some(project) => {
+                if !project.read_only {
+                    release(path);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Release every lock, when the application exits
+    pub fn close_all(&self) {
+        for (path, project) in self.open.lock().unwrap().drain() {
+            if !project.read_only {
+                release(&path);
+            }
+        }
+    }
+}
+
+/// Current owner of the lock on `path`, if any
+fn read_lock(path: &Path) -> Option<LockInfo> {
+    let content = fs::read_to_string(lock_path(path)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Create the lock file, replacing a stale one, or any lock with `break_lock`.
+/// Returns whether a lock was taken over.
+fn acquire(path: &Path, break_lock: bool) -> Result<bool, LockError> {
+    let current = LockInfo::current();
+    let lock_file = lock_path(path);
+    let mut took_over = false;
+    if let Some(existing) = read_lock(path) {
+        if existing.is_own(&current) {
+            return Ok(false);
+        }
+        if !break_lock && !existing.is_stale(&current) {
+            return Err(LockError::Locked(existing));
+        }
+        fs::remove_file(&lock_file)?;
+        took_over = true;
+    } else if lock_file.exists() {
+        // Unreadable: probably being written by another process right now
+        return Err(LockError::Io(io::Error::new(
+            io::ErrorKind::WouldBlock,
+            format!("{} is unreadable", lock_file.display()),
+        )));
+    }
+
+    // `create_new` fails if someone else created the lock in the meantime
+    let mut file = match OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&lock_file)
+    {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            return Err(match read_lock(path) {
+                Some(existing) => LockError::Locked(existing),
+                None => LockError::Io(e),
+            })
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let json = serde_json::to_vec_pretty(&current).map_err(io::Error::other)?;
+    file.write_all(&json)?;
+    file.sync_all()?;
+    Ok(took_over)
+}
+
+/// Remove the lock file if this process holds it
+fn release(path: &Path) {
+    if read_lock(path).is_some_and(|lock| lock.is_own(&LockInfo::current())) {
+        let _ = fs::remove_file(lock_path(path));
+    }
+}
+
+/// `<name>.lock` next to the project
+fn lock_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".lock");
+    path.with_file_name(name)
+}
+
+/// The same key for every way of writing the path, before and after the file
+/// exists, so projects saved under a new name are found again
+fn key(path: &Path) -> PathBuf {
+    file_watcher::normalize(path)
+}
+
+/// Modification time and size, to notice changes by others
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Whether a process with this ID is running on this host. Assumed running
+/// when it can't be determined, so locks are not broken by mistake.
+fn process_running(pid: u32) -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        Path::new("/proc").join(pid.to_string()).exists()
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        match std::process::Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {}", pid), "/NH", "/FO", "CSV"])
+            .output()
+        {
+            Ok(output) => String::from_utf8_lossy(&output.stdout).contains(&format!("\"{}\"", pid)),
+            Err(_) => true,
+        }
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    {
+        // "Operation not permitted": running, but owned by another user
+        match std::process::Command::new("kill")
+            .args(["-0", &pid.to_string()])
+            .output()
+        {
+            Ok(output) => {
+                output.status.success()
+                    || String::from_utf8_lossy(&output.stderr).contains("not permitted")
+            }
+            Err(_) => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::temp_dir;
+    use super::*;
+
+    #[test]
+    fn save_as_moves_the_lock() {
+        let dir = temp_dir("project_lock_save_as");
+        let old = dir.join("a.o2d");
+        // Written the way a file dialog might, through another directory
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        let new = dir.join("sub").join("..").join("b.o2d");
+        fs::write(&old, "{}").unwrap();
+        let locks = ProjectLocks::new();
+        locks.open(&old, &LoadOptions::default()).unwrap();
+
+        // The new file's key must not change once it exists
+        let key_before = key(&new);
+        let locked_here = locks.check_save(&new, false).unwrap();
+        fs::write(&new, "{}").unwrap();
+        locks.saved(&new);
+        locks.moved(&old, &new);
+        assert_eq!(key(&new), key_before);
+        let saved_again = locks.check_save(&new, false);
+        let old_locked = lock_path(&old).exists();
+        let new_locked = lock_path(&new).exists();
+        locks.close_all();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(locked_here);
+        assert!(matches!(saved_again, Ok(false)), "{:?}", saved_again);
+        assert!(!old_locked);
+        assert!(new_locked);
+    }
+
+    #[test]
+    fn failed_save_as_and_reload_keep_the_locks_consistent() {
+        let dir = temp_dir("project_lock_failures");
+        let path = dir.join("a.o2d");
+        let new = dir.join("b.o2d");
+        fs::write(&path, "{}").unwrap();
+        let locks = ProjectLocks::new();
+        let first = locks.open(&path, &LoadOptions::default()).unwrap();
+        let reload = locks.open(&path, &LoadOptions::default()).unwrap();
+
+        // Save As fails after the check: the new name is given up again
+        let locked_here = locks.check_save(&new, false).unwrap();
+        let released = locks.close(&new);
+        let new_locked = lock_path(&new).exists();
+        let path_locked = lock_path(&path).exists();
+        locks.close_all();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(!first.reopened);
+        assert!(reload.reopened);
+        assert!(locked_here && released);
+        assert!(!new_locked);
+        assert!(path_locked);
+    }
+
+    #[test]
+    fn locks_of_other_hosts_are_only_broken_when_asked() {
+        let dir = temp_dir("project_lock_break");
+        let path = dir.join("a.o2d");
+        fs::write(&path, "{}").unwrap();
+        let other = LockInfo {
+            host: format!("{}-elsewhere", LockInfo::current().host),
+            ..LockInfo::current()
+        };
+        fs::write(lock_path(&path), serde_json::to_vec(&other).unwrap()).unwrap();
+
+        let locks = ProjectLocks::new();
+        let refused = locks.open(&path, &LoadOptions::default());
+        let options = LoadOptions {
+            break_lock: true,
+            ..Default::default()
+        };
+        let broken = locks.open(&path, &options);
+        let owner = read_lock(&path);
+        locks.close_all();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(refused, Err(LockError::Locked(lock)) if lock == other));
+        assert!(broken.unwrap().took_over);
+        assert_eq!(owner.unwrap().host, LockInfo::current().host);
+    }
+}
@@ -0,0 +1,175 @@
+//! Shape exchange model shared by the DXF import and export commands.
+//!
+//! Coordinates are DXF world coordinates (Y up) and angles are in radians,
+//! except hatch pattern angles, which are in degrees, matching the frontend
+//! shape model in `src/types/geometry.ts`.
+
+use super::dxf_blocks::{BlockData, GroupData};
+use super::dxf_dimensions::DimensionStyleData;
+use super::dxf_hatch::HatchPatternData;
+use super::dxf_layers::LayerData;
+use super::dxf_layouts::SheetData;
+use super::dxf_linetypes::LineTypeData;
+use super::dxf_report::ConversionReport;
+use super::dxf_xdata::DxfEntityData;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ShapeData {
+    /// Frontend shape id, echoed in export reports
+    pub id: Option<String>,
+    pub shape_type: String,
+    /// Layer name; `None` places the shape on layer "0"
+    pub layer: Option<String>,
+    /// `#rrggbb` entity colour; `None` means ByLayer
+    pub color: Option<String>,
+    /// DXF linetype name or project line style; `None` means ByLayer
+    pub line_type: Option<String>,
+    /// Per-entity linetype scale (DXF group 48)
+    pub line_type_scale: Option<f64>,
+    pub start: Option<PointData>,
+    pub end: Option<PointData>,
+    pub center: Option<PointData>,
+    pub radius: Option<f64>,
+    pub points: Option<Vec<PointData>>,
+    /// Arc / elliptical arc start angle (radians, parametric for ellipses)
+    pub start_angle: Option<f64>,
+    /// Arc / elliptical arc end angle (radians, parametric for ellipses)
+    pub end_angle: Option<f64>,
+    pub radius_x: Option<f64>,
+    pub radius_y: Option<f64>,
+    /// Rotation in radians
+    pub rotation: Option<f64>,
+    pub closed: Option<bool>,
+    /// Bulge per polyline segment (DXF convention, segment i starts at point i)
+    pub bulge: Option<Vec<f64>>,
+    /// Spline degree (defaults to 3)
+    pub degree: Option<i32>,
+    /// Spline knot vector; a clamped uniform vector is generated when missing
+    pub knots: Option<Vec<f64>>,
+    /// Spline control point weights (rational splines only)
+    pub weights: Option<Vec<f64>>,
+    pub position: Option<PointData>,
+    pub text: Option<String>,
+    /// Text height in drawing units
+    pub font_size: Option<f64>,
+    pub font_family: Option<String>,
+    /// "left" | "center" | "right"
+    pub alignment: Option<String>,
+    /// "top" | "middle" | "bottom"
+    pub vertical_alignment: Option<String>,
+    pub bold: Option<bool>,
+    pub italic: Option<bool>,
+    pub underline: Option<bool>,
+    pub strikethrough: Option<bool>,
+    pub width_factor: Option<f64>,
+    /// Oblique (slant) angle in degrees
+    pub oblique_angle: Option<f64>,
+    /// Wrapping width for multiline text
+    pub fixed_width: Option<f64>,
+    /// Project text style name, written as the DXF STYLE table entry
+    pub text_style_name: Option<String>,
+    /// Block definition referenced by a "block" shape
+    pub block_name: Option<String>,
+    pub scale_x: Option<f64>,
+    pub scale_y: Option<f64>,
+    /// Key/value metadata, e.g. ATTRIB tag -> value
+    pub attributes: Option<BTreeMap<String, String>>,
+    /// Group the shape belongs to (exploded INSERTs form one group each)
+    pub group_id: Option<String>,
+    /// "linear" | "aligned" | "angular" | "radius" | "diameter"
+    pub dimension_type: Option<String>,
+    /// "horizontal" | "vertical" for linear dimensions
+    pub linear_direction: Option<String>,
+    /// Distance from the measured points to the dimension line, positive to the
+    /// left of points[0] -> points[1] (up / right for linear dimensions)
+    pub dimension_line_offset: Option<f64>,
+    /// Displayed dimension text
+    pub value: Option<String>,
+    pub value_overridden: Option<bool>,
+    pub dimension_style: Option<DimensionStyleData>,
+    pub dimension_style_name: Option<String>,
+    /// Text position relative to its default position on the dimension line
+    pub text_offset: Option<PointData>,
+    /// "solid" | "diagonal" | "crosshatch" | "horizontal" | "vertical" | "dots" | "custom"
+    pub pattern_type: Option<String>,
+    /// Pattern rotation in degrees
+    pub pattern_angle: Option<f64>,
+    pub pattern_scale: Option<f64>,
+    pub custom_pattern_id: Option<String>,
+    /// Background pattern drawn behind the foreground pattern
+    pub bg_pattern_type: Option<String>,
+    pub bg_pattern_angle: Option<f64>,
+    pub bg_pattern_scale: Option<f64>,
+    pub bg_fill_color: Option<String>,
+    pub bg_custom_pattern_id: Option<String>,
+    /// Solid fill behind all patterns
+    pub background_color: Option<String>,
+    /// Holes in a hatch, as closed polygons
+    pub inner_loops: Option<Vec<Vec<PointData>>>,
+    pub boundary_visible: Option<bool>,
+    /// Image size in drawing units; `position` is the top-left corner
+    pub width: Option<f64>,
+    pub height: Option<f64>,
+    /// Embedded image as a base64 data URL
+    pub image_data: Option<String>,
+    /// Image file the embedded data was read from
+    pub source_path: Option<String>,
+    pub source_file_name: Option<String>,
+    /// Image size in pixels
+    pub original_width: Option<f64>,
+    pub original_height: Option<f64>,
+    /// 0 (transparent) to 1
+    pub opacity: Option<f64>,
+    /// Drawn behind all other shapes
+    pub is_underlay: Option<bool>,
+    /// Image clipping boundary as a closed polygon
+    pub clip_points: Option<Vec<PointData>>,
+    /// Wall or slab thickness, beam flange width. The centerline bulge of walls
+    /// and beams is the first `bulge` value; labels of elements are in `text`.
+    pub thickness: Option<f64>,
+    /// "center" | "left" | "right" of the centerline (beams also "top" | "bottom")
+    pub justification: Option<String>,
+    pub show_centerline: Option<bool>,
+    /// Cross inside a pile circle
+    pub show_cross: Option<bool>,
+    /// Gridline bubble: "start" | "end" | "both"
+    pub bubble_position: Option<String>,
+    /// Gridline bubble radius, level marker size
+    pub bubble_radius: Option<f64>,
+    /// Gridline overshoot beyond start and end
+    pub extension: Option<f64>,
+    /// Wall and slab hatch colour; the element colour when missing
+    pub pattern_color: Option<String>,
+    /// Element a shape was exploded from on export (see `dxf_elements`)
+    pub element_type: Option<String>,
+    /// XDATA and extension dictionary of the source DXF entity, written back on export
+    pub dxf_data: Option<DxfEntityData>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct PointData {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// Result of a DXF import: tables, block definitions and the converted shapes
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DxfDocument {
+    pub layers: Vec<LayerData>,
+    pub line_types: Vec<LineTypeData>,
+    pub blocks: Vec<BlockData>,
+    pub groups: Vec<GroupData>,
+    /// Definitions of the custom patterns used by imported hatches
+    pub hatch_patterns: Vec<HatchPatternData>,
+    pub shapes: Vec<ShapeData>,
+    /// Paper space layouts
+    pub sheets: Vec<SheetData>,
+    /// Base point subtracted from model space coordinates, in project millimetres;
+    /// kept with the project and passed back to `export_dxf` to restore the
+    /// original coordinates
+    pub origin: Option<PointData>,
+    /// What was converted, approximated or skipped
+    pub report: ConversionReport,
+}
@@ -0,0 +1,12 @@
+//! Helpers shared by the tests of the command modules
+
+use std::fs;
+use std::path::PathBuf;
+
+/// An empty directory of its own for a test, named after it
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,391 @@
+//! File Watcher - tells the webview when the open project or a file it references
+//! changes on disk, e.g. after a colleague saved it or a git pull.
+//!
+//! The directories of the tracked files are watched rather than the files, so
+//! files replaced by a rename (atomic saves, git checkouts) keep being seen.
+//! Events are debounced and emitted as:
+//! - `file-changed`: `{ path, role }`
+//! - `file-deleted`: `{ path, role }`
+//! - `file-renamed`: `{ path, newPath, role }`, after which the new path is tracked
+//!
+//! `role` is "project" or "reference". Writes of this instance are announced with
+//! `ignore_own_write` and not reported.
+
+use notify_debouncer_full::notify::event::{ModifyKind, RenameMode};
+use notify_debouncer_full::notify::{EventKind, RecommendedWatcher, RecursiveMode};
+use notify_debouncer_full::{
+    new_debouncer, DebounceEventResult, DebouncedEvent, Debouncer, RecommendedCache,
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tauri::{AppHandle, Emitter};
+
+/// Quiet time before changes are reported; editors and git write in bursts
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Keys in the project JSON that hold paths of referenced files
+const REFERENCE_KEYS: [&str; 1] = ["sourcePath"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Project,
+    Reference,
+}
+
+impl Role {
+    fn as_str(self) -> &'static str {
+        match self {
+            Role::Project => "project",
+            Role::Reference => "reference",
+        }
+    }
+}
+
+/// Payload of the events sent to the webview
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FileEvent {
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    new_path: Option<String>,
+    role: &'static str,
+}
+
+/// Modification time and size
+type Stamp = (SystemTime, u64);
+
+/// Files being tracked, shared with the debouncer thread
+#[derive(Default)]
+struct Tracked {
+    files: HashMap<PathBuf, Role>,
+    /// File state after writes of this instance
+    own_writes: HashMap<PathBuf, Stamp>,
+}
+
+struct Watching {
+    debouncer: Debouncer<RecommendedWatcher, RecommendedCache>,
+    dirs: HashSet<PathBuf>,
+}
+
+/// Watcher for the open project, managed as tauri state
+#[derive(Default)]
+pub struct FileWatcher {
+    tracked: Arc<Mutex<Tracked>>,
+    watching: Mutex<Option<Watching>>,
+}
+
+impl FileWatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Track `project` and the files it references, replacing what was tracked
+    /// before. Returns the number of referenced files.
+    pub fn watch_project(
+        &self,
+        app: &AppHandle,
+        project: &Path,
+        project_json: &str,
+        extra: &[PathBuf],
+    ) -> notify_debouncer_full::notify::Result<usize> {
+        let project = normalize(project);
+        let base_dir = project.parent().unwrap_or(Path::new("."));
+        let mut references = references(project_json, base_dir);
+        references.extend(extra.iter().map(|path| normalize(path)));
+        references.retain(|path| *path != project);
+        references.sort();
+        references.dedup();
+
+        let mut files = HashMap::new();
+        files.insert(project.clone(), Role::Project);
+        for reference in &references {
+            files.insert(reference.clone(), Role::Reference);
+        }
+        let dirs: HashSet<PathBuf> = files
+            .keys()
+            .filter_map(|path| path.parent())
+            .filter(|dir| dir.is_dir())
+            .map(Path::to_path_buf)
+            .collect();
+
+        let mut watching = self.watching.lock().unwrap();
+        if watching.is_none() {
+            let tracked = self.tracked.clone();
+            let app = app.clone();
+            let debouncer =
+                new_debouncer(
+                    DEBOUNCE,
+                    None,
+                    move |result: DebounceEventResult| match result {
+                        Ok(events) => {
+                            let changes: Vec<_> = {
+                                let mut tracked = tracked.lock().unwrap();
+                                events
+                                    .iter()
+                                    .flat_map(|event| tracked.classify(event))
+                                    .collect()
+                            };
+                            for (name, payload) in changes {
+                                let _ = app.emit(name, payload);
+                            }
+                        }
+                        Err(errors) => {
+                            for e in errors {
+                                eprintln!("[FileWatcher] {}", e);
+                            }
+                        }
+                    },
+                )?;
+            *watching = Some(Watching {
+                debouncer,
+                dirs: HashSet::new(),
+            });
+        }
+        let watching = watching.as_mut().unwrap();
+        for dir in watching.dirs.difference(&dirs) {
+            let _ = watching.debouncer.unwatch(dir);
+        }
+        for dir in dirs.difference(&watching.dirs) {
+            watching.debouncer.watch(dir, RecursiveMode::NonRecursive)?;
+        }
+        watching.dirs = dirs;
+
+        let mut tracked = self.tracked.lock().unwrap();
+        tracked
+            .own_writes
+            .retain(|path, _| files.contains_key(path));
+        tracked.files = files;
+        Ok(references.len())
+    }
+
+    /// Stop watching, when the project is closed
+    pub fn unwatch(&self) {
+        if let Some(watching) = self.watching.lock().unwrap().take() {
+            watching.debouncer.stop();
+        }
+        *self.tracked.lock().unwrap() = Tracked::default();
+    }
+
+    /// Don't report the change this instance just made to `path`
+    pub fn ignore_own_write(&self, path: &Path) {
+        let path = normalize(path);
+        if let Some(stamp) = stamp(&path) {
+            self.tracked.lock().unwrap().own_writes.insert(path, stamp);
+        }
+    }
+}
+
+impl Tracked {
+    /// Events to emit for one file system event
+    fn classify(&mut self, event: &DebouncedEvent) -> Vec<(&'static str, FileEvent)> {
+        let mut changes = Vec::new();
+        match event.kind {
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+                let (from, to) = (&event.paths[0], &event.paths[1]);
+                if let Some(role) = self.files.remove(from) {
+                    self.files.insert(to.clone(), role);
+                    changes.push(("file-renamed", file_event(from, Some(to), role)));
+                } else if let Some(change) = self.changed(to) {
+                    // Replaced by a rename, as atomic saves do
+                    changes.push(change);
+                }
+            }
+            EventKind::Modify(ModifyKind::Name(_)) => {
+                for path in &event.paths {
+                    let change = if path.exists() {
+                        self.changed(path)
+                    } else {
+                        self.deleted(path)
+                    };
+                    changes.extend(change);
+                }
+            }
+            EventKind::Create(_) | EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Any) => {
+                changes.extend(event.paths.iter().filter_map(|path| self.changed(path)));
+            }
+            EventKind::Remove(_) => {
+                changes.extend(event.paths.iter().filter_map(|path| self.deleted(path)));
+            }
+            _ => {}
+        }
+        changes
+    }
+
+    fn changed(&mut self, path: &Path) -> Option<(&'static str, FileEvent)> {
+        let role = *self.files.get(path)?;
+        let current = stamp(path);
+        if current.is_some() && self.own_writes.get(path) == current.as_ref() {
+            return None;
+        }
+        self.own_writes.remove(path);
+        Some(("file-changed", file_event(path, None, role)))
+    }
+
+    fn deleted(&mut self, path: &Path) -> Option<(&'static str, FileEvent)> {
+        let role = *self.files.get(path)?;
+        self.own_writes.remove(path);
+        Some(("file-deleted", file_event(path, None, role)))
+    }
+}
+
+fn file_event(path: &Path, new_path: Option<&Path>, role: Role) -> FileEvent {
+    FileEvent {
+        path: path.to_string_lossy().into_owned(),
+        new_path: new_path.map(|p| p.to_string_lossy().into_owned()),
+        role: role.as_str(),
+    }
+}
+
+/// Paths of the files the project references, resolved against its directory
+pub fn references(project_json: &str, base_dir: &Path) -> Vec<PathBuf> {
+    fn collect(value: &Value, base_dir: &Path, found: &mut Vec<PathBuf>) {
+        match value {
+            Value::Object(object) => {
+                for (key, value) in object {
+                    match value.as_str() {
+                        Some(path)
+                            if !path.is_empty() && REFERENCE_KEYS.contains(&key.as_str()) =>
+                        {
+                            found.push(normalize(&base_dir.join(path)));
+                        }
+                        _ => collect(value, base_dir, found),
+                    }
+                }
+            }
+            Value::Array(items) => {
+                for item in items {
+                    collect(item, base_dir, found);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut found = Vec::new();
+    if let Ok(project) = serde_json::from_str::<Value>(project_json) {
+        collect(&project, base_dir, &mut found);
+    }
+    found
+}
+
+/// Absolute path with its directory resolved, as the watcher reports paths. The
+/// file itself may not exist (yet).
+pub fn normalize(path: &Path) -> PathBuf {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir().unwrap_or_default().join(path)
+    };
+    match (absolute.parent(), absolute.file_name()) {
+        (Some(dir), Some(name)) => std::fs::canonicalize(dir)
+            .map(|dir| strip_verbatim(dir).join(name))
+            .unwrap_or(absolute),
+        _ => absolute,
+    }
+}
+
+/// `canonicalize` returns `\\?\C:\...` paths on Windows; drive paths are kept
+/// without the prefix, as users and other APIs write them
+fn strip_verbatim(path: PathBuf) -> PathBuf {
+    match path.to_str().and_then(|p| p.strip_prefix(r"\\?\")) {
+        Some(rest) if !rest.starts_with("UNC\\") => PathBuf::from(rest),
+        _ => path,
+    }
+}
+
+fn stamp(path: &Path) -> Option<Stamp> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify_debouncer_full::notify::event::{CreateKind, DataChange, RemoveKind};
+    use notify_debouncer_full::notify::Event;
+    use std::fs;
+    use std::time::Instant;
+
+    fn event(kind: EventKind, paths: &[&Path]) -> DebouncedEvent {
+        let event = paths.iter().fold(Event::new(kind), |event, path| {
+            event.add_path(path.to_path_buf())
+        });
+        DebouncedEvent::new(event, Instant::now())
+    }
+
+    fn names(changes: &[(&'static str, FileEvent)]) -> Vec<(&'static str, &'static str)> {
+        changes
+            .iter()
+            .map(|(name, event)| (*name, event.role))
+            .collect()
+    }
+
+    #[test]
+    fn classifies_changes_of_tracked_files() {
+        let temp = |name: &str| {
+            let path =
+                std::env::temp_dir().join(format!("watcher_{}_{}", name, std::process::id()));
+            normalize(&path)
+        };
+        let (project, reference, renamed, other) = (
+            temp("a.o2d"),
+            temp("photo.png"),
+            temp("moved.png"),
+            temp("other.txt"),
+        );
+        fs::write(&project, "{}").unwrap();
+        fs::write(&reference, "png").unwrap();
+        let mut tracked = Tracked::default();
+        tracked.files.insert(project.clone(), Role::Project);
+        tracked.files.insert(reference.clone(), Role::Reference);
+        let modified = EventKind::Modify(ModifyKind::Data(DataChange::Content));
+        let renamed_both = EventKind::Modify(ModifyKind::Name(RenameMode::Both));
+
+        let changed = tracked.classify(&event(modified, &[&project]));
+        let untracked = tracked.classify(&event(EventKind::Create(CreateKind::File), &[&other]));
+
+        // Writes of this instance are ignored until someone else changes the file
+        tracked
+            .own_writes
+            .insert(project.clone(), stamp(&project).unwrap());
+        let own = tracked.classify(&event(modified, &[&project]));
+        fs::write(&project, "{\"version\":3}").unwrap();
+        let foreign = tracked.classify(&event(modified, &[&project]));
+
+        // Atomic saves replace the project by renaming a temp file onto it
+        let replaced = tracked.classify(&event(renamed_both, &[&other, &project]));
+
+        fs::rename(&reference, &renamed).unwrap();
+        let moved = tracked.classify(&event(renamed_both, &[&reference, &renamed]));
+        let moved_changed = tracked.classify(&event(modified, &[&renamed]));
+        fs::remove_file(&project).unwrap();
+        fs::remove_file(&renamed).unwrap();
+        let deleted = tracked.classify(&event(EventKind::Remove(RemoveKind::File), &[&project]));
+
+        assert_eq!(names(&changed), [("file-changed", "project")]);
+        assert!(untracked.is_empty());
+        assert!(own.is_empty());
+        assert_eq!(names(&foreign), [("file-changed", "project")]);
+        assert_eq!(names(&replaced), [("file-changed", "project")]);
+        assert_eq!(names(&moved), [("file-renamed", "reference")]);
+        let new_path = renamed.to_string_lossy().into_owned();
+        assert_eq!(moved[0].1.new_path.as_deref(), Some(new_path.as_str()));
+        assert_eq!(names(&moved_changed), [("file-changed", "reference")]);
+        assert_eq!(names(&deleted), [("file-deleted", "project")]);
+    }
+
+    #[test]
+    fn finds_references_in_the_project() {
+        let base = Path::new("/projects/house");
+        let json = r#"{"shapes":[{"sourcePath":"img/plan.png"},{"sourcePath":""}],
+            "underlay":{"sourcePath":"/scans/site.pdf"}}"#;
+        let found = references(json, base);
+        assert_eq!(found.len(), 2);
+        assert!(found[0].ends_with("img/plan.png"));
+        assert!(found[1].ends_with("site.pdf"));
+    }
+}
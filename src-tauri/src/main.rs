@@ -3,8 +3,11 @@
 
 mod commands;
 mod api_server;
+mod file_watcher;
+mod project_schema;
 
-use commands::{save_file, load_file, export_dxf, import_dxf, execute_shell, open_file_with_default_app, print_file, get_printers, open_printer_properties};
+use commands::{save_file, load_file, load_project_asset, close_project, watch_project, unwatch_project, validate_project, export_dxf, export_dxf_project, import_dxf, start_dxf_import, cancel_dxf_import, inspect_dxf, DxfImportJobs, ProjectLocks, execute_shell, open_file_with_default_app, print_file, get_printers, open_printer_properties};
+use file_watcher::FileWatcher;
 use api_server::{ApiServerState, find_free_port, write_discovery_file, remove_discovery_file, start_server};
 use std::sync::Arc;
 use tauri::Manager;
@@ -15,9 +18,54 @@ fn api_eval_callback(eval_id: String, result: String, state: tauri::State<'_, Ar
     state.deliver_result(&eval_id, result);
 }
 
+/// Print the validation report of a project file as JSON. Exit code 0 when the
+/// project is valid, 1 when it has broken references, 2 when it can't be loaded.
+/// `cmd.exe` doesn't wait for GUI applications; `start /wait` gets the exit code.
+fn validate_project_cli(path: &str) -> i32 {
+    let report = match commands::read_project(std::path::Path::new(path)) {
+        Ok(json) => project_schema::validate(&json),
+        Err(e) => project_schema::ValidationReport::unreadable(format!("Failed to load file: {}", e)),
+    };
+    println!("{}", serde_json::to_string_pretty(&report).unwrap_or_default());
+    match (report.valid, report.issues.is_empty()) {
+        (true, true) => 0,
+        (true, false) => 1,
+        (false, _) => 2,
+    }
+}
+
+/// Release builds are GUI applications on Windows and start without a console.
+/// Attach to the console of the shell the app was started from, so the report of
+/// `--validate-project` is printed there; when the output is redirected, it goes
+/// to the redirection either way.
+#[cfg(windows)]
+fn attach_parent_console() {
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+    // Fails without a parent console, e.g. when started from Explorer
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
 fn main() {
     // Parse --api-port from command line args
     let args: Vec<String> = std::env::args().collect();
+
+    // Check a project file and exit: --validate-project <path>
+    if let Some(path) = args
+        .iter()
+        .position(|a| a == "--validate-project")
+        .and_then(|i| args.get(i + 1))
+    {
+        #[cfg(windows)]
+        attach_parent_console();
+        std::process::exit(validate_project_cli(path));
+    }
+
     let requested_port: Option<u16> = args
         .iter()
         .position(|a| a == "--api-port")
@@ -37,6 +85,9 @@ fn main() {
 
     tauri::Builder::default()
         .manage(api_state.clone())
+        .manage(Arc::new(DxfImportJobs::new()))
+        .manage(Arc::new(ProjectLocks::new()))
+        .manage(Arc::new(FileWatcher::new()))
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
@@ -47,8 +98,17 @@ fn main() {
         .invoke_handler(tauri::generate_handler![
             save_file,
             load_file,
+            load_project_asset,
+            close_project,
+            watch_project,
+            unwatch_project,
+            validate_project,
             export_dxf,
+            export_dxf_project,
             import_dxf,
+            start_dxf_import,
+            cancel_dxf_import,
+            inspect_dxf,
             execute_shell,
             open_file_with_default_app,
             print_file,
@@ -67,9 +127,12 @@ fn main() {
 
             Ok(())
         })
-        .on_window_event(|_window, event| {
+        .on_window_event(|window, event| {
             if let tauri::WindowEvent::Destroyed = event {
                 remove_discovery_file();
+                // Others may edit the projects once this instance is gone
+                window.state::<Arc<ProjectLocks>>().close_all();
+                window.state::<Arc<FileWatcher>>().unwatch();
             }
         })
         .run(tauri::generate_context!())
@@ -0,0 +1,754 @@
+//! Project file schema - versions, migrations and validation of `.o2d` projects.
+//!
+//! Mirrors `ProjectFileV1`/`V2`/`V3` and their migrations in `fileService.ts`, so
+//! the backend, the command line and the API server can check project files the
+//! same way the webview reads them:
+//!
+//! 1. The JSON is parsed and its `version` checked
+//! 2. Older versions are migrated to the current one
+//! 3. The typed model below is deserialized, which reports schema errors with the
+//!    JSON path (and line and column when no migration was needed)
+//! 4. References between drawings, layers, shapes and sheets are checked
+//!
+//! The typed model only covers what is checked; the project JSON itself is passed
+//! on unchanged apart from migrations, so fields unknown here are never lost.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// `FILE_FORMAT_VERSION` in `fileService.ts`
+pub const CURRENT_VERSION: u64 = 3;
+
+/// Annotation text scale before V3 (`REFERENCE_SCALE` in `migrateV2ToV3`)
+const REFERENCE_SCALE: f64 = 0.02;
+
+/// `CAD_DEFAULT_FONT` in `cadDefaults.ts`
+const DEFAULT_FONT: &str = "Osifont";
+
+/// `DEFAULT_DRAWING_SCALE` in `fileService.ts` (1:50)
+const DEFAULT_DRAWING_SCALE: f64 = 0.02;
+
+// ============================================================================
+// Typed model (V3). Most fields are only deserialized to check their types.
+// ============================================================================
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[allow(dead_code)]
+struct ProjectFile {
+    version: u64,
+    name: String,
+    /// Written as "drafts" by older versions
+    #[serde(alias = "drafts")]
+    drawings: Vec<Drawing>,
+    #[serde(default)]
+    sheets: Vec<Sheet>,
+    #[serde(alias = "activeDraftId")]
+    active_drawing_id: Option<String>,
+    active_sheet_id: Option<String>,
+    #[serde(default, alias = "draftViewports")]
+    drawing_viewports: BTreeMap<String, Viewport>,
+    #[serde(default)]
+    sheet_viewports: BTreeMap<String, Viewport>,
+    shapes: Vec<Shape>,
+    layers: Vec<Layer>,
+    active_layer_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[allow(dead_code)]
+struct Drawing {
+    id: String,
+    name: String,
+    boundary: Option<Boundary>,
+    scale: Option<f64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[allow(dead_code)]
+struct Boundary {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[allow(dead_code)]
+struct Layer {
+    id: String,
+    name: String,
+    #[serde(alias = "draftId")]
+    drawing_id: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[allow(dead_code)]
+struct Shape {
+    id: String,
+    #[serde(rename = "type")]
+    shape_type: String,
+    layer_id: String,
+    #[serde(alias = "draftId")]
+    drawing_id: String,
+    // Geometry shared by many shape types, checked when present
+    start: Option<Point>,
+    end: Option<Point>,
+    center: Option<Point>,
+    position: Option<Point>,
+    points: Option<Vec<Point>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[allow(dead_code)]
+struct Point {
+    x: f64,
+    y: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[allow(dead_code)]
+struct Viewport {
+    offset_x: f64,
+    offset_y: f64,
+    zoom: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[allow(dead_code)]
+struct Sheet {
+    id: String,
+    name: String,
+    #[serde(default)]
+    viewports: Vec<SheetViewport>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[allow(dead_code)]
+struct SheetViewport {
+    id: String,
+    drawing_id: String,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+    center_x: f64,
+    center_y: f64,
+    scale: f64,
+    #[serde(default)]
+    layer_overrides: Vec<LayerOverride>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LayerOverride {
+    layer_id: String,
+}
+
+// ============================================================================
+// Results
+// ============================================================================
+
+/// Problem that makes a project unreadable
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectError {
+    /// JSON path such as `shapes[12].start.x`, if known
+    pub path: Option<String>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl ProjectError {
+    fn new(path: Option<&str>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.map(str::to_string),
+            line: None,
+            column: None,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ProjectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.path, self.line, self.column) {
+            (Some(path), Some(line), Some(column)) => write!(
+                f,
+                "{} at {} (line {}, column {})",
+                self.message, path, line, column
+            ),
+            (Some(path), _, _) => write!(f, "{} at {}", self.message, path),
+            (None, Some(line), Some(column)) => {
+                write!(f, "{} (line {}, column {})", self.message, line, column)
+            }
+            _ => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Inconsistency the project can still be opened with
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectIssue {
+    pub path: String,
+    pub message: String,
+}
+
+/// Project read and migrated to `CURRENT_VERSION`
+#[derive(Debug, Clone)]
+pub struct LoadedProject {
+    /// Project JSON in the current version
+    pub json: String,
+    /// Version the file was written in
+    pub version: u64,
+    pub issues: Vec<ProjectIssue>,
+}
+
+impl LoadedProject {
+    pub fn migrated(&self) -> bool {
+        self.version < CURRENT_VERSION
+    }
+}
+
+/// Report of `validate`, as returned by the CLI and the API server
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationReport {
+    pub valid: bool,
+    pub version: Option<u64>,
+    pub migrated: bool,
+    pub error: Option<ProjectError>,
+    pub issues: Vec<ProjectIssue>,
+}
+
+// ============================================================================
+// Loading
+// ============================================================================
+
+/// Check, migrate and validate project JSON
+pub fn load(json: &str) -> Result<LoadedProject, ProjectError> {
+    let mut value: Value = serde_json::from_str(json).map_err(|e| ProjectError {
+        path: None,
+        line: Some(e.line()),
+        column: Some(e.column()),
+        message: format!("Invalid JSON: {}", schema_message(&e)),
+    })?;
+    let version = match value.get("version") {
+        Some(version) => version
+            .as_u64()
+            .filter(|&v| v >= 1)
+            .ok_or_else(|| ProjectError::new(Some("version"), "Invalid file format version"))?,
+        None => {
+            return Err(ProjectError::new(
+                Some("version"),
+                "Missing file format version",
+            ))
+        }
+    };
+    if version > CURRENT_VERSION {
+        return Err(ProjectError::new(
+            Some("version"),
+            format!(
+                "Unsupported file format version: {} (this version reads up to {})",
+                version, CURRENT_VERSION
+            ),
+        ));
+    }
+
+    // Chain migrations: V1 → V2 → V3
+    if version == 1 {
+        migrate_v1_to_v2(&mut value)?;
+    }
+    if version <= 2 {
+        migrate_v2_to_v3(&mut value);
+    }
+
+    // Unmigrated files are checked from the text, for line and column numbers
+    let project: ProjectFile = if version == CURRENT_VERSION {
+        let mut deserializer = serde_json::Deserializer::from_str(json);
+        serde_path_to_error::deserialize(&mut deserializer).map_err(|e| ProjectError {
+            path: Some(e.path().to_string()),
+            line: Some(e.inner().line()),
+            column: Some(e.inner().column()),
+            message: schema_message(e.inner()),
+        })?
+    } else {
+        serde_path_to_error::deserialize(&value).map_err(|e| ProjectError {
+            path: Some(e.path().to_string()),
+            line: None,
+            column: None,
+            message: schema_message(e.inner()),
+        })?
+    };
+
+    let json = if version == CURRENT_VERSION {
+        json.to_string()
+    } else {
+        serde_json::to_string(&value).map_err(|e| ProjectError::new(None, e.to_string()))?
+    };
+    Ok(LoadedProject {
+        json,
+        version,
+        issues: check_references(&project),
+    })
+}
+
+/// `load` as a report, for tools that only check files
+pub fn validate(json: &str) -> ValidationReport {
+    match load(json) {
+        Ok(project) => ValidationReport {
+            valid: true,
+            version: Some(project.version),
+            migrated: project.migrated(),
+            error: None,
+            issues: project.issues,
+        },
+        Err(error) => ValidationReport {
+            valid: false,
+            version: serde_json::from_str::<Value>(json)
+                .ok()
+                .and_then(|v| v.get("version")?.as_u64()),
+            migrated: false,
+            error: Some(error),
+            issues: Vec::new(),
+        },
+    }
+}
+
+impl ValidationReport {
+    /// Report for a file that could not be read at all
+    pub fn unreadable(message: String) -> Self {
+        Self {
+            valid: false,
+            version: None,
+            migrated: false,
+            error: Some(ProjectError::new(None, message)),
+            issues: Vec::new(),
+        }
+    }
+}
+
+/// serde_json appends the position to its messages; it is reported separately
+fn schema_message(e: &serde_json::Error) -> String {
+    let message = e.to_string();
+    match message.rfind(" at line ") {
+        Some(index) => message[..index].to_string(),
+        None => message,
+    }
+}
+
+// ============================================================================
+// Migrations (on the raw JSON, like the webview does)
+// ============================================================================
+
+/// `migrateV1ToV2`: one drawing holding all shapes and layers
+fn migrate_v1_to_v2(value: &mut Value) -> Result<(), ProjectError> {
+    let project = value
+        .as_object_mut()
+        .ok_or_else(|| ProjectError::new(None, "Project is not a JSON object"))?;
+    let drawing_id = generate_id();
+    let now = now_iso();
+    let created_at = project.get("createdAt").cloned().unwrap_or(Value::Null);
+
+    for key in ["shapes", "layers"] {
+        match project.get_mut(key) {
+            Some(Value::Array(items)) => {
+                for item in items.iter_mut().filter_map(Value::as_object_mut) {
+                    item.insert("drawingId".to_string(), Value::String(drawing_id.clone()));
+                }
+            }
+            _ => return Err(ProjectError::new(Some(key), "Missing or invalid list")),
+        }
+    }
+
+    let viewport = project.remove("viewport").unwrap_or(Value::Null);
+    project.insert("version".to_string(), json!(2));
+    project.insert("modifiedAt".to_string(), json!(now));
+    project.insert(
+        "drawings".to_string(),
+        json!([{
+            "id": drawing_id,
+            "name": "Drawing 1",
+            "boundary": { "x": -500, "y": -500, "width": 1000, "height": 1000 },
+            "scale": DEFAULT_DRAWING_SCALE,
+            "drawingType": "standalone",
+            "createdAt": created_at,
+            "modifiedAt": now,
+        }]),
+    );
+    project.insert("sheets".to_string(), json!([]));
+    project.insert("activeDrawingId".to_string(), json!(drawing_id));
+    project.insert("activeSheetId".to_string(), Value::Null);
+    let mut viewports = Map::new();
+    viewports.insert(drawing_id, viewport);
+    project.insert("drawingViewports".to_string(), Value::Object(viewports));
+    project.insert("sheetViewports".to_string(), json!({}));
+    Ok(())
+}
+
+/// `migrateV2ToV3`: annotation text and dimension styles in paper millimetres,
+/// Osifont instead of Arial and a line height of 1.4
+fn migrate_v2_to_v3(value: &mut Value) {
+    let Some(project) = value.as_object_mut() else {
+        return;
+    };
+    if let Some(Value::Array(shapes)) = project.get_mut("shapes") {
+        for shape in shapes.iter_mut().filter_map(Value::as_object_mut) {
+            match shape.get("type").and_then(Value::as_str) {
+                Some("text") => migrate_text(shape),
+                Some("dimension") => {
+                    if let Some(Value::Object(style)) = shape.get_mut("dimensionStyle") {
+                        for key in [
+                            "textHeight",
+                            "arrowSize",
+                            "extensionLineGap",
+                            "extensionLineOvershoot",
+                        ] {
+                            scale_number(style, key, REFERENCE_SCALE);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    // Title block sizes are in paper mm already; only the font changes
+    if let Some(Value::Array(sheets)) = project.get_mut("sheets") {
+        let fields = sheets
+            .iter_mut()
+            .filter_map(|sheet| {
+                sheet
+                    .get_mut("titleBlock")?
+                    .get_mut("fields")?
+                    .as_array_mut()
+            })
+            .flatten()
+            .filter_map(Value::as_object_mut);
+        for field in fields {
+            replace_font(field);
+        }
+    }
+    project.insert("version".to_string(), json!(3));
+}
+
+fn migrate_text(shape: &mut Map<String, Value>) {
+    let is_model = shape.get("isModelText").and_then(Value::as_bool) == Some(true);
+    if !is_model {
+        scale_number(shape, "fontSize", REFERENCE_SCALE);
+    }
+    replace_font(shape);
+    if shape.get("lineHeight").and_then(Value::as_f64) == Some(1.2) {
+        shape.insert("lineHeight".to_string(), json!(1.4));
+    }
+}
+
+fn replace_font(object: &mut Map<String, Value>) {
+    if object.get("fontFamily").and_then(Value::as_str) == Some("Arial") {
+        object.insert("fontFamily".to_string(), json!(DEFAULT_FONT));
+    }
+}
+
+fn scale_number(object: &mut Map<String, Value>, key: &str, factor: f64) {
+    if let Some(number) = object.get(key).and_then(Value::as_f64) {
+        object.insert(key.to_string(), json!(number * factor));
+    }
+}
+
+/// Same shape as `generateId` in `fileService.ts`
+fn generate_id() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let mut random = u64::from(now.subsec_nanos()) ^ (u64::from(std::process::id()) << 20);
+    let suffix: String = (0..9)
+        .map(|_| {
+            random = random
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            std::char::from_digit(((random >> 33) % 36) as u32, 36).unwrap_or('0')
+        })
+        .collect();
+    format!("{}-{}", now.as_millis(), suffix)
+}
+
+/// Current UTC time as `YYYY-MM-DDTHH:MM:SS.mmmZ`, like `Date.toISOString`
+fn now_iso() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = now.as_secs();
+    let (days, rem) = (secs / 86400, secs % 86400);
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60,
+        now.subsec_millis()
+    )
+}
+
+// ============================================================================
+// References
+// ============================================================================
+
+fn check_references(project: &ProjectFile) -> Vec<ProjectIssue> {
+    let mut issues = Vec::new();
+    let mut issue = |path: String, message: String| issues.push(ProjectIssue { path, message });
+
+    let drawings = unique_ids(
+        project.drawings.iter().map(|d| d.id.as_str()),
+        "drawings",
+        "drawing",
+        &mut issue,
+    );
+    if project.drawings.is_empty() {
+        issue(
+            "drawings".to_string(),
+            "Project has no drawings".to_string(),
+        );
+    }
+    let sheets = unique_ids(
+        project.sheets.iter().map(|s| s.id.as_str()),
+        "sheets",
+        "sheet",
+        &mut issue,
+    );
+
+    let mut layers: HashMap<&str, &str> = HashMap::new();
+    for (index, layer) in project.layers.iter().enumerate() {
+        if layers.insert(&layer.id, &layer.drawing_id).is_some() {
+            issue(
+                format!("layers[{}].id", index),
+                format!("Duplicate layer ID {}", layer.id),
+            );
+        }
+        if !drawings.contains(layer.drawing_id.as_str()) {
+            issue(
+                format!("layers[{}].drawingId", index),
+                format!("Unknown drawing {}", layer.drawing_id),
+            );
+        }
+    }
+
+    let mut shapes = HashSet::new();
+    for (index, shape) in project.shapes.iter().enumerate() {
+        if !shapes.insert(shape.id.as_str()) {
+            issue(
+                format!("shapes[{}].id", index),
+                format!("Duplicate shape ID {}", shape.id),
+            );
+        }
+        if !drawings.contains(shape.drawing_id.as_str()) {
+            issue(
+                format!("shapes[{}].drawingId", index),
+                format!("Unknown drawing {}", shape.drawing_id),
+            );
+        }
+        match layers.get(shape.layer_id.as_str()) {
+            None => issue(
+                format!("shapes[{}].layerId", index),
+                format!("Unknown layer {}", shape.layer_id),
+            ),
+            Some(&drawing) if drawing != shape.drawing_id => issue(
+                format!("shapes[{}].layerId", index),
+                format!("Layer {} belongs to another drawing", shape.layer_id),
+            ),
+            Some(_) => {}
+        }
+    }
+
+    for (sheet_index, sheet) in project.sheets.iter().enumerate() {
+        for (index, viewport) in sheet.viewports.iter().enumerate() {
+            let path = format!("sheets[{}].viewports[{}]", sheet_index, index);
+            if !drawings.contains(viewport.drawing_id.as_str()) {
+                issue(
+                    format!("{}.drawingId", path),
+                    format!("Unknown drawing {}", viewport.drawing_id),
+                );
+            }
+            for (override_index, layer_override) in viewport.layer_overrides.iter().enumerate() {
+                if !layers.contains_key(layer_override.layer_id.as_str()) {
+                    issue(
+                        format!("{}.layerOverrides[{}].layerId", path, override_index),
+                        format!("Unknown layer {}", layer_override.layer_id),
+                    );
+                }
+            }
+        }
+    }
+
+    if let Some(id) = &project.active_drawing_id {
+        if !drawings.contains(id.as_str()) {
+            issue(
+                "activeDrawingId".to_string(),
+                format!("Unknown drawing {}", id),
+            );
+        }
+    }
+    if let Some(id) = &project.active_sheet_id {
+        if !sheets.contains(id.as_str()) {
+            issue("activeSheetId".to_string(), format!("Unknown sheet {}", id));
+        }
+    }
+    if let Some(id) = &project.active_layer_id {
+        if !layers.contains_key(id.as_str()) {
+            issue("activeLayerId".to_string(), format!("Unknown layer {}", id));
+        }
+    }
+    for id in project.drawing_viewports.keys() {
+        if !drawings.contains(id.as_str()) {
+            issue(
+                format!("drawingViewports.{}", id),
+                format!("Unknown drawing {}", id),
+            );
+        }
+    }
+    for id in project.sheet_viewports.keys() {
+        if !sheets.contains(id.as_str()) {
+            issue(
+                format!("sheetViewports.{}", id),
+                format!("Unknown sheet {}", id),
+            );
+        }
+    }
+    issues
+}
+
+/// Collect IDs, reporting duplicates
+fn unique_ids<'a>(
+    ids: impl Iterator<Item = &'a str>,
+    list: &str,
+    what: &str,
+    issue: &mut impl FnMut(String, String),
+) -> HashSet<&'a str> {
+    let mut seen = HashSet::new();
+    for (index, id) in ids.enumerate() {
+        if !seen.insert(id) {
+            issue(
+                format!("{}[{}].id", list, index),
+                format!("Duplicate {} ID {}", what, id),
+            );
+        }
+    }
+    seen
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v1_project() -> Value {
+        json!({
+            "version": 1,
+            "name": "Plan",
+            "createdAt": "2020-01-01T00:00:00.000Z",
+            "shapes": [{ "id": "s1", "type": "line", "layerId": "l1",
+                         "start": { "x": 0, "y": 0 }, "end": { "x": 1, "y": 0 } }],
+            "layers": [{ "id": "l1", "name": "Layer 0" }],
+            "activeLayerId": "l1",
+            "viewport": { "offsetX": 1, "offsetY": 2, "zoom": 3 },
+        })
+    }
+
+    #[test]
+    fn migrate_v1_to_v2_puts_everything_in_one_drawing() {
+        let mut project = v1_project();
+        migrate_v1_to_v2(&mut project).unwrap();
+
+        let drawings = project["drawings"].as_array().unwrap();
+        assert_eq!(drawings.len(), 1);
+        let id = &drawings[0]["id"];
+        assert_eq!(drawings[0]["createdAt"], "2020-01-01T00:00:00.000Z");
+        assert_eq!(&project["shapes"][0]["drawingId"], id);
+        assert_eq!(&project["layers"][0]["drawingId"], id);
+        assert_eq!(&project["activeDrawingId"], id);
+        let viewport = &project["drawingViewports"][id.as_str().unwrap()];
+        assert_eq!(viewport["zoom"], 3);
+        assert!(project.get("viewport").is_none());
+        assert_eq!(project["version"], 2);
+    }
+
+    #[test]
+    fn migrate_v1_to_v2_needs_shapes_and_layers() {
+        let mut project = v1_project();
+        project.as_object_mut().unwrap().remove("layers");
+        let error = migrate_v1_to_v2(&mut project).unwrap_err();
+        assert_eq!(error.path.as_deref(), Some("layers"));
+    }
+
+    #[test]
+    fn migrate_v2_to_v3_scales_annotation_text() {
+        let mut project = json!({
+            "version": 2,
+            "shapes": [
+                { "type": "text", "fontSize": 100.0, "fontFamily": "Arial", "lineHeight": 1.2 },
+                { "type": "text", "fontSize": 100.0, "isModelText": true, "fontFamily": "Courier" },
+                { "type": "dimension", "dimensionStyle": { "textHeight": 250.0, "arrowSize": 150.0 } },
+            ],
+            "sheets": [{ "titleBlock": { "fields": [{ "fontFamily": "Arial" }] } }],
+        });
+        migrate_v2_to_v3(&mut project);
+
+        let shapes = &project["shapes"];
+        assert_eq!(shapes[0]["fontSize"], 2.0);
+        assert_eq!(shapes[0]["fontFamily"], DEFAULT_FONT);
+        assert_eq!(shapes[0]["lineHeight"], 1.4);
+        assert_eq!(shapes[1]["fontSize"], 100.0);
+        assert_eq!(shapes[1]["fontFamily"], "Courier");
+        assert_eq!(shapes[2]["dimensionStyle"]["textHeight"], 5.0);
+        assert_eq!(shapes[2]["dimensionStyle"]["arrowSize"], 3.0);
+        assert_eq!(
+            project["sheets"][0]["titleBlock"]["fields"][0]["fontFamily"],
+            DEFAULT_FONT
+        );
+        assert_eq!(project["version"], 3);
+    }
+
+    #[test]
+    fn load_migrates_v1_to_the_current_version() {
+        let project = load(&v1_project().to_string()).unwrap();
+        assert_eq!(project.version, 1);
+        assert!(project.migrated());
+        assert!(project.issues.is_empty(), "{:?}", project.issues);
+        let json: Value = serde_json::from_str(&project.json).unwrap();
+        assert_eq!(json["version"], CURRENT_VERSION);
+    }
+
+    #[test]
+    fn load_rejects_newer_versions() {
+        let error = load(r#"{"version": 99}"#).unwrap_err();
+        assert_eq!(error.path.as_deref(), Some("version"));
+    }
+
+    #[test]
+    fn load_reports_where_the_schema_breaks() {
+        let mut project = v1_project();
+        migrate_v1_to_v2(&mut project).unwrap();
+        migrate_v2_to_v3(&mut project);
+        project["shapes"][0]["start"]["x"] = json!("zero");
+        let error = load(&serde_json::to_string_pretty(&project).unwrap()).unwrap_err();
+        assert_eq!(error.path.as_deref(), Some("shapes[0].start.x"));
+        assert!(error.line.is_some());
+    }
+}